@@ -1,8 +1,12 @@
 use async_trait::async_trait;
 
 use crate::types::{
-    AvailableCustomCommands, DatasetFullMountState, DatasetMountedResponse, DatasetsFullMountState,
-    KeyLoadedResponse, RunCommandOutput,
+    AvailableCustomCommands, BootStatus, CommandHistory, CommandOutputChunk, CommandStatuses,
+    CustomCommandRunOptions, DatasetFullMountState, DatasetMountOptions, DatasetMountedResponse,
+    DatasetsFullMountState, ImportablePools, JobCancelledResponse, JobId, JobOutput,
+    JobStartedResponse, JobStatus, JobsList, KeyLoadedResponse, PassphraseVerifiedResponse,
+    PoolImportOptions, PoolImportedResponse, RunCommandOutput, SchedulesStatus,
+    UnlockGroupResponse, UnlockGroups,
 };
 
 use super::{
@@ -44,6 +48,13 @@ impl ZfsRemoteAPI for ApiAny {
         }
     }
 
+    fn events_url(&self) -> Option<String> {
+        match self {
+            ApiAny::Live(e) => e.events_url(),
+            ApiAny::Mock(e) => e.events_url(),
+        }
+    }
+
     async fn encrypted_datasets_state(&self) -> Result<DatasetsFullMountState, Self::Error> {
         match self {
             ApiAny::Live(e) => e.encrypted_datasets_state().await.map_err(Into::into),
@@ -78,13 +89,86 @@ impl ZfsRemoteAPI for ApiAny {
         }
     }
 
+    async fn verify_passphrase(
+        &self,
+        dataset_name: &str,
+        password: &str,
+    ) -> Result<PassphraseVerifiedResponse, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e
+                .verify_passphrase(dataset_name, password)
+                .await
+                .map_err(Into::into),
+            ApiAny::Mock(e) => e
+                .verify_passphrase(dataset_name, password)
+                .await
+                .map_err(Into::into),
+        }
+    }
+
     async fn mount_dataset(
         &mut self,
         dataset_name: &str,
+        options: &DatasetMountOptions,
     ) -> Result<DatasetMountedResponse, Self::Error> {
         match self {
-            ApiAny::Live(e) => e.mount_dataset(dataset_name).await.map_err(Into::into),
-            ApiAny::Mock(e) => e.mount_dataset(dataset_name).await.map_err(Into::into),
+            ApiAny::Live(e) => e
+                .mount_dataset(dataset_name, options)
+                .await
+                .map_err(Into::into),
+            ApiAny::Mock(e) => e
+                .mount_dataset(dataset_name, options)
+                .await
+                .map_err(Into::into),
+        }
+    }
+
+    async fn importable_pools(&self) -> Result<ImportablePools, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e.importable_pools().await.map_err(Into::into),
+            ApiAny::Mock(e) => e.importable_pools().await.map_err(Into::into),
+        }
+    }
+
+    async fn import_pool(
+        &mut self,
+        pool_name: &str,
+        options: &PoolImportOptions,
+    ) -> Result<PoolImportedResponse, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e.import_pool(pool_name, options).await.map_err(Into::into),
+            ApiAny::Mock(e) => e.import_pool(pool_name, options).await.map_err(Into::into),
+        }
+    }
+
+    async fn unlock_groups(&self) -> Result<UnlockGroups, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e.unlock_groups().await.map_err(Into::into),
+            ApiAny::Mock(e) => e.unlock_groups().await.map_err(Into::into),
+        }
+    }
+
+    async fn unlock_group(
+        &mut self,
+        group_name: &str,
+        password: &str,
+    ) -> Result<UnlockGroupResponse, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e
+                .unlock_group(group_name, password)
+                .await
+                .map_err(Into::into),
+            ApiAny::Mock(e) => e
+                .unlock_group(group_name, password)
+                .await
+                .map_err(Into::into),
+        }
+    }
+
+    async fn boot_status(&self) -> Result<BootStatus, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e.boot_status().await.map_err(Into::into),
+            ApiAny::Mock(e) => e.boot_status().await.map_err(Into::into),
         }
     }
 
@@ -98,19 +182,104 @@ impl ZfsRemoteAPI for ApiAny {
     async fn call_custom_command(
         &mut self,
         endpoint: &str,
-        stdin: Option<&str>,
+        options: &CustomCommandRunOptions,
     ) -> Result<RunCommandOutput, Self::Error> {
         match self {
             ApiAny::Live(e) => e
-                .call_custom_command(endpoint, stdin)
+                .call_custom_command(endpoint, options)
                 .await
                 .map_err(Into::into),
             ApiAny::Mock(e) => e
-                .call_custom_command(endpoint, stdin)
+                .call_custom_command(endpoint, options)
                 .await
                 .map_err(Into::into),
         }
     }
+
+    async fn call_custom_command_streaming(
+        &mut self,
+        endpoint: &str,
+        options: &CustomCommandRunOptions,
+        on_started: &dyn Fn(JobId),
+        on_output: &dyn Fn(CommandOutputChunk),
+    ) -> Result<RunCommandOutput, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e
+                .call_custom_command_streaming(endpoint, options, on_started, on_output)
+                .await
+                .map_err(Into::into),
+            ApiAny::Mock(e) => e
+                .call_custom_command_streaming(endpoint, options, on_started, on_output)
+                .await
+                .map_err(Into::into),
+        }
+    }
+
+    async fn cancel_job(&mut self, job_id: JobId) -> Result<JobCancelledResponse, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e.cancel_job(job_id).await.map_err(Into::into),
+            ApiAny::Mock(e) => e.cancel_job(job_id).await.map_err(Into::into),
+        }
+    }
+
+    async fn start_custom_command_job(
+        &mut self,
+        endpoint: &str,
+        options: &CustomCommandRunOptions,
+    ) -> Result<JobStartedResponse, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e
+                .start_custom_command_job(endpoint, options)
+                .await
+                .map_err(Into::into),
+            ApiAny::Mock(e) => e
+                .start_custom_command_job(endpoint, options)
+                .await
+                .map_err(Into::into),
+        }
+    }
+
+    async fn list_jobs(&self) -> Result<JobsList, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e.list_jobs().await.map_err(Into::into),
+            ApiAny::Mock(e) => e.list_jobs().await.map_err(Into::into),
+        }
+    }
+
+    async fn job_status(&self, job_id: JobId) -> Result<JobStatus, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e.job_status(job_id).await.map_err(Into::into),
+            ApiAny::Mock(e) => e.job_status(job_id).await.map_err(Into::into),
+        }
+    }
+
+    async fn job_output(&self, job_id: JobId) -> Result<JobOutput, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e.job_output(job_id).await.map_err(Into::into),
+            ApiAny::Mock(e) => e.job_output(job_id).await.map_err(Into::into),
+        }
+    }
+
+    async fn schedule_status(&self) -> Result<SchedulesStatus, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e.schedule_status().await.map_err(Into::into),
+            ApiAny::Mock(e) => e.schedule_status().await.map_err(Into::into),
+        }
+    }
+
+    async fn command_history(&self, endpoint: &str) -> Result<CommandHistory, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e.command_history(endpoint).await.map_err(Into::into),
+            ApiAny::Mock(e) => e.command_history(endpoint).await.map_err(Into::into),
+        }
+    }
+
+    async fn command_statuses(&self) -> Result<CommandStatuses, Self::Error> {
+        match self {
+            ApiAny::Live(e) => e.command_statuses().await.map_err(Into::into),
+            ApiAny::Mock(e) => e.command_statuses().await.map_err(Into::into),
+        }
+    }
 }
 
 impl From<ApiRouteImpl> for ApiAny {
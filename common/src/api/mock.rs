@@ -8,8 +8,15 @@ use async_trait::async_trait;
 use crate::{
     config::{MockSettings, MockedCustomCommandConfig},
     types::{
-        AvailableCustomCommands, CustomCommandPublicInfo, DatasetFullMountState,
-        DatasetMountedResponse, DatasetsFullMountState, KeyLoadedResponse, RunCommandOutput,
+        AvailableCustomCommands, BootState, BootStatus, CommandHistory, CommandHistoryEntry,
+        CommandKind, CommandOutputChunk, CommandPreconditions, CommandStatus, CommandStatuses,
+        CustomCommandPublicInfo, CustomCommandRunOptions, DatasetFullMountState,
+        DatasetMountOptions, DatasetMountedResponse, DatasetsFullMountState, ImportablePool,
+        ImportablePools, JobCancelledResponse, JobId, JobOutput, JobStartedResponse, JobState,
+        JobStatus, JobsList, KeyLoadedResponse, OutputEncoding, OutputStream,
+        PassphraseVerifiedResponse, PoolImportOptions, PoolImportedResponse, RunCommandOutput,
+        SchedulesStatus, UnlockGroup, UnlockGroupMemberOutcome, UnlockGroupResponse, UnlockGroups,
+        DEFAULT_STATUS_INTERVAL_SECS,
     },
 };
 
@@ -27,6 +34,18 @@ pub enum ApiMockError {
     SimulatedError(String),
     #[error("Custom command not found: {0}")]
     CustomCommandNotFound(String),
+    #[error("Pool not available for import: {0}")]
+    PoolNotImportable(String),
+    #[error("Unlock group not found: {0}")]
+    UnlockGroupNotFound(String),
+    #[error("Job not found: {0}")]
+    JobNotFound(JobId),
+    #[error("Custom command must be confirmed: {0}")]
+    ConfirmationRequired(String),
+    #[error("The stdin of custom command {0} is too large")]
+    StdinTooLarge(String),
+    #[error("Custom command {0} can't run yet: {1}")]
+    PreconditionNotMet(String, String),
 }
 
 #[derive(Debug, Clone)]
@@ -43,12 +62,20 @@ pub struct MockCustomCommandDetails {
     expected_stdout: String,
     expected_stderr: String,
     expected_error_code: i32,
+    exit_code_meaning: Option<String>,
     call_counter: u64,
+    /// The calls, newest first
+    history: Vec<CommandHistoryEntry>,
+    /// The position in the config, to list the commands in the same order
+    position: usize,
 }
 
 struct ApiMockInner {
     state: BTreeMap<String, MockDatasetDetails>,
+    importable_pools: BTreeMap<String, ImportablePool>,
+    unlock_groups: Vec<UnlockGroup>,
     available_commands: BTreeMap<String, MockCustomCommandDetails>,
+    jobs: BTreeMap<JobId, JobOutput>,
 }
 
 #[derive(Clone)]
@@ -62,14 +89,29 @@ impl ApiMock {
             .custom_commands
             .unwrap_or_default()
             .into_iter()
+            .enumerate()
             .map(
-                |MockedCustomCommandConfig {
-                     unique_label,
-                     expected_stdout,
-                     expected_stderr,
-                     expected_error_code,
-                     stdin: stdin_config,
-                 }| {
+                |(
+                    position,
+                    MockedCustomCommandConfig {
+                        unique_label,
+                        expected_stdout,
+                        expected_stderr,
+                        expected_error_code,
+                        exit_code_meaning,
+                        stdin: stdin_config,
+                        parameters,
+                        group,
+                        order,
+                        description,
+                        icon,
+                        confirm,
+                        kind,
+                        status_interval_secs,
+                        requires_mounted,
+                        requires_key_loaded,
+                    },
+                )| {
                     (
                         unique_label.clone(),
                         MockCustomCommandDetails {
@@ -79,11 +121,32 @@ impl ApiMock {
                                 stdin_allow: stdin_config.is_stdin_enabled(),
                                 stdin_text_placeholder: stdin_config.stdin_placeholder_text(),
                                 stdin_is_password: stdin_config.is_password(),
+                                stdin_mode: stdin_config.mode(),
+                                stdin_max_bytes: stdin_config.max_bytes(),
+                                parameters,
+                                busy: false,
+                                schedule: None,
+                                group,
+                                order,
+                                description,
+                                icon,
+                                confirm,
+                                kind,
+                                status_interval_secs: (kind == CommandKind::Status).then(|| {
+                                    status_interval_secs.unwrap_or(DEFAULT_STATUS_INTERVAL_SECS)
+                                }),
+                                preconditions: CommandPreconditions {
+                                    requires_mounted,
+                                    requires_key_loaded,
+                                },
                             },
                             expected_stdout,
                             expected_stderr,
                             expected_error_code,
+                            exit_code_meaning,
                             call_counter: 0,
+                            history: Vec::new(),
+                            position,
                         },
                     )
                 },
@@ -110,9 +173,29 @@ impl ApiMock {
             })
             .collect();
 
+        let importable_pools = config
+            .importable_pools
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, pool_name)| {
+                (
+                    pool_name.clone(),
+                    ImportablePool {
+                        pool_name,
+                        pool_id: (idx + 1).to_string(),
+                        state: "ONLINE".to_string(),
+                    },
+                )
+            })
+            .collect();
+
         let result = ApiMockInner {
             state,
+            importable_pools,
+            unlock_groups: config.unlock_groups.unwrap_or_default(),
             available_commands: cmds,
+            jobs: BTreeMap::new(),
         };
 
         Self {
@@ -129,6 +212,10 @@ impl ZfsRemoteAPI for ApiMock {
         Ok(())
     }
 
+    fn events_url(&self) -> Option<String> {
+        None
+    }
+
     async fn encrypted_datasets_state(&self) -> Result<DatasetsFullMountState, Self::Error> {
         sleep_for_dramatic_effect().await;
 
@@ -168,15 +255,41 @@ impl ZfsRemoteAPI for ApiMock {
             Ok(KeyLoadedResponse {
                 dataset_name: dataset_name.to_string(),
                 key_loaded: true,
+                hooks: Vec::new(),
             })
         } else {
             Err(ApiMockError::InvalidEncryptionPassword)
         }
     }
 
+    async fn verify_passphrase(
+        &self,
+        dataset_name: &str,
+        password: &str,
+    ) -> Result<PassphraseVerifiedResponse, Self::Error> {
+        sleep_for_dramatic_effect().await;
+
+        let inner = self.inner.lock().expect("Poisoned mutex");
+
+        let dataset_details = inner
+            .state
+            .get(dataset_name)
+            .ok_or(ApiMockError::DatasetNotFound(dataset_name.to_string()))?;
+
+        if random_0_to_1_float() < dataset_details.error_probability {
+            return Err(ApiMockError::SimulatedError(dataset_name.to_string()));
+        }
+
+        Ok(PassphraseVerifiedResponse {
+            dataset_name: dataset_name.to_string(),
+            passphrase_correct: password == dataset_details.unlock_password,
+        })
+    }
+
     async fn mount_dataset(
         &mut self,
         dataset_name: &str,
+        _options: &DatasetMountOptions,
     ) -> Result<DatasetMountedResponse, Self::Error> {
         sleep_for_dramatic_effect().await;
 
@@ -195,6 +308,7 @@ impl ZfsRemoteAPI for ApiMock {
         Ok(DatasetMountedResponse {
             dataset_name: dataset_name.to_string(),
             is_mounted: true,
+            hooks: Vec::new(),
         })
     }
 
@@ -218,46 +332,199 @@ impl ZfsRemoteAPI for ApiMock {
         Ok(dataset_details.state.clone())
     }
 
+    async fn importable_pools(&self) -> Result<ImportablePools, Self::Error> {
+        sleep_for_dramatic_effect().await;
+
+        let inner = self.inner.lock().expect("Poisoned mutex");
+
+        Ok(ImportablePools {
+            pools: inner.importable_pools.clone(),
+        })
+    }
+
+    async fn import_pool(
+        &mut self,
+        pool_name: &str,
+        _options: &PoolImportOptions,
+    ) -> Result<PoolImportedResponse, Self::Error> {
+        sleep_for_dramatic_effect().await;
+
+        let mut inner = self.inner.lock().expect("Poisoned mutex");
+
+        inner
+            .importable_pools
+            .remove(pool_name)
+            .ok_or(ApiMockError::PoolNotImportable(pool_name.to_string()))?;
+
+        Ok(PoolImportedResponse {
+            pool_name: pool_name.to_string(),
+            is_imported: true,
+        })
+    }
+
+    async fn unlock_groups(&self) -> Result<UnlockGroups, Self::Error> {
+        sleep_for_dramatic_effect().await;
+
+        let inner = self.inner.lock().expect("Poisoned mutex");
+
+        Ok(UnlockGroups {
+            groups: inner.unlock_groups.clone(),
+        })
+    }
+
+    async fn unlock_group(
+        &mut self,
+        group_name: &str,
+        password: &str,
+    ) -> Result<UnlockGroupResponse, Self::Error> {
+        let group = self
+            .inner
+            .lock()
+            .expect("Poisoned mutex")
+            .unlock_groups
+            .iter()
+            .find(|g| g.name == group_name)
+            .cloned()
+            .ok_or(ApiMockError::UnlockGroupNotFound(group_name.to_string()))?;
+
+        let mut outcomes = BTreeMap::new();
+
+        for dataset_name in &group.datasets {
+            let mut outcome = UnlockGroupMemberOutcome::default();
+
+            match self.load_key(dataset_name, password).await {
+                Ok(_) => outcome.key_loaded = true,
+                Err(e) => outcome.error = Some(e.to_string()),
+            }
+
+            if outcome.key_loaded && group.mount_after {
+                match self
+                    .mount_dataset(dataset_name, &DatasetMountOptions::default())
+                    .await
+                {
+                    Ok(_) => outcome.is_mounted = true,
+                    Err(e) => outcome.error = Some(e.to_string()),
+                }
+            } else {
+                outcome.is_mounted = self
+                    .inner
+                    .lock()
+                    .expect("Poisoned mutex")
+                    .state
+                    .get(dataset_name)
+                    .map(|ds| ds.state.is_mounted)
+                    .unwrap_or(false);
+            }
+
+            outcomes.insert(dataset_name.to_string(), outcome);
+        }
+
+        Ok(UnlockGroupResponse {
+            group_name: group_name.to_string(),
+            outcomes,
+        })
+    }
+
+    /// The mock has no boot orchestration, so the state is derived from the datasets
+    async fn boot_status(&self) -> Result<BootStatus, Self::Error> {
+        let inner = self.inner.lock().expect("Poisoned mutex");
+
+        let locked_datasets = inner
+            .state
+            .iter()
+            .filter(|(_, ds)| !ds.state.key_loaded)
+            .map(|(ds_name, _)| ds_name.to_string())
+            .collect::<Vec<_>>();
+
+        let state = if locked_datasets.is_empty() {
+            BootState::Ready
+        } else {
+            BootState::WaitingForUnlock { locked_datasets }
+        };
+
+        Ok(BootStatus {
+            state,
+            notifications: Vec::new(),
+            startup_commands: Vec::new(),
+        })
+    }
+
     async fn list_available_commands(&self) -> Result<AvailableCustomCommands, Self::Error> {
         sleep_for_dramatic_effect().await;
 
         let inner = self.inner.lock().expect("Poisoned mutex");
 
+        let mut commands = inner.available_commands.values().collect::<Vec<_>>();
+        commands.sort_by_key(|c| (c.cmd.order, c.position));
+
         Ok(AvailableCustomCommands {
-            commands: inner
-                .available_commands
-                .values()
-                .map(|c| c.cmd.clone())
-                .collect(),
+            commands: commands.into_iter().map(|c| c.cmd.clone()).collect(),
         })
     }
 
     async fn call_custom_command(
         &mut self,
         endpoint: &str,
-        stdin: Option<&str>,
+        options: &CustomCommandRunOptions,
     ) -> Result<RunCommandOutput, Self::Error> {
         sleep_for_dramatic_effect().await;
 
         let mut inner = self.inner.lock().expect("Poisoned mutex");
 
+        let dataset_states = inner
+            .state
+            .iter()
+            .map(|(name, details)| (name.clone(), details.state.clone()))
+            .collect::<BTreeMap<_, _>>();
+
         let cmd = inner
             .available_commands
             .get_mut(endpoint)
             .ok_or(ApiMockError::CustomCommandNotFound(endpoint.to_string()))?;
 
+        let unmet = cmd.cmd.preconditions.unmet(&dataset_states);
+        if !unmet.is_empty() {
+            return Err(ApiMockError::PreconditionNotMet(
+                endpoint.to_string(),
+                unmet.join("; "),
+            ));
+        }
+
+        if let Some(confirm) = &cmd.cmd.confirm {
+            if options.confirmation.as_deref() != Some(confirm.token(endpoint)) {
+                return Err(ApiMockError::ConfirmationRequired(endpoint.to_string()));
+            }
+        }
+
+        // Base64 takes 4 characters for every 3 bytes
+        let stdin_bytes = match options.stdin_encoding {
+            OutputEncoding::Text => options.stdin.as_ref().map(|s| s.len()),
+            OutputEncoding::Base64 => options.stdin.as_ref().map(|s| s.len() / 4 * 3),
+        };
+        if stdin_bytes.unwrap_or_default() > cmd.cmd.stdin_max_bytes {
+            return Err(ApiMockError::StdinTooLarge(endpoint.to_string()));
+        }
+
         cmd.call_counter += 1;
 
-        match stdin {
-            Some(s) => Ok(RunCommandOutput {
+        let stdin = match options.stdin_encoding {
+            OutputEncoding::Text => options.stdin.clone(),
+            OutputEncoding::Base64 => options
+                .stdin
+                .as_ref()
+                .map(|s| format!("a file of {} base64 characters", s.len())),
+        };
+        let mut output = match stdin.as_deref() {
+            Some(s) => RunCommandOutput {
                 stdout: format!(
                     "{} - {} - piped: {s}",
                     cmd.expected_stdout, cmd.call_counter
                 ),
                 stderr: format!("{} - {}", cmd.expected_stderr, cmd.call_counter),
                 error_code: cmd.expected_error_code,
-            }),
-            None => Ok(RunCommandOutput {
+                ..Default::default()
+            },
+            None => RunCommandOutput {
                 stdout: if cmd.expected_stdout.is_empty() {
                     String::new()
                 } else {
@@ -275,11 +542,191 @@ impl ZfsRemoteAPI for ApiMock {
                     )
                 },
                 error_code: cmd.expected_error_code,
-            }),
+                ..Default::default()
+            },
+        };
+
+        for (name, value) in &options.parameters {
+            output.stdout += &format!("\n{name} = {value}");
+        }
+        output.exit_code_meaning = cmd.exit_code_meaning.clone();
+        if cmd.cmd.kind == CommandKind::Status {
+            output.status_ok = Some(output.error_code == 0);
+        }
+
+        cmd.history.insert(
+            0,
+            CommandHistoryEntry {
+                started_at_unix_secs: now_unix_secs(),
+                duration_ms: 0,
+                caller: "mock".to_string(),
+                stdin: stdin.filter(|_| !cmd.cmd.stdin_is_password),
+                stdin_truncated: false,
+                parameters: options.parameters.clone(),
+                error_code: Some(output.error_code),
+                error: None,
+                stopped: None,
+                stdout: output.stdout.clone(),
+                stderr: output.stderr.clone(),
+                stdout_truncated: false,
+                stderr_truncated: false,
+            },
+        );
+
+        Ok(output)
+    }
+
+    async fn call_custom_command_streaming(
+        &mut self,
+        endpoint: &str,
+        options: &CustomCommandRunOptions,
+        _on_started: &dyn Fn(JobId),
+        on_output: &dyn Fn(CommandOutputChunk),
+    ) -> Result<RunCommandOutput, Self::Error> {
+        // The mocked runs aren't jobs, as they always finish by themselves
+        let output = self.call_custom_command(endpoint, options).await?;
+
+        // The output arrives line by line, to look like a command that takes its time
+        let lines = output
+            .stdout
+            .lines()
+            .map(|l| (OutputStream::Stdout, l))
+            .chain(output.stderr.lines().map(|l| (OutputStream::Stderr, l)));
+        for (stream, line) in lines {
+            on_output(CommandOutputChunk {
+                stream,
+                data: format!("{line}\n"),
+            });
+            Sleepr::new(STREAMED_LINE_DELAY).sleep().await;
+        }
+
+        Ok(output)
+    }
+
+    async fn cancel_job(&mut self, job_id: JobId) -> Result<JobCancelledResponse, Self::Error> {
+        let inner = self.inner.lock().expect("Poisoned mutex");
+
+        if !inner.jobs.contains_key(&job_id) {
+            return Err(ApiMockError::JobNotFound(job_id));
         }
+
+        // Mocked jobs are already finished when they're started, so there's nothing to cancel
+        Ok(JobCancelledResponse {
+            job_id,
+            cancelled: false,
+        })
+    }
+
+    async fn start_custom_command_job(
+        &mut self,
+        endpoint: &str,
+        options: &CustomCommandRunOptions,
+    ) -> Result<JobStartedResponse, Self::Error> {
+        let started_at_unix_secs = now_unix_secs();
+
+        // The mocked command runs right away, so the job is already finished when it's returned
+        let output = self.call_custom_command(endpoint, options).await?;
+
+        let mut inner = self.inner.lock().expect("Poisoned mutex");
+        let job_id = inner.jobs.keys().next_back().map_or(1, |id| id + 1);
+        inner.jobs.insert(
+            job_id,
+            JobOutput {
+                status: JobStatus {
+                    job_id,
+                    endpoint: endpoint.to_string(),
+                    state: JobState::Finished,
+                    started_at_unix_secs,
+                    finished_at_unix_secs: Some(now_unix_secs()),
+                    error: None,
+                },
+                output,
+            },
+        );
+
+        Ok(JobStartedResponse { job_id })
+    }
+
+    async fn list_jobs(&self) -> Result<JobsList, Self::Error> {
+        let inner = self.inner.lock().expect("Poisoned mutex");
+
+        Ok(JobsList {
+            jobs: inner
+                .jobs
+                .values()
+                .rev()
+                .map(|j| j.status.clone())
+                .collect(),
+        })
+    }
+
+    async fn job_status(&self, job_id: JobId) -> Result<JobStatus, Self::Error> {
+        self.job_output(job_id).await.map(|j| j.status)
+    }
+
+    async fn job_output(&self, job_id: JobId) -> Result<JobOutput, Self::Error> {
+        let inner = self.inner.lock().expect("Poisoned mutex");
+
+        inner
+            .jobs
+            .get(&job_id)
+            .cloned()
+            .ok_or(ApiMockError::JobNotFound(job_id))
+    }
+
+    async fn command_history(&self, endpoint: &str) -> Result<CommandHistory, Self::Error> {
+        let inner = self.inner.lock().expect("Poisoned mutex");
+
+        let cmd = inner
+            .available_commands
+            .get(endpoint)
+            .ok_or(ApiMockError::CustomCommandNotFound(endpoint.to_string()))?;
+
+        Ok(CommandHistory {
+            endpoint: endpoint.to_string(),
+            runs: cmd.history.clone(),
+        })
     }
+
+    async fn schedule_status(&self) -> Result<SchedulesStatus, Self::Error> {
+        // The mocked commands don't run by themselves
+        Ok(SchedulesStatus::default())
+    }
+
+    async fn command_statuses(&self) -> Result<CommandStatuses, Self::Error> {
+        let inner = self.inner.lock().expect("Poisoned mutex");
+
+        // The status commands are always healthy when they're expected to exit with zero
+        let commands = inner
+            .available_commands
+            .iter()
+            .filter(|(_, cmd)| cmd.cmd.kind == CommandKind::Status)
+            .map(|(endpoint, cmd)| CommandStatus {
+                endpoint: endpoint.clone(),
+                checked_at_unix_secs: now_unix_secs(),
+                output: Some(RunCommandOutput {
+                    stdout: cmd.expected_stdout.clone(),
+                    stderr: cmd.expected_stderr.clone(),
+                    error_code: cmd.expected_error_code,
+                    exit_code_meaning: cmd.exit_code_meaning.clone(),
+                    status_ok: Some(cmd.expected_error_code == 0),
+                    ..Default::default()
+                }),
+                error: None,
+            })
+            .collect();
+
+        Ok(CommandStatuses { commands })
+    }
+}
+
+fn now_unix_secs() -> u64 {
+    (js_sys::Date::now() / 1000.) as u64
 }
 
+/// The delay between lines of streamed output, in milliseconds
+const STREAMED_LINE_DELAY: u32 = 300;
+
 async fn sleep_for_dramatic_effect() {
     const SLEEP_DURATION: u32 = 1000;
     Sleepr::new(SLEEP_DURATION).sleep().await;
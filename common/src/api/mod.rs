@@ -1,5 +1,6 @@
 pub mod api_wrapper;
 pub mod mock;
+pub mod multi_host;
 pub mod routed;
 pub mod sleeper;
 pub mod traits;
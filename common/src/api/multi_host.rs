@@ -0,0 +1,39 @@
+use super::traits::ZfsRemoteHighLevel;
+
+/// The API of one machine, with the name it's shown with
+#[derive(Debug, Clone)]
+pub struct Host<A> {
+    pub name: String,
+    pub api: A,
+}
+
+/// Aggregates the APIs of multiple machines. Every host is accessed on its own, with its own API,
+/// so a host that is down doesn't affect the others.
+#[derive(Debug, Clone)]
+pub struct MultiHostApi<A> {
+    hosts: Vec<Host<A>>,
+}
+
+impl<A: ZfsRemoteHighLevel> MultiHostApi<A> {
+    pub fn new(hosts: Vec<Host<A>>) -> Self {
+        Self { hosts }
+    }
+
+    pub fn hosts(&self) -> &[Host<A>] {
+        &self.hosts
+    }
+
+    pub fn host(&self, name: &str) -> Option<&A> {
+        self.hosts.iter().find(|h| h.name == name).map(|h| &h.api)
+    }
+
+    /// Tests the connections of all the hosts concurrently, and returns the results in the order of the hosts
+    pub async fn test_connections(&self) -> Vec<(String, Result<(), A::Error>)> {
+        let tests = self.hosts.iter().map(|h| async move {
+            let result = h.api.test_connection().await;
+            (h.name.clone(), result)
+        });
+
+        futures::future::join_all(tests).await
+    }
+}
@@ -2,14 +2,20 @@ use std::collections::BTreeMap;
 
 use super::traits::HttpRequest;
 use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use reqwasm::websocket::{futures::WebSocket, Message};
 use serde::Deserialize;
 
 use crate::{
-    config::LiveSettings,
+    config::HostSettings,
     types::{
-        AvailableCustomCommands, CustomCommandRunOptions, DatasetBody, DatasetFullMountState,
-        DatasetMountedResponse, DatasetsFullMountState, HelloResponse, KeyLoadedResponse,
-        RunCommandOutput, HELLO_RESPONSE,
+        AvailableCustomCommands, BootStatus, CommandHistory, CommandOutputChunk, CommandStatuses,
+        CommandStreamMessage, CustomCommandRunOptions, DatasetBody, DatasetFullMountState,
+        DatasetMountOptions, DatasetMountedResponse, DatasetsFullMountState, HelloResponse,
+        ImportablePools, JobCancelledResponse, JobId, JobOutput, JobStartedResponse, JobStatus,
+        JobsList, KeyLoadedResponse, MountDatasetBody, PassphraseVerifiedResponse, PoolImportBody,
+        PoolImportOptions, PoolImportedResponse, RunCommandOutput, SchedulesStatus,
+        UnlockGroupBody, UnlockGroupResponse, UnlockGroups, HELLO_RESPONSE,
     },
 };
 
@@ -27,6 +33,8 @@ pub enum ApiError {
     Response(u16, String),
     #[error("Response content extraction error: {0}")]
     ResponseExtraction(String),
+    #[error("WebSocket error for URL `{0}`: {1}")]
+    WebSocket(String, String),
 }
 
 #[derive(Debug, Clone)]
@@ -35,7 +43,7 @@ pub struct ApiRouteImpl {
 }
 
 impl ApiRouteImpl {
-    pub fn new_from_config(settings: LiveSettings) -> Self {
+    pub fn new_from_config(settings: HostSettings) -> Self {
         Self {
             base_url: settings.base_url.trim_end_matches('/').to_string(),
         }
@@ -48,6 +56,7 @@ impl ZfsRemoteAPI for ApiRouteImpl {
 
     async fn test_connection(&self) -> Result<(), Self::Error> {
         let url = format!("{}/hello", self.base_url);
+        #[allow(clippy::useless_conversion)]
         let body: HelloResponse = do_get_request(&url).await.map_err(Into::into)?;
 
         if body.result != HELLO_RESPONSE {
@@ -60,6 +69,10 @@ impl ZfsRemoteAPI for ApiRouteImpl {
         }
     }
 
+    fn events_url(&self) -> Option<String> {
+        Some(format!("{}/events", self.base_url))
+    }
+
     async fn encrypted_datasets_state(&self) -> Result<DatasetsFullMountState, Self::Error> {
         let url = format!("{}/zfs/encrypted-datasets-state", self.base_url);
         do_get_request(&url).await
@@ -98,21 +111,91 @@ impl ZfsRemoteAPI for ApiRouteImpl {
         .await
     }
 
+    async fn verify_passphrase(
+        &self,
+        dataset_name: &str,
+        password: &str,
+    ) -> Result<PassphraseVerifiedResponse, Self::Error> {
+        let url = format!("{}/zfs/verify-passphrase", self.base_url);
+        do_post_request(
+            &url,
+            Some(DatasetBody {
+                dataset_name: dataset_name.to_string(),
+            }),
+            [("Authorization".to_string(), password.to_string())]
+                .into_iter()
+                .collect(),
+        )
+        .await
+    }
+
     async fn mount_dataset(
         &mut self,
         dataset_name: &str,
+        options: &DatasetMountOptions,
     ) -> Result<DatasetMountedResponse, Self::Error> {
         let url = format!("{}/zfs/mount-dataset", self.base_url);
         do_post_request(
             &url,
-            Some(DatasetBody {
+            Some(MountDatasetBody {
                 dataset_name: dataset_name.to_string(),
+                options: options.clone(),
+            }),
+            [].into_iter().collect(),
+        )
+        .await
+    }
+
+    async fn importable_pools(&self) -> Result<ImportablePools, Self::Error> {
+        let url = format!("{}/zfs/importable-pools", self.base_url);
+        do_get_request(&url).await
+    }
+
+    async fn import_pool(
+        &mut self,
+        pool_name: &str,
+        options: &PoolImportOptions,
+    ) -> Result<PoolImportedResponse, Self::Error> {
+        let url = format!("{}/zfs/import-pool", self.base_url);
+        do_post_request(
+            &url,
+            Some(PoolImportBody {
+                pool_name: pool_name.to_string(),
+                options: options.clone(),
             }),
             [].into_iter().collect(),
         )
         .await
     }
 
+    async fn unlock_groups(&self) -> Result<UnlockGroups, Self::Error> {
+        let url = format!("{}/zfs/unlock-groups", self.base_url);
+        do_get_request(&url).await
+    }
+
+    async fn unlock_group(
+        &mut self,
+        group_name: &str,
+        password: &str,
+    ) -> Result<UnlockGroupResponse, Self::Error> {
+        let url = format!("{}/zfs/unlock-group", self.base_url);
+        do_post_request(
+            &url,
+            Some(UnlockGroupBody {
+                group_name: group_name.to_string(),
+            }),
+            [("Authorization".to_string(), password.to_string())]
+                .into_iter()
+                .collect(),
+        )
+        .await
+    }
+
+    async fn boot_status(&self) -> Result<BootStatus, Self::Error> {
+        let url = format!("{}/boot-status", self.base_url);
+        do_get_request(&url).await
+    }
+
     async fn list_available_commands(&self) -> Result<AvailableCustomCommands, Self::Error> {
         let url = format!("{}/custom-commands-list", self.base_url);
 
@@ -122,17 +205,101 @@ impl ZfsRemoteAPI for ApiRouteImpl {
     async fn call_custom_command(
         &mut self,
         endpoint: &str,
-        stdin: Option<&str>,
+        options: &CustomCommandRunOptions,
     ) -> Result<RunCommandOutput, Self::Error> {
         let url = format!("{}/custom-commands/{}", self.base_url, endpoint);
-        do_post_request(
-            &url,
-            Some(CustomCommandRunOptions {
-                stdin: stdin.map(|v| v.to_string()),
-            }),
-            [].into_iter().collect(),
-        )
-        .await
+        do_post_request(&url, Some(options.clone()), [].into_iter().collect()).await
+    }
+
+    async fn call_custom_command_streaming(
+        &mut self,
+        endpoint: &str,
+        options: &CustomCommandRunOptions,
+        on_started: &dyn Fn(JobId),
+        on_output: &dyn Fn(CommandOutputChunk),
+    ) -> Result<RunCommandOutput, Self::Error> {
+        // The WebSocket URL has the same address, with `ws` instead of `http` (and `wss` for `https`)
+        let Some(ws_base_url) = self.base_url.strip_prefix("http") else {
+            return self.call_custom_command(endpoint, options).await;
+        };
+        let url = format!("ws{ws_base_url}/custom-commands/{endpoint}/stream");
+
+        let ws_error = |e: String| ApiError::WebSocket(url.clone(), e);
+
+        let mut socket = WebSocket::open(&url).map_err(|e| ws_error(e.to_string()))?;
+
+        let options_json = serde_json::to_string(options).map_err(|e| ws_error(e.to_string()))?;
+        socket
+            .send(Message::Text(options_json))
+            .await
+            .map_err(|e| ws_error(e.to_string()))?;
+
+        while let Some(message) = socket.next().await {
+            let text = match message.map_err(|e| ws_error(e.to_string()))? {
+                Message::Text(text) => text,
+                Message::Bytes(_) => continue,
+            };
+
+            let message = serde_json::from_str::<CommandStreamMessage>(&text)
+                .map_err(|e| ApiError::JsonConversion(url.clone(), e.to_string()))?;
+
+            match message {
+                CommandStreamMessage::Started { job_id } => on_started(job_id),
+                CommandStreamMessage::Output(chunk) => on_output(chunk),
+                CommandStreamMessage::Finished { output } => return Ok(output),
+                CommandStreamMessage::Failed { status, error } => {
+                    return Err(ApiError::Response(status, error))
+                }
+            }
+        }
+
+        Err(ws_error(
+            "Connection closed before the command finished".to_string(),
+        ))
+    }
+
+    async fn cancel_job(&mut self, job_id: JobId) -> Result<JobCancelledResponse, Self::Error> {
+        let url = format!("{}/jobs/{}/cancel", self.base_url, job_id);
+        do_post_request::<_, ()>(&url, None, [].into_iter().collect()).await
+    }
+
+    async fn start_custom_command_job(
+        &mut self,
+        endpoint: &str,
+        options: &CustomCommandRunOptions,
+    ) -> Result<JobStartedResponse, Self::Error> {
+        let url = format!("{}/custom-commands/{}/start", self.base_url, endpoint);
+        do_post_request(&url, Some(options.clone()), [].into_iter().collect()).await
+    }
+
+    async fn list_jobs(&self) -> Result<JobsList, Self::Error> {
+        let url = format!("{}/jobs", self.base_url);
+        do_get_request(&url).await
+    }
+
+    async fn job_status(&self, job_id: JobId) -> Result<JobStatus, Self::Error> {
+        let url = format!("{}/jobs/{}", self.base_url, job_id);
+        do_get_request(&url).await
+    }
+
+    async fn job_output(&self, job_id: JobId) -> Result<JobOutput, Self::Error> {
+        let url = format!("{}/jobs/{}/output", self.base_url, job_id);
+        do_get_request(&url).await
+    }
+
+    async fn schedule_status(&self) -> Result<SchedulesStatus, Self::Error> {
+        let url = format!("{}/schedule-status", self.base_url);
+        do_get_request(&url).await
+    }
+
+    async fn command_history(&self, endpoint: &str) -> Result<CommandHistory, Self::Error> {
+        let url = format!("{}/history/{}", self.base_url, endpoint);
+        do_get_request(&url).await
+    }
+
+    async fn command_statuses(&self) -> Result<CommandStatuses, Self::Error> {
+        let url = format!("{}/command-statuses", self.base_url);
+        do_get_request(&url).await
     }
 }
 
@@ -1,8 +1,12 @@
 use std::collections::BTreeMap;
 
 use crate::types::{
-    AvailableCustomCommands, DatasetFullMountState, DatasetMountedResponse, DatasetsFullMountState,
-    KeyLoadedResponse, RunCommandOutput,
+    AvailableCustomCommands, BootStatus, CommandHistory, CommandOutputChunk, CommandStatuses,
+    CustomCommandRunOptions, DatasetFullMountState, DatasetMountOptions, DatasetMountedResponse,
+    DatasetsFullMountState, ImportablePools, JobCancelledResponse, JobId, JobOutput,
+    JobStartedResponse, JobStatus, JobsList, KeyLoadedResponse, PassphraseVerifiedResponse,
+    PoolImportOptions, PoolImportedResponse, RunCommandOutput, SchedulesStatus,
+    UnlockGroupResponse, UnlockGroups,
 };
 use async_trait::async_trait;
 use reqwasm::http;
@@ -14,6 +18,9 @@ pub trait ZfsRemoteAPI: Clone {
     /// Test the connection to the API server
     async fn test_connection(&self) -> Result<(), Self::Error>;
 
+    /// The URL of the server-sent events stream, or None if the API has no server behind it
+    fn events_url(&self) -> Option<String>;
+
     async fn encrypted_datasets_state(&self) -> Result<DatasetsFullMountState, Self::Error>;
 
     async fn encrypted_dataset_state(
@@ -27,18 +34,84 @@ pub trait ZfsRemoteAPI: Clone {
         password: &str,
     ) -> Result<KeyLoadedResponse, Self::Error>;
 
+    /// Checks whether the password is correct for the dataset, without loading the key.
+    /// A wrong password is not an error, but a response with `passphrase_correct == false`.
+    async fn verify_passphrase(
+        &self,
+        dataset_name: &str,
+        password: &str,
+    ) -> Result<PassphraseVerifiedResponse, Self::Error>;
+
     async fn mount_dataset(
         &mut self,
         dataset_name: &str,
+        options: &DatasetMountOptions,
     ) -> Result<DatasetMountedResponse, Self::Error>;
 
+    async fn importable_pools(&self) -> Result<ImportablePools, Self::Error>;
+
+    async fn import_pool(
+        &mut self,
+        pool_name: &str,
+        options: &PoolImportOptions,
+    ) -> Result<PoolImportedResponse, Self::Error>;
+
+    async fn unlock_groups(&self) -> Result<UnlockGroups, Self::Error>;
+
+    /// Loads the key of every dataset in the group with the given password, and mounts them if
+    /// the group is configured to. A failure of one dataset doesn't stop the others.
+    async fn unlock_group(
+        &mut self,
+        group_name: &str,
+        password: &str,
+    ) -> Result<UnlockGroupResponse, Self::Error>;
+
+    /// The state of the recovery that the server drives after a reboot
+    async fn boot_status(&self) -> Result<BootStatus, Self::Error>;
+
     async fn list_available_commands(&self) -> Result<AvailableCustomCommands, Self::Error>;
 
     async fn call_custom_command(
         &mut self,
         endpoint: &str,
-        stdin: Option<&str>,
+        options: &CustomCommandRunOptions,
     ) -> Result<RunCommandOutput, Self::Error>;
+
+    /// Like `call_custom_command`, where `on_output` gets the output while the command runs.
+    /// `on_started` gets the job of the run, if the server made one, to cancel it.
+    async fn call_custom_command_streaming(
+        &mut self,
+        endpoint: &str,
+        options: &CustomCommandRunOptions,
+        on_started: &dyn Fn(JobId),
+        on_output: &dyn Fn(CommandOutputChunk),
+    ) -> Result<RunCommandOutput, Self::Error>;
+
+    /// Stops the job, whose output then shows it was cancelled. Other runs of the command go on.
+    async fn cancel_job(&mut self, job_id: JobId) -> Result<JobCancelledResponse, Self::Error>;
+
+    /// Starts the custom command as a background job, and returns without waiting for it
+    async fn start_custom_command_job(
+        &mut self,
+        endpoint: &str,
+        options: &CustomCommandRunOptions,
+    ) -> Result<JobStartedResponse, Self::Error>;
+
+    /// The recent jobs, newest first, including the running ones
+    async fn list_jobs(&self) -> Result<JobsList, Self::Error>;
+
+    async fn job_status(&self, job_id: JobId) -> Result<JobStatus, Self::Error>;
+
+    async fn job_output(&self, job_id: JobId) -> Result<JobOutput, Self::Error>;
+
+    /// The custom commands that the server runs periodically, with their last results
+    async fn schedule_status(&self) -> Result<SchedulesStatus, Self::Error>;
+
+    /// The last runs of the custom command, newest first, which are kept across restarts of the server
+    async fn command_history(&self, endpoint: &str) -> Result<CommandHistory, Self::Error>;
+
+    /// The latest results of the status commands, which the server checks by itself
+    async fn command_statuses(&self) -> Result<CommandStatuses, Self::Error>;
 }
 
 #[async_trait(?Send)]
@@ -1,12 +1,22 @@
-use std::{path::Path, str::FromStr};
+use std::{collections::BTreeSet, path::Path, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 
+use crate::types::{
+    CommandConfirmation, CommandKind, CommandParameter, StdinMode, UnlockGroup,
+    DEFAULT_STDIN_MAX_BYTES,
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct MockSettings {
     // Dataset name, password, probability of failure
     pub datasets_and_passwords: Option<Vec<(String, String, f32)>>,
+    // Names of pools that are not imported yet
+    pub importable_pools: Option<Vec<String>>,
+    // Groups of datasets that are unlocked together with one password
+    #[serde(rename = "unlock_group")]
+    pub unlock_groups: Option<Vec<UnlockGroup>>,
     #[allow(clippy::type_complexity)]
     #[serde(rename = "custom_command")]
     pub custom_commands: Option<Vec<MockedCustomCommandConfig>>,
@@ -15,6 +25,33 @@ pub struct MockSettings {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct LiveSettings {
+    /// The URL of the API server, when there's only one
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// API servers of multiple machines, where every one gets its own section in the page
+    #[serde(default, rename = "host")]
+    pub hosts: Vec<HostSettings>,
+}
+
+impl LiveSettings {
+    /// All the configured API servers, where `base_url` comes first, named by its URL
+    pub fn all_hosts(&self) -> Vec<HostSettings> {
+        self.base_url
+            .iter()
+            .map(|url| HostSettings {
+                name: url.to_string(),
+                base_url: url.to_string(),
+            })
+            .chain(self.hosts.iter().cloned())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostSettings {
+    /// The name of the machine, to show in the UI
+    pub name: String,
     pub base_url: String,
 }
 
@@ -41,7 +78,8 @@ impl WebPageConfig {
 
     pub fn from_base_url(url: impl Into<String>) -> Self {
         let config = LiveSettings {
-            base_url: url.into(),
+            base_url: Some(url.into()),
+            hosts: Vec::new(),
         };
         Self {
             mode: LiveOrMock::Live(config),
@@ -54,6 +92,19 @@ impl FromStr for WebPageConfig {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let config: WebPageConfig = toml::from_str(s)?;
+
+        if let LiveOrMock::Live(settings) = &config.mode {
+            let hosts = settings.all_hosts();
+            if hosts.is_empty() {
+                return Err("Live mode requires `base_url` or at least one `host`".into());
+            }
+
+            let mut seen = BTreeSet::new();
+            if let Some(host) = hosts.iter().find(|h| !seen.insert(&h.name)) {
+                return Err(format!("Host name `{}` is used more than once", host.name).into());
+            }
+        }
+
         Ok(config)
     }
 }
@@ -65,7 +116,32 @@ pub struct MockedCustomCommandConfig {
     pub expected_stdout: String,
     pub expected_stderr: String,
     pub expected_error_code: i32,
+    /// What the expected error code means, to show next to it
+    #[serde(default)]
+    pub exit_code_meaning: Option<String>,
     pub stdin: MockedCustomCommandStdinConfig,
+    #[serde(default, rename = "parameter")]
+    pub parameters: Vec<CommandParameter>,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub order: i32,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub confirm: Option<CommandConfirmation>,
+    /// Status commands are shown as badges, which are green when the expected error code is 0
+    #[serde(default)]
+    pub kind: CommandKind,
+    #[serde(default)]
+    pub status_interval_secs: Option<u64>,
+    /// Datasets of `datasets_and_passwords` that must be mounted before the command runs
+    #[serde(default)]
+    pub requires_mounted: Vec<String>,
+    #[serde(default)]
+    pub requires_key_loaded: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,6 +159,10 @@ pub struct MockedCustomCommandStdinSettings {
     pub placeholder: String,
     #[serde(default = "default_true")]
     pub is_password: bool,
+    #[serde(default)]
+    pub mode: StdinMode,
+    #[serde(default)]
+    pub max_bytes: Option<usize>,
 }
 
 fn default_true() -> bool {
@@ -110,6 +190,22 @@ impl MockedCustomCommandStdinConfig {
             MockedCustomCommandStdinConfig::AllSettings(s) => s.is_password,
         }
     }
+
+    pub fn mode(&self) -> StdinMode {
+        match self {
+            MockedCustomCommandStdinConfig::Simple(_) => StdinMode::Line,
+            MockedCustomCommandStdinConfig::AllSettings(s) => s.mode,
+        }
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        match self {
+            MockedCustomCommandStdinConfig::Simple(_) => DEFAULT_STDIN_MAX_BYTES,
+            MockedCustomCommandStdinConfig::AllSettings(s) => {
+                s.max_bytes.unwrap_or(DEFAULT_STDIN_MAX_BYTES)
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -123,4 +219,40 @@ mod tests {
         // println!("{_config:?}");
         // println!("{}", toml::to_string_pretty(&_config).unwrap());
     }
+
+    #[test]
+    fn live_hosts() {
+        let config: WebPageConfig = r#"
+            [mode.live]
+            base_url = "http://127.0.0.1:6677"
+
+            [[mode.live.host]]
+            name = "nas"
+            base_url = "http://nas:6677"
+        "#
+        .parse()
+        .unwrap();
+        let LiveOrMock::Live(settings) = config.mode else {
+            panic!("Expected live mode");
+        };
+        let names = settings
+            .all_hosts()
+            .into_iter()
+            .map(|h| h.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["http://127.0.0.1:6677", "nas"]);
+
+        assert!("[mode.live]".parse::<WebPageConfig>().is_err());
+
+        let duplicates = r#"
+            [[mode.live.host]]
+            name = "nas"
+            base_url = "http://nas:6677"
+
+            [[mode.live.host]]
+            name = "nas"
+            base_url = "http://nas2:6677"
+        "#;
+        assert!(duplicates.parse::<WebPageConfig>().is_err());
+    }
 }
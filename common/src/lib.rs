@@ -1,3 +1,4 @@
 pub mod api;
 pub mod config;
+pub mod structured_output;
 pub mod types;
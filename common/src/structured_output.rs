@@ -0,0 +1,68 @@
+//! Parsing of command outputs that have a structure, to show them as more than plain text
+
+/// Splits every non-empty line at its first `=` or `:`, e.g., the output of `systemctl show`.
+/// A line without either is a key with an empty value.
+pub fn parse_key_values(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match line.find(['=', ':']) {
+            Some(idx) => (
+                line[..idx].trim().to_string(),
+                line[idx + 1..].trim().to_string(),
+            ),
+            None => (line.trim().to_string(), String::new()),
+        })
+        .collect()
+}
+
+/// Splits every non-empty line into columns, where the first line is the header. Columns are
+/// separated by tabs if there are any, e.g., `zfs list -H`, or otherwise by two spaces or more,
+/// e.g., `docker ps`, so that single spaces stay within the values.
+pub fn parse_table(text: &str) -> Vec<Vec<String>> {
+    let tab_separated = text.contains('\t');
+
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            if tab_separated {
+                line.split('\t').map(|c| c.trim().to_string()).collect()
+            } else {
+                line.split("  ")
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty())
+                    .map(String::from)
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_values_split_at_the_first_separator() {
+        let parsed = parse_key_values("ActiveState=active\nURL: http://a:1\n\nalone\n");
+        assert_eq!(
+            parsed,
+            vec![
+                ("ActiveState".to_string(), "active".to_string()),
+                ("URL".to_string(), "http://a:1".to_string()),
+                ("alone".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tables_split_at_tabs_or_wide_spaces() {
+        assert_eq!(
+            parse_table("tank\t1.2T\ntank/docker\t50G\n"),
+            vec![vec!["tank", "1.2T"], vec!["tank/docker", "50G"]]
+        );
+        assert_eq!(
+            parse_table("NAMES      STATUS\nweb app    Up 2 hours\n"),
+            vec![vec!["NAMES", "STATUS"], vec!["web app", "Up 2 hours"]]
+        );
+    }
+}
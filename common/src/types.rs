@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 pub const HELLO_RESPONSE: &str = "WelcomeToTheUltimateUnlocker!";
 
@@ -8,12 +8,32 @@ pub const HELLO_RESPONSE: &str = "WelcomeToTheUltimateUnlocker!";
 pub struct DatasetMountedResponse {
     pub dataset_name: String,
     pub is_mounted: bool,
+    /// The results of the `on_mounted` hooks that ran after mounting the dataset
+    #[serde(default)]
+    pub hooks: Vec<HookOutput>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct KeyLoadedResponse {
     pub dataset_name: String,
     pub key_loaded: bool,
+    /// The results of the `on_key_loaded` hooks that ran after loading the key
+    #[serde(default)]
+    pub hooks: Vec<HookOutput>,
+}
+
+/// The result of checking a passphrase without loading the key
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PassphraseVerifiedResponse {
+    pub dataset_name: String,
+    pub passphrase_correct: bool,
+}
+
+/// The result of a command that ran automatically after a dataset's key was loaded, or after it was mounted
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct HookOutput {
+    pub label: String,
+    pub output: RunCommandOutput,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -43,13 +63,467 @@ pub struct DatasetBody {
     pub dataset_name: String,
 }
 
+/// Options that apply only to one mount, without changing the properties of the dataset
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DatasetMountOptions {
+    /// Mount the dataset read-only
+    #[serde(default)]
+    pub readonly: bool,
+    /// Mount the dataset at this directory instead of its `mountpoint` property.
+    /// The server only allows directories under its configured prefixes.
+    #[serde(default)]
+    pub mountpoint: Option<String>,
+}
+
+impl DatasetMountOptions {
+    /// Whether the dataset is mounted as it would be by default, i.e., read-write at its own mountpoint
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MountDatasetBody {
+    pub dataset_name: String,
+    #[serde(default)]
+    pub options: DatasetMountOptions,
+}
+
+/// A pool that is not imported, but was found on the devices and can be imported
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ImportablePool {
+    pub pool_name: String,
+    pub pool_id: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportablePools {
+    pub pools: BTreeMap<String, ImportablePool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PoolImportOptions {
+    /// Import the pool with `readonly=on`
+    #[serde(default)]
+    pub readonly: bool,
+    /// Alternate root directory for the pool, equivalent to `zpool import -R <altroot>`
+    #[serde(default)]
+    pub altroot: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PoolImportBody {
+    pub pool_name: String,
+    #[serde(default)]
+    pub options: PoolImportOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PoolImportedResponse {
+    pub pool_name: String,
+    pub is_imported: bool,
+}
+
+/// A named set of datasets that share one passphrase, so that they can be unlocked together
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct UnlockGroup {
+    pub name: String,
+    pub datasets: Vec<String>,
+    /// Whether to mount the datasets after their keys are loaded
+    #[serde(default)]
+    pub mount_after: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct UnlockGroups {
+    pub groups: Vec<UnlockGroup>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlockGroupBody {
+    pub group_name: String,
+}
+
+/// The outcome of unlocking one dataset of an unlock group
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct UnlockGroupMemberOutcome {
+    pub key_loaded: bool,
+    pub is_mounted: bool,
+    /// The error of loading the key or mounting, if any. Other members are unaffected by it.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// The results of the hooks that ran after loading the key and mounting
+    #[serde(default)]
+    pub hooks: Vec<HookOutput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct UnlockGroupResponse {
+    pub group_name: String,
+    pub outcomes: BTreeMap<String, UnlockGroupMemberOutcome>,
+}
+
+/// The stages of the recovery that the server drives on startup, after a reboot
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum BootState {
+    /// Boot orchestration is not enabled in the server
+    Disabled,
+    Checking,
+    WaitingForUnlock {
+        locked_datasets: Vec<String>,
+    },
+    Mounting,
+    RunningStartupCommands,
+    Ready,
+    Failed {
+        error: String,
+    },
+}
+
+/// Events that the server broadcasts to all the clients, so that every client sees changes
+/// that were made by others
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ServerEvent {
+    DatasetChanged {
+        state: DatasetFullMountState,
+    },
+    DatasetRemoved {
+        dataset_name: String,
+    },
+    CommandStarted {
+        endpoint: String,
+    },
+    CommandFinished {
+        endpoint: String,
+        output: RunCommandOutput,
+    },
+    CommandFailed {
+        endpoint: String,
+        error: String,
+    },
+    /// The commands that can't run now because of the concurrency limits, sent when they change
+    CommandsBusy {
+        endpoints: Vec<String>,
+    },
+    /// The next run or the last results of a scheduled command changed
+    ScheduleChanged {
+        status: CommandScheduleStatus,
+    },
+    /// A status command was checked by the server
+    StatusChanged {
+        status: CommandStatus,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct BootStatus {
+    pub state: BootState,
+    /// The results of sending the "waiting for unlock" notifications
+    #[serde(default)]
+    pub notifications: Vec<HookOutput>,
+    /// The results of the start-up commands, in the order they ran
+    #[serde(default)]
+    pub startup_commands: Vec<HookOutput>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
 pub struct RunCommandOutput {
     pub stdout: String,
     pub stderr: String,
     pub error_code: i32,
+    /// Why the command was stopped, if it didn't exit by itself
+    #[serde(default)]
+    pub stopped: Option<CommandStopReason>,
+    /// The signal that terminated the command, if it was terminated by one
+    #[serde(default)]
+    pub signal: Option<i32>,
+    /// The result of every command in the chain, in order, including the ones that didn't run
+    #[serde(default)]
+    pub stages: Vec<StageOutput>,
+    /// How the output bytes were turned into `stdout` and `stderr`, which is also used for the stages
+    #[serde(default)]
+    pub encoding: OutputEncoding,
+    /// Whether the stdout went beyond the size limit, and only its beginning was kept
+    #[serde(default)]
+    pub stdout_truncated: bool,
+    /// Whether the stderr of any of the commands went beyond the size limit
+    #[serde(default)]
+    pub stderr_truncated: bool,
+    /// How the stdout is structured, to show it accordingly
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// What the exit code means, if the command has a meaning for it
+    #[serde(default)]
+    pub exit_code_meaning: Option<String>,
+    /// For status commands, whether the output meets the command's success criterion
+    #[serde(default)]
+    pub status_ok: Option<bool>,
+}
+
+/// The structure of the stdout of a command, which the UI uses to show it
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Plain,
+    Json,
+    /// Lines of `key=value` or `key: value`
+    #[serde(alias = "key-value")]
+    KeyValue,
+    /// Lines of columns separated by tabs or by wide spaces, where the first line is the header
+    Table,
+}
+
+/// How the output bytes of a command are turned into strings
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputEncoding {
+    /// UTF-8 text, where invalid bytes are replaced
+    #[default]
+    Text,
+    /// The exact bytes, encoded in base64, e.g., for binary output
+    Base64,
+}
+
+/// The result of one command of a chain, where the combined output has the stdout of the last
+/// command that ran and the stderr of all of them
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct StageOutput {
+    pub argv: Vec<String>,
+    pub stdout: String,
+    pub stderr: String,
+    pub error_code: i32,
+    pub duration_ms: u64,
+    /// Whether the command was skipped, because of the stages before it
+    #[serde(default)]
+    pub skipped: bool,
+    /// Whether the outputs of the stage were cut, because the outputs of all the stages
+    /// together are limited in size
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+impl RunCommandOutput {
+    pub fn output_of(&self, stream: OutputStream) -> &str {
+        match stream {
+            OutputStream::Stdout => &self.stdout,
+            OutputStream::Stderr => &self.stderr,
+        }
+    }
+
+    pub fn is_truncated(&self, stream: OutputStream) -> bool {
+        match stream {
+            OutputStream::Stdout => self.stdout_truncated,
+            OutputStream::Stderr => self.stderr_truncated,
+        }
+    }
+
+    /// The first stage that ran and failed, with its position in the chain
+    pub fn failed_stage(&self) -> Option<(usize, &StageOutput)> {
+        self.stages
+            .iter()
+            .enumerate()
+            .find(|(_, stage)| !stage.skipped && stage.error_code != 0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandStopReason {
+    TimedOut,
+    Cancelled,
+}
+
+pub type JobId = u64;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Finished,
+    /// The command couldn't be run, e.g., because of invalid parameters
+    Failed,
+}
+
+/// Every call of a custom command is a job, which is kept for a while after it finishes,
+/// so that its result can be retrieved later
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct JobStatus {
+    pub job_id: JobId,
+    pub endpoint: String,
+    pub state: JobState,
+    pub started_at_unix_secs: u64,
+    #[serde(default)]
+    pub finished_at_unix_secs: Option<u64>,
+    /// Why the job failed, if it did
+    #[serde(default)]
+    pub error: Option<String>,
 }
 
+/// The output of a job, which is the output so far while it runs
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct JobOutput {
+    pub status: JobStatus,
+    pub output: RunCommandOutput,
+}
+
+/// The jobs that the server keeps, newest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct JobsList {
+    pub jobs: Vec<JobStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct JobStartedResponse {
+    pub job_id: JobId,
+}
+
+/// A past run of a custom command, as it's kept in the history of the server
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CommandHistoryEntry {
+    pub started_at_unix_secs: u64,
+    pub duration_ms: u64,
+    /// Who ran the command: the address of the client, or `schedule` for scheduled runs
+    pub caller: String,
+    /// The beginning of the stdin, unless the command takes a password, which is never kept
+    #[serde(default)]
+    pub stdin: Option<String>,
+    /// Whether the stdin was cut by the history's limit
+    #[serde(default)]
+    pub stdin_truncated: bool,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, String>,
+    /// The exit code, if the command ran
+    #[serde(default)]
+    pub error_code: Option<i32>,
+    /// Why the command couldn't run, if it didn't
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub stopped: Option<CommandStopReason>,
+    /// The beginning of the stdout, as far as the history keeps
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+    /// Whether the stdout was cut, by the command's limit or by the history's
+    #[serde(default)]
+    pub stdout_truncated: bool,
+    #[serde(default)]
+    pub stderr_truncated: bool,
+}
+
+/// The last runs of a custom command, newest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CommandHistory {
+    pub endpoint: String,
+    pub runs: Vec<CommandHistoryEntry>,
+}
+
+/// A run of a scheduled custom command, which is also kept as a job
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ScheduledRun {
+    pub job_id: JobId,
+    pub started_at_unix_secs: u64,
+    pub finished_at_unix_secs: u64,
+    /// The exit code, if the command ran
+    #[serde(default)]
+    pub error_code: Option<i32>,
+    /// Why the command couldn't run, e.g., because it was busy
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// A custom command that the server runs by itself, periodically
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CommandScheduleStatus {
+    pub endpoint: String,
+    /// The schedule as it was configured, e.g., a cron expression
+    pub schedule: String,
+    #[serde(default)]
+    pub next_run_at_unix_secs: Option<u64>,
+    /// The most recent runs, newest first
+    #[serde(default)]
+    pub last_runs: Vec<ScheduledRun>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SchedulesStatus {
+    pub commands: Vec<CommandScheduleStatus>,
+}
+
+/// The latest result of a status command, which the server checks at the command's interval
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CommandStatus {
+    pub endpoint: String,
+    pub checked_at_unix_secs: u64,
+    /// The output of the last check, if the command could run
+    #[serde(default)]
+    pub output: Option<RunCommandOutput>,
+    /// Why the command couldn't run in the last check
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl CommandStatus {
+    pub fn is_ok(&self) -> bool {
+        self.output.as_ref().and_then(|o| o.status_ok) == Some(true)
+    }
+}
+
+/// The status commands that were checked at least once
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CommandStatuses {
+    pub commands: Vec<CommandStatus>,
+}
+
+/// The response to cancelling a job
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct JobCancelledResponse {
+    pub job_id: JobId,
+    /// Whether the job was running, and was stopped. False if it was already over.
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// A piece of the output of a command, sent while the command runs
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CommandOutputChunk {
+    pub stream: OutputStream,
+    pub data: String,
+}
+
+/// The messages that the server sends over the WebSocket of a streamed custom command.
+/// The client sends `CustomCommandRunOptions` as the first message, then gets the job of the run,
+/// the output while the command runs, and finally either `Finished` or `Failed`.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CommandStreamMessage {
+    /// The run is this job, which can be cancelled
+    Started {
+        job_id: JobId,
+    },
+    Output(CommandOutputChunk),
+    Finished {
+        output: RunCommandOutput,
+    },
+    Failed {
+        /// The HTTP status code that the error would have had as a response
+        status: u16,
+        error: String,
+    },
+}
+
+/// The custom commands, in the order to show them
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct AvailableCustomCommands {
     pub commands: Vec<CustomCommandPublicInfo>,
@@ -63,11 +537,215 @@ pub struct CustomCommandPublicInfo {
     pub stdin_allow: bool,
     pub stdin_text_placeholder: String,
     pub stdin_is_password: bool,
+    #[serde(default)]
+    pub stdin_mode: StdinMode,
+    /// The most bytes of stdin that the command takes
+    #[serde(default = "default_stdin_max_bytes")]
+    pub stdin_max_bytes: usize,
+    #[serde(default)]
+    pub parameters: Vec<CommandParameter>,
+    /// Whether the command can't run now, because of the concurrency limits
+    #[serde(default)]
+    pub busy: bool,
+    /// When the server runs the command by itself, if it does
+    #[serde(default)]
+    pub schedule: Option<String>,
+    /// The section of the UI where the command shows, if any
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Commands are sorted by this, and then by their order in the config
+    #[serde(default)]
+    pub order: i32,
+    /// What the command does, to show under its label
+    #[serde(default)]
+    pub description: Option<String>,
+    /// A short text to show before the label, e.g., an emoji
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// What the user must confirm before the command runs, if anything
+    #[serde(default)]
+    pub confirm: Option<CommandConfirmation>,
+    #[serde(default)]
+    pub kind: CommandKind,
+    /// How often, in seconds, the server runs a status command
+    #[serde(default)]
+    pub status_interval_secs: Option<u64>,
+    #[serde(default)]
+    pub preconditions: CommandPreconditions,
 }
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+/// The states that datasets must be in before a custom command can run, e.g., the mounted
+/// dataset where a service keeps its data
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CommandPreconditions {
+    /// Datasets that must be mounted
+    #[serde(default)]
+    pub requires_mounted: Vec<String>,
+    /// Datasets whose key must be loaded
+    #[serde(default)]
+    pub requires_key_loaded: Vec<String>,
+}
+
+impl CommandPreconditions {
+    pub fn is_empty(&self) -> bool {
+        self.requires_mounted.is_empty() && self.requires_key_loaded.is_empty()
+    }
+
+    /// Whether the state of the dataset matters to the preconditions
+    pub fn involves(&self, dataset_name: &str) -> bool {
+        self.requires_mounted
+            .iter()
+            .chain(&self.requires_key_loaded)
+            .any(|d| d == dataset_name)
+    }
+
+    /// Why the command can't run with the given states of the encrypted datasets, with one reason
+    /// for every precondition that doesn't hold. Datasets without a state are never ready.
+    pub fn unmet(&self, states: &BTreeMap<String, DatasetFullMountState>) -> Vec<String> {
+        let key_loaded =
+            self.requires_key_loaded
+                .iter()
+                .filter_map(|name| match states.get(name) {
+                    Some(state) if state.key_loaded => None,
+                    Some(_) => Some(format!("The key of dataset `{name}` is not loaded")),
+                    None => Some(format!("Dataset `{name}` is not available")),
+                });
+        let mounted = self
+            .requires_mounted
+            .iter()
+            .filter_map(|name| match states.get(name) {
+                Some(state) if state.is_mounted => None,
+                Some(_) => Some(format!("Dataset `{name}` is not mounted")),
+                None => Some(format!("Dataset `{name}` is not available")),
+            });
+
+        key_loaded.chain(mounted).collect()
+    }
+}
+
+/// The most bytes of stdin that commands take, if they don't set it
+pub const DEFAULT_STDIN_MAX_BYTES: usize = 64 * 1024;
+
+fn default_stdin_max_bytes() -> usize {
+    DEFAULT_STDIN_MAX_BYTES
+}
+
+/// How the UI takes the stdin of a command
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StdinMode {
+    /// A single line, which can be a password
+    #[default]
+    Line,
+    /// A text area, e.g., for a config snippet
+    Multiline,
+    /// The content of a file that the user picks
+    File,
+}
+
+/// How long status commands wait between their runs, if they don't set it
+pub const DEFAULT_STATUS_INTERVAL_SECS: u64 = 30;
+
+/// What a custom command is for, which decides how the UI shows it
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandKind {
+    /// A command that the user runs with a button
+    #[default]
+    Action,
+    /// A health check, e.g., whether a service is running, which the UI runs by itself
+    /// and shows as a badge
+    Status,
+}
+
+/// A confirmation that the user gives before a command runs, e.g., for destructive commands
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CommandConfirmation {
+    /// The question to show, e.g., "Restart docker? The containers will stop."
+    pub message: String,
+    /// A word that the user must type to continue, e.g., `RESTART`. If not set, it's a yes/no prompt.
+    #[serde(default)]
+    pub typed_word: Option<String>,
+}
+
+impl CommandConfirmation {
+    /// What a run must have as its `confirmation`: the typed word, or the command's endpoint
+    /// for a yes/no prompt, so that a script has to name the command it confirms
+    pub fn token<'a>(&'a self, endpoint: &'a str) -> &'a str {
+        self.typed_word.as_deref().unwrap_or(endpoint)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandParameterType {
+    String,
+    Int,
+    Enum,
+    Bool,
+}
+
+/// A declared input of a custom command, which replaces the `{name}` placeholders in its `run_cmd` arguments
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CommandParameter {
+    /// The name used in placeholders. Only ASCII alphanumerics and underscores.
+    pub name: String,
+    /// The label of the input, to show in the UI
+    pub label: String,
+    #[serde(rename = "type")]
+    pub kind: CommandParameterType,
+    /// The allowed values, for parameters of the `enum` type
+    #[serde(default)]
+    pub values: Vec<String>,
+    /// A regular expression that the whole value must match
+    #[serde(default)]
+    pub regex: Option<String>,
+    /// Whether a `string` value may start with `-`. It's rejected otherwise, because the
+    /// program would take it as an option.
+    #[serde(default)]
+    pub allow_leading_dash: bool,
+    /// The value to use when none is given
+    #[serde(default, deserialize_with = "deserialize_scalar_as_string")]
+    pub default: Option<String>,
+}
+
+/// Accepts `default = 5` and `default = true`, and not only strings
+fn deserialize_scalar_as_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Scalar {
+        String(String),
+        Int(i64),
+        Bool(bool),
+    }
+
+    let value: Option<Scalar> = Deserialize::deserialize(deserializer)?;
+
+    Ok(value.map(|v| match v {
+        Scalar::String(s) => s,
+        Scalar::Int(i) => i.to_string(),
+        Scalar::Bool(b) => b.to_string(),
+    }))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct CustomCommandRunOptions {
     pub stdin: Option<String>,
+    /// How `stdin` is encoded: as text, or as base64 for files that aren't text
+    #[serde(default)]
+    pub stdin_encoding: OutputEncoding,
+    /// The values of the command's parameters, by name. Parameters that are left out take their defaults.
+    #[serde(default)]
+    pub parameters: BTreeMap<String, String>,
+    /// The confirmation of commands that require one, which is the typed word for commands with one,
+    /// or the endpoint of the command otherwise
+    #[serde(default)]
+    pub confirmation: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use common::{
+    api::traits::ZfsRemoteHighLevel,
+    types::{BootState, BootStatus, HookOutput},
+};
+use leptos::{
+    component, create_local_resource, on_cleanup, set_interval_with_handle, view, CollectView,
+    IntoView, SignalGet, Trigger,
+};
+
+use crate::app::log;
+
+use super::cmds::{ErrorCodeFromOutput, StdOutputFormatted};
+
+const BOOT_STATUS_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+fn is_final_state(state: &BootState) -> bool {
+    matches!(
+        state,
+        BootState::Disabled | BootState::Ready | BootState::Failed { .. }
+    )
+}
+
+/// Shows the progress of the recovery that the server drives after a reboot, and nothing
+/// if boot orchestration is disabled in the server.
+#[component]
+pub fn BootStatusBanner<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    /// The status is reloaded when datasets change (e.g., after a group is unlocked)
+    datasets_changed: Trigger,
+) -> impl IntoView {
+    let boot_status = create_local_resource(
+        move || datasets_changed.track(),
+        move |_| {
+            let api = api.clone();
+            async move {
+                let result = api.boot_status().await;
+                if let Err(e) = &result {
+                    log(&format!("Boot status request error: {e}"));
+                }
+                result.ok()
+            }
+        },
+    );
+
+    // The orchestration moves on its own in the server, so it's polled until it's done
+    let poll_handle = set_interval_with_handle(
+        move || {
+            let done = boot_status
+                .get()
+                .flatten()
+                .map(|s| is_final_state(&s.state))
+                .unwrap_or(false);
+            if !done {
+                boot_status.refetch();
+            }
+        },
+        BOOT_STATUS_POLL_INTERVAL,
+    );
+    if let Ok(handle) = poll_handle {
+        on_cleanup(move || handle.clear());
+    }
+
+    move || {
+        boot_status
+            .get()
+            .flatten()
+            .map(|status| view! { <BootStatusView status /> })
+    }
+}
+
+#[component]
+fn BootStatusView(status: BootStatus) -> impl IntoView {
+    let (class, message) = match &status.state {
+        BootState::Disabled => return view! {}.into_view(),
+        BootState::Checking => (
+            "boot-banner boot-banner-progress",
+            "The server restarted, and is checking the datasets".to_string(),
+        ),
+        BootState::WaitingForUnlock { locked_datasets } => (
+            "boot-banner boot-banner-waiting",
+            format!(
+                "The server restarted, and is waiting for the keys of: {}",
+                locked_datasets.join(", ")
+            ),
+        ),
+        BootState::Mounting => (
+            "boot-banner boot-banner-progress",
+            "Keys loaded. Mounting the datasets...".to_string(),
+        ),
+        BootState::RunningStartupCommands => (
+            "boot-banner boot-banner-progress",
+            "Datasets mounted. Running the start-up commands...".to_string(),
+        ),
+        BootState::Ready => (
+            "boot-banner boot-banner-ready",
+            "The server is fully recovered after the restart".to_string(),
+        ),
+        BootState::Failed { error } => (
+            "boot-banner boot-banner-failed",
+            format!("Recovery after the restart failed: {error}"),
+        ),
+    };
+
+    view! {
+        <div class=class>
+            <p>{message}</p>
+            {status.startup_commands.into_iter().map(|cmd| view! { <BootCommandOutput cmd /> }).collect_view()}
+        </div>
+    }
+    .into_view()
+}
+
+#[component]
+fn BootCommandOutput(cmd: HookOutput) -> impl IntoView {
+    view! {
+        <div class="zfs-hook-output">
+            <p>{cmd.label}</p>
+            <ErrorCodeFromOutput output=cmd.output.clone() />
+            <StdOutputFormatted output=cmd.output.stdout button_label="Show stdout".to_string() />
+            <StdOutputFormatted output=cmd.output.stderr button_label="Show stderr".to_string() />
+        </div>
+    }
+}
@@ -1,15 +1,25 @@
+use base64::prelude::*;
 use common::{
     api::{
         mock::ApiMock,
         routed::ApiRouteImpl,
         traits::{ZfsRemoteAPI, ZfsRemoteHighLevel},
     },
-    types::{AvailableCustomCommands, CustomCommandPublicInfo, RunCommandOutput},
+    structured_output::{parse_key_values, parse_table},
+    types::{
+        AvailableCustomCommands, CommandConfirmation, CommandHistory, CommandKind,
+        CommandParameter, CommandParameterType, CommandStopReason, CustomCommandPublicInfo,
+        CustomCommandRunOptions, OutputEncoding, OutputFormat, OutputStream, RunCommandOutput,
+        StdinMode,
+    },
 };
+use std::collections::BTreeMap;
+
 use leptos::{
-    component, create_action, create_local_resource, create_rw_signal, create_signal,
-    event_target_value, view, CollectView, ErrorBoundary, IntoView, Show, SignalGet, SignalSet,
-    Transition,
+    component, create_action, create_local_resource, create_rw_signal, create_signal, event_target,
+    event_target_checked, event_target_value, spawn_local, view, Callable, Callback, CollectView,
+    ErrorBoundary, IntoView, RwSignal, Show, SignalGet, SignalGetUntracked, SignalSet,
+    SignalUpdate, SignalWith, Transition, View, WriteSignal,
 };
 use leptos_icons::Icon;
 
@@ -28,12 +38,15 @@ pub fn CommandsTableFromConfig() -> impl IntoView {
     let after_config_view = move || {
         configuration_getter.and_then(|config| match config.mode.clone() {
             common::config::LiveOrMock::Live(s) => {
-                log("Initializing live object for commands table");
-                view! { <CommandsTable api=ApiRouteImpl::new_from_config(s) /> }
+                log("Initializing live objects for commands table");
+                s.all_hosts()
+                    .into_iter()
+                    .map(|h| view! { <CommandsTable api=ApiRouteImpl::new_from_config(h) /> })
+                    .collect_view()
             }
             common::config::LiveOrMock::Mock(m) => {
                 log("Initializing mock object for commands table");
-                view! { <CommandsTable api=ApiMock::new_from_config(m) /> }
+                view! { <CommandsTable api=ApiMock::new_from_config(m) /> }.into_view()
             }
         })
     };
@@ -101,34 +114,82 @@ fn CommandCallsTable<'a, A: ZfsRemoteHighLevel + 'static>(
     available_commands: &'a AvailableCustomCommands,
 ) -> impl IntoView {
     // log(&format!("Commands found: {:?}", available_commands));
-    let rows_count = available_commands.commands.len();
-
-    let available_commands = (*available_commands).clone();
+    let sections = command_sections(available_commands);
+    let rows_count = sections.len();
 
     view! {
         <div class="custom-commands-table-container">
-            <Show when=move || (rows_count > 0) fallback=|| view! { <NoCommandsAvailable /> }>
-                <table class="custom-commands-table">
-                    <thead>
-                        <CommandRow api=api.clone() command_info=None />
-                    </thead>
-                    <tbody>
-                        {available_commands
-                            .commands
-                            .iter()
-                            .map(|command_info| {
-                                view! {
-                                    <CommandRow api=api.clone() command_info=Some(command_info) />
-                                }
-                            })
-                            .collect_view()}
-                    </tbody>
-                </table>
+            <Show when={move || rows_count > 0} fallback=|| view! { <NoCommandsAvailable /> }>
+                {sections
+                    .iter()
+                    .map(|(group, commands)| {
+                        let table = view! { <CommandsSectionTable api=api.clone() commands /> };
+                        match group {
+                            Some(group) => view! {
+                                <details class="custom-commands-group" open>
+                                    <summary>{group}</summary>
+                                    {table}
+                                </details>
+                            }
+                            .into_view(),
+                            None => table.into_view(),
+                        }
+                    })
+                    .collect_view()}
             </Show>
         </div>
     }
 }
 
+/// The commands split by their groups, where the commands without a group come first,
+/// and then the groups in the order of their first command. Status commands are left out,
+/// since they show as badges at the top of the page.
+fn command_sections(
+    available_commands: &AvailableCustomCommands,
+) -> Vec<(Option<String>, Vec<CustomCommandPublicInfo>)> {
+    let mut sections: Vec<(Option<String>, Vec<CustomCommandPublicInfo>)> = Vec::new();
+
+    for command in available_commands
+        .commands
+        .iter()
+        .filter(|c| c.kind == CommandKind::Action)
+    {
+        match sections
+            .iter_mut()
+            .find(|(group, _)| *group == command.group)
+        {
+            Some((_, commands)) => commands.push(command.clone()),
+            None => sections.push((command.group.clone(), vec![command.clone()])),
+        }
+    }
+    sections.sort_by_key(|(group, _)| group.is_some());
+
+    sections
+}
+
+#[allow(clippy::needless_lifetimes)]
+#[component]
+fn CommandsSectionTable<'a, A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    commands: &'a [CustomCommandPublicInfo],
+) -> impl IntoView {
+    view! {
+        <table class="custom-commands-table">
+            <thead>
+                <CommandRow api=api.clone() command_info=None />
+            </thead>
+            <tbody>
+                {commands
+                    .iter()
+                    .map(|command_info| {
+                        view! { <CommandRow api=api.clone() command_info=Some(command_info) /> }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
+
 #[component]
 fn NoCommandsAvailable() -> impl IntoView {
     view! { <p align="center">"No commands available to execute"</p> }
@@ -151,7 +212,23 @@ fn CommandTableCell<A: ZfsRemoteHighLevel + 'static>(
         CustomCommandsTableColumnDefinition::Name => match command_resource {
             Some(res) => view! {
                 <div class="table-cell-cmd-label">
-                    <p>{&res.command_info().label}</p>
+                    <p>
+                        {res
+                            .command_info()
+                            .icon
+                            .clone()
+                            .map(|icon| view! { <span class="custom-command-icon">{icon}</span> })}
+                        {&res.command_info().label}
+                    </p>
+                    {res
+                        .command_info()
+                        .description
+                        .clone()
+                        .map(|description| {
+                            view! { <p class="custom-command-description">{description}</p> }
+                        })}
+                    <ScheduleInfo command_resource=res.clone() />
+                    <CommandHistoryButton command_resource=res.clone() />
                 </div>
             }
             .into_view(),
@@ -167,22 +244,14 @@ fn CommandTableCell<A: ZfsRemoteHighLevel + 'static>(
         },
         CustomCommandsTableColumnDefinition::Stdout => match command_resource {
             Some(ds) => view! {
-                <StringOutputCell
-                    command_resource=ds
-                    extractor=|o| o.stdout.to_string()
-                    output_name="stdout".to_string()
-                />
+                <StringOutputCell command_resource=ds stream=OutputStream::Stdout />
             }
             .into_view(),
             None => view! { <p>"Stdout output"</p> }.into_view(),
         },
         CustomCommandsTableColumnDefinition::Stderr => match command_resource {
             Some(ds) => view! {
-                <StringOutputCell
-                    command_resource=ds
-                    extractor=|o| o.stderr.to_string()
-                    output_name="stderr".to_string()
-                />
+                <StringOutputCell command_resource=ds stream=OutputStream::Stderr />
             }
             .into_view(),
             None => view! { <p>"Stderr output"</p> }.into_view(),
@@ -195,26 +264,100 @@ fn CommandExecuteCell<A: ZfsRemoteHighLevel + 'static>(
     command_resource: CommandResource<A>,
 ) -> impl IntoView {
     let (stdin_in_input, set_stdin_in_input) = create_signal("".to_string());
+    // Files are sent in base64, since they don't have to be text
+    let stdin_mode = command_resource.command_info().stdin_mode;
+    let stdin_encoding = match stdin_mode {
+        StdinMode::Line | StdinMode::Multiline => OutputEncoding::Text,
+        StdinMode::File => OutputEncoding::Base64,
+    };
+    let stdin_error = create_rw_signal(None::<String>);
+
+    // Parameters start with their defaults, where parameters without a default start empty
+    let parameter_values = create_rw_signal(
+        command_resource
+            .command_info()
+            .parameters
+            .iter()
+            .map(|p| (p.name.clone(), p.default.clone().unwrap_or_default()))
+            .collect::<BTreeMap<_, _>>(),
+    );
 
     let command_resource_for_action = command_resource.clone();
 
     // This action takes the action from the user, the click, and sends it to the API to execute the command
-    let call_command = create_action(move |stdin_string: &String| {
-        let command_resource = command_resource_for_action.clone();
-        let stdin_string = command_resource
-            .command_info()
-            .stdin_allow
-            .then_some(stdin_string.clone());
+    let call_command = create_action(
+        move |(stdin_string, confirmation): &(String, Option<String>)| {
+            let command_resource = command_resource_for_action.clone();
+            let options = CustomCommandRunOptions {
+                stdin: command_resource
+                    .command_info()
+                    .stdin_allow
+                    .then_some(stdin_string.clone()),
+                stdin_encoding,
+                parameters: parameter_values.get_untracked(),
+                confirmation: confirmation.clone(),
+            };
+            async move {
+                // We reset first, to trigger the loading animation
+                command_resource.set_command_state_as_loading();
+                command_resource.call_command(options)
+            }
+        },
+    );
+
+    // Commands that require a confirmation only run from the dialog
+    let confirm_open = create_rw_signal(false);
+
+    let command_resource_for_cancel = command_resource.clone();
+    let cancel_command = create_action(move |_: &()| {
+        let command_resource = command_resource_for_cancel.clone();
         async move {
-            // We reset first, to trigger the loading animation
-            command_resource.set_command_state_as_loading();
-            command_resource.call_command(stdin_string)
+            if let Err(e) = command_resource.cancel().await {
+                log(&format!("Cancelling the command failed: {e}"));
+            }
         }
     });
 
     // This contains the text field + submit button objects, depending on whether stdin is allowed or not
     move || {
-        let stdin_field = if command_resource.command_info().stdin_allow {
+        let stdin_field = if !command_resource.command_info().stdin_allow {
+            view! {}.into_view()
+        } else if stdin_mode == StdinMode::Multiline {
+            view! {
+                <textarea
+                    class="custom-command-stdin-multiline"
+                    rows=5
+                    placeholder=command_resource.command_info().stdin_text_placeholder.clone()
+                    on:input=move |ev| {
+                        set_stdin_in_input.set(event_target_value(&ev));
+                    }
+                    prop:value=stdin_in_input
+                ></textarea>
+                <br />
+            }
+            .into_view()
+        } else if stdin_mode == StdinMode::File {
+            let max_bytes = command_resource.command_info().stdin_max_bytes;
+            view! {
+                <input
+                    type="file"
+                    title=command_resource.command_info().stdin_text_placeholder.clone()
+                    on:change=move |ev| {
+                        set_stdin_in_input.set(String::new());
+                        stdin_error.set(None);
+                        let input: web_sys::HtmlInputElement = event_target(&ev);
+                        if let Some(file) = input.files().and_then(|files| files.get(0)) {
+                            read_stdin_file(file, max_bytes, set_stdin_in_input, stdin_error);
+                        }
+                    }
+                />
+                <Show when=move || stdin_error.get().is_some()>
+                    <span class="custom-command-stdin-error">{move || stdin_error.get()}</span>
+                </Show>
+                <br />
+            }
+            .into_view()
+        } else {
             view! {
                 <input
                     type=view! {
@@ -233,20 +376,427 @@ fn CommandExecuteCell<A: ZfsRemoteHighLevel + 'static>(
                 <br />
             }
             .into_view()
-        } else {
-            view! {}.into_view()
         };
+        let parameter_fields = command_resource
+            .command_info()
+            .parameters
+            .iter()
+            .map(|parameter| {
+                view! { <ParameterInput parameter=parameter.clone() values=parameter_values /> }
+            })
+            .collect_view();
+        let is_running = {
+            let command_resource = command_resource.clone();
+            move || matches!(command_resource.get(), OutputExecutionResult::Loading)
+        };
+        let confirmation_dialog = command_resource
+            .command_info()
+            .confirm
+            .clone()
+            .map(|confirm| {
+                view! {
+                    <ConfirmationDialog
+                        confirmation=confirm
+                        endpoint=command_resource.command_info().endpoint.clone()
+                        open=confirm_open
+                        on_confirm=move |token| {
+                            call_command.dispatch((stdin_in_input.get_untracked(), Some(token)));
+                        }
+                    />
+                }
+            });
+        let needs_confirmation = confirmation_dialog.is_some();
+        let unmet_preconditions = {
+            let command_resource = command_resource.clone();
+            move || command_resource.unmet_preconditions()
+        };
+        let unmet_preconditions_for_button = unmet_preconditions.clone();
         view! {
+            {parameter_fields}
             {stdin_field}
-            <button on:click=move |_| {
-                call_command.dispatch(stdin_in_input.get());
-            }>"Execute command"</button>
+            <button
+                disabled=move || !unmet_preconditions_for_button().is_empty()
+                on:click=move |_| {
+                    if needs_confirmation {
+                        confirm_open.set(true);
+                    } else {
+                        call_command.dispatch((stdin_in_input.get(), None));
+                    }
+                }
+            >
+                "Execute command"
+            </button>
+            {move || {
+                unmet_preconditions()
+                    .into_iter()
+                    .map(|reason| view! { <p class="custom-command-precondition">{reason}</p> })
+                    .collect_view()
+            }}
+            {confirmation_dialog}
+            <Show when={
+                let command_resource = command_resource.clone();
+                move || command_resource.busy()
+            }>
+                <span
+                    class="custom-command-busy"
+                    title="Another run is in the way, so the command will wait or be rejected"
+                >
+                    "Busy"
+                </span>
+            </Show>
+            <Show when={
+                let command_resource = command_resource.clone();
+                move || is_running() && command_resource.can_cancel()
+            }>
+                <button
+                    class="custom-command-cancel"
+                    disabled=move || cancel_command.pending().get()
+                    on:click=move |_| cancel_command.dispatch(())
+                >
+                    "Cancel"
+                </button>
+            </Show>
+        }
+    }
+}
+
+fn format_unix_secs(secs: u64) -> String {
+    js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(secs as f64 * 1000.))
+        .to_locale_string("default", &wasm_bindgen::JsValue::UNDEFINED)
+        .into()
+}
+
+/// Reads the file that the user picked for the stdin, and gives it in base64 to `set_stdin`.
+/// Files beyond the limit of the command aren't read.
+fn read_stdin_file(
+    file: web_sys::File,
+    max_bytes: usize,
+    set_stdin: WriteSignal<String>,
+    stdin_error: RwSignal<Option<String>>,
+) {
+    if file.size() > max_bytes as f64 {
+        stdin_error.set(Some(format!(
+            "The file is larger than the limit of {max_bytes} bytes"
+        )));
+        return;
+    }
+
+    spawn_local(async move {
+        match wasm_bindgen_futures::JsFuture::from(file.array_buffer()).await {
+            Ok(buffer) => {
+                let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+                set_stdin.set(BASE64_STANDARD.encode(bytes));
+            }
+            Err(e) => {
+                log(&format!("Reading the file failed: {e:?}"));
+                stdin_error.set(Some("Reading the file failed".to_string()));
+            }
+        }
+    });
+}
+
+/// The schedule of a command that the server runs by itself, with its last and next runs
+#[component]
+fn ScheduleInfo<A: ZfsRemoteHighLevel + 'static>(
+    command_resource: CommandResource<A>,
+) -> impl IntoView {
+    let Some(schedule) = command_resource.command_info().schedule.clone() else {
+        return view! {}.into_view();
+    };
+
+    let status = move || {
+        let status = command_resource.schedule();
+        let last_run = status.as_ref().and_then(|s| s.last_runs.first().cloned());
+        let next_run = status.and_then(|s| s.next_run_at_unix_secs);
+
+        let last_run_text = match &last_run {
+            Some(run) => format_unix_secs(run.started_at_unix_secs),
+            None => "never".to_string(),
+        };
+        let next_run_text = match next_run {
+            Some(secs) => format_unix_secs(secs),
+            None => "-".to_string(),
+        };
+        let last_result = match last_run {
+            Some(run) => match (run.error_code, run.error) {
+                (Some(code), _) => view! {
+                    <span class:custom-command-schedule-failed=code != 0>{code}</span>
+                }
+                .into_view(),
+                (None, error) => view! {
+                    <span class="custom-command-schedule-failed" title=error>
+                        "not run"
+                    </span>
+                }
+                .into_view(),
+            },
+            None => view! { "-" }.into_view(),
+        };
+
+        view! {
+            <br />
+            "Last run: "
+            {last_run_text}
+            <br />
+            "Next run: "
+            {next_run_text}
+            <br />
+            "Last exit code: "
+            {last_result}
+        }
+    };
+
+    view! {
+        <p class="custom-command-schedule">"Scheduled: " {schedule} {status}</p>
+    }
+    .into_view()
+}
+
+/// A button that shows the last runs of the command, as the server keeps them, which are
+/// retrieved whenever it's clicked
+#[component]
+fn CommandHistoryButton<A: ZfsRemoteHighLevel + 'static>(
+    command_resource: CommandResource<A>,
+) -> impl IntoView {
+    let open_dialog = create_rw_signal(false);
+    let history = create_rw_signal(None::<Result<CommandHistory, String>>);
+
+    let on_click = move |_| {
+        history.set(None);
+        open_dialog.set(true);
+        let command_resource = command_resource.clone();
+        spawn_local(async move {
+            let result = command_resource.history().await.map_err(|e| {
+                log(&format!(
+                    "Request for command history returned an error: {e}"
+                ));
+                e.to_string()
+            });
+            history.set(Some(result));
+        });
+    };
+
+    let runs_view = move || match history.get() {
+        None => view! { <p>"Loading..."</p> }.into_view(),
+        Some(Err(e)) => view! { <p class="custom-command-schedule-failed">{e}</p> }.into_view(),
+        Some(Ok(history)) if history.runs.is_empty() => {
+            view! { <p>"The command didn't run yet"</p> }.into_view()
         }
+        Some(Ok(history)) => view! {
+            <table class="custom-command-history">
+                <tr>
+                    <th>"Time"</th>
+                    <th>"Caller"</th>
+                    <th>"Exit code"</th>
+                    <th>"Duration"</th>
+                    <th>"Stdout"</th>
+                    <th>"Stderr"</th>
+                </tr>
+                {history
+                    .runs
+                    .into_iter()
+                    .map(|run| {
+                        let exit_code = match (run.error_code, run.error, run.stopped) {
+                            (_, _, Some(CommandStopReason::TimedOut)) => {
+                                view! { <span>"timed out"</span> }.into_view()
+                            }
+                            (_, _, Some(CommandStopReason::Cancelled)) => {
+                                view! { <span>"cancelled"</span> }.into_view()
+                            }
+                            (Some(code), _, None) => view! {
+                                <span class:custom-command-schedule-failed=code != 0>{code}</span>
+                            }
+                            .into_view(),
+                            (None, error, None) => view! {
+                                <span class="custom-command-schedule-failed" title=error>
+                                    "not run"
+                                </span>
+                            }
+                            .into_view(),
+                        };
+                        let truncated = |output: String, truncated: bool| {
+                            if truncated {
+                                format!("{output}\n[...]")
+                            } else {
+                                output
+                            }
+                        };
+                        view! {
+                            <tr>
+                                <td>{format_unix_secs(run.started_at_unix_secs)}</td>
+                                <td>{run.caller}</td>
+                                <td>{exit_code}</td>
+                                <td>{format!("{:.1} s", run.duration_ms as f64 / 1000.)}</td>
+                                <td>
+                                    <pre>{truncated(run.stdout, run.stdout_truncated)}</pre>
+                                </td>
+                                <td>
+                                    <pre>{truncated(run.stderr, run.stderr_truncated)}</pre>
+                                </td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </table>
+        }
+        .into_view(),
+    };
+
+    view! {
+        <button class="custom-command-history-button" on:click=on_click>
+            "History"
+        </button>
+        <Modal
+            open=open_dialog
+            on_close=move || open_dialog.set(false)
+            children=move || runs_view.into_view().into()
+        />
     }
 }
 
+/// Asks the user to confirm a command before it runs, with a yes/no prompt, or by typing a word.
+/// The confirmation is given to `on_confirm`, to send it with the run.
 #[component]
-fn StdOutputFormatted(output: String, button_label: String) -> impl IntoView {
+fn ConfirmationDialog(
+    confirmation: CommandConfirmation,
+    endpoint: String,
+    open: RwSignal<bool>,
+    #[prop(into)] on_confirm: Callback<String>,
+) -> impl IntoView {
+    let typed = create_rw_signal(String::new());
+    let token = confirmation.token(&endpoint).to_string();
+
+    let can_confirm = {
+        let token = token.clone();
+        let needs_typing = confirmation.typed_word.is_some();
+        move || !needs_typing || typed.get() == token
+    };
+
+    let typed_word_input = confirmation.typed_word.clone().map(|word| {
+        view! {
+            <p>"Type " <b>{word}</b> " to continue"</p>
+            <input
+                type="text"
+                on:input=move |ev| typed.set(event_target_value(&ev))
+                prop:value=typed
+            />
+        }
+    });
+
+    view! {
+        <Modal
+            open=open
+            on_close=move || {
+                open.set(false);
+                typed.set(String::new());
+            }
+            children=move || {
+                view! {
+                    <p class="custom-command-confirm-message">{confirmation.message}</p>
+                    {typed_word_input}
+                    <button
+                        class="custom-command-confirm"
+                        disabled=move || !can_confirm()
+                        on:click=move |_| {
+                            open.set(false);
+                            on_confirm.call(token.clone());
+                        }
+                    >
+                        "Run"
+                    </button>
+                }
+                    .into_view()
+                    .into()
+            }
+        />
+    }
+}
+
+/// An input for one parameter of a custom command, that writes the value in the given map
+#[component]
+fn ParameterInput(
+    parameter: CommandParameter,
+    values: RwSignal<BTreeMap<String, String>>,
+) -> impl IntoView {
+    let name = parameter.name.clone();
+    let value = {
+        let name = name.clone();
+        move || values.with(|v| v.get(&name).cloned().unwrap_or_default())
+    };
+    let set_value = move |new_value: String| {
+        values.update(|v| {
+            v.insert(name.clone(), new_value);
+        })
+    };
+
+    let input = match parameter.kind {
+        CommandParameterType::String => view! {
+            <input
+                type="text"
+                pattern=parameter.regex.clone()
+                on:input=move |ev| set_value(event_target_value(&ev))
+                prop:value=value
+            />
+        }
+        .into_view(),
+        CommandParameterType::Int => view! {
+            <input
+                type="number"
+                step="1"
+                on:input=move |ev| set_value(event_target_value(&ev))
+                prop:value=value
+            />
+        }
+        .into_view(),
+        CommandParameterType::Enum => {
+            let options = parameter
+                .values
+                .iter()
+                .map(|v| {
+                    let v_for_selected = v.clone();
+                    let value = value.clone();
+                    view! {
+                        <option value=v.clone() selected=move || value() == v_for_selected>
+                            {v.clone()}
+                        </option>
+                    }
+                })
+                .collect_view();
+            view! {
+                <select on:change=move |ev| set_value(event_target_value(&ev))>
+                    // Without a default, nothing is chosen until the user picks a value
+                    <option value="" disabled=true selected=parameter.default.is_none()>
+                        "Choose..."
+                    </option>
+                    {options}
+                </select>
+            }
+            .into_view()
+        }
+        CommandParameterType::Bool => view! {
+            <input
+                type="checkbox"
+                on:change=move |ev| set_value(event_target_checked(&ev).to_string())
+                prop:checked=move || value() == "true"
+            />
+        }
+        .into_view(),
+    };
+
+    view! {
+        <label class="custom-command-parameter">{parameter.label} ": " {input}</label>
+        <br />
+    }
+}
+
+#[component]
+pub(super) fn StdOutputFormatted(
+    output: String,
+    button_label: String,
+    /// How the output is structured, where plain text is the default
+    #[prop(optional)]
+    format: OutputFormat,
+) -> impl IntoView {
     let open_dialog = create_rw_signal(false);
 
     if !output.trim().is_empty() {
@@ -255,13 +805,7 @@ fn StdOutputFormatted(output: String, button_label: String) -> impl IntoView {
             <Modal
                 open=open_dialog
                 on_close=move || {}
-                children=move || {
-                    {
-                        view! { <p class="custom-commands-std-output">{output}</p> }
-                    }
-                        .into_view()
-                        .into()
-                }
+                children=move || structured_output_view(output, format).into()
             />
         }
         .into_view()
@@ -270,24 +814,161 @@ fn StdOutputFormatted(output: String, button_label: String) -> impl IntoView {
     }
 }
 
+/// The output as a tree or as a table, depending on its format. Output that doesn't fit its format
+/// is shown as plain text.
+fn structured_output_view(output: String, format: OutputFormat) -> View {
+    let table = |rows: Vec<Vec<String>>, has_header: bool| {
+        let mut rows = rows.into_iter();
+        let header = has_header.then(|| rows.next()).flatten().map(|header| {
+            view! { <tr>{header.into_iter().map(|c| view! { <th>{c}</th> }).collect_view()}</tr> }
+        });
+        view! {
+            <table class="custom-commands-structured-output">
+                <thead>{header}</thead>
+                <tbody>
+                    {rows
+                        .map(|row| {
+                            view! {
+                                <tr>
+                                    {row.into_iter().map(|c| view! { <td>{c}</td> }).collect_view()}
+                                </tr>
+                            }
+                        })
+                        .collect_view()}
+                </tbody>
+            </table>
+        }
+        .into_view()
+    };
+
+    match format {
+        OutputFormat::Json => match serde_json::from_str::<serde_json::Value>(&output) {
+            Ok(value) => view! {
+                <div class="custom-commands-structured-output">{json_tree_view(value)}</div>
+            }
+            .into_view(),
+            Err(_) => structured_output_view(output, OutputFormat::Plain),
+        },
+        OutputFormat::KeyValue => table(
+            parse_key_values(&output)
+                .into_iter()
+                .map(|(key, value)| vec![key, value])
+                .collect(),
+            false,
+        ),
+        OutputFormat::Table => table(parse_table(&output), true),
+        OutputFormat::Plain => {
+            view! { <p class="custom-commands-std-output">{output}</p> }.into_view()
+        }
+    }
+}
+
+/// A JSON value as nested lists, where objects and arrays can be collapsed
+fn json_tree_view(value: serde_json::Value) -> View {
+    let entries = match value {
+        serde_json::Value::Object(map) => map.into_iter().collect::<Vec<_>>(),
+        serde_json::Value::Array(items) => items
+            .into_iter()
+            .enumerate()
+            .map(|(idx, item)| (idx.to_string(), item))
+            .collect(),
+        serde_json::Value::String(s) => {
+            return view! { <span class="json-string">{s}</span> }.into_view()
+        }
+        scalar => {
+            return view! { <span class="json-scalar">{scalar.to_string()}</span> }.into_view()
+        }
+    };
+
+    view! {
+        <ul class="json-tree">
+            {entries
+                .into_iter()
+                .map(|(key, value)| {
+                    if value.is_object() || value.is_array() {
+                        view! {
+                            <li>
+                                <details open>
+                                    <summary class="json-key">{key}</summary>
+                                    {json_tree_view(value)}
+                                </details>
+                            </li>
+                        }
+                    } else {
+                        view! {
+                            <li>
+                                <span class="json-key">{key} ": "</span>
+                                {json_tree_view(value)}
+                            </li>
+                        }
+                    }
+                })
+                .collect_view()}
+        </ul>
+    }
+    .into_view()
+}
+
+fn stream_name(stream: OutputStream) -> &'static str {
+    match stream {
+        OutputStream::Stdout => "stdout",
+        OutputStream::Stderr => "stderr",
+    }
+}
+
+/// One output of a command, with a note when it was truncated, and a link to download it as a file
+#[component]
+fn CommandOutputFormatted(output: RunCommandOutput, stream: OutputStream) -> impl IntoView {
+    let name = stream_name(stream);
+    let encoded = output.output_of(stream).to_string();
+
+    // Binary output is shown as text as far as it can be, while the download has the exact bytes
+    let (text, base64) = match output.encoding {
+        OutputEncoding::Text => (encoded.clone(), BASE64_STANDARD.encode(&encoded)),
+        OutputEncoding::Base64 => {
+            let bytes = BASE64_STANDARD.decode(&encoded).unwrap_or_default();
+            (String::from_utf8_lossy(&bytes).into_owned(), encoded)
+        }
+    };
+    let has_output = !text.is_empty();
+    let file_name = match output.encoding {
+        OutputEncoding::Text => format!("{name}.txt"),
+        OutputEncoding::Base64 => format!("{name}.bin"),
+    };
+
+    // Only text can have a structure, and it's the stdout that has it
+    let format = match (stream, output.encoding) {
+        (OutputStream::Stdout, OutputEncoding::Text) => output.format,
+        _ => OutputFormat::Plain,
+    };
+
+    view! {
+        <StdOutputFormatted output=text button_label=format!("Show {name}") format />
+        <Show when=move || output.is_truncated(stream)>
+            <p class="custom-commands-output-truncated">"Truncated"</p>
+        </Show>
+        <Show when=move || has_output>
+            <a
+                class="custom-commands-output-download"
+                href=format!("data:application/octet-stream;base64,{base64}")
+                download=file_name.clone()
+            >
+                "Download"
+            </a>
+        </Show>
+    }
+}
+
 #[component]
 fn StringOutputCell<A: ZfsRemoteHighLevel + 'static>(
     command_resource: CommandResource<A>,
-    extractor: impl Fn(&RunCommandOutput) -> String + 'static,
-    output_name: String,
+    stream: OutputStream,
 ) -> impl IntoView {
     // This contains the text field + submit button objects, depending on whether stdin is allowed
     let finished_view =
         move |output_result: Result<RunCommandOutput, <A as ZfsRemoteAPI>::Error>| {
             match output_result {
-                Ok(output) => {
-                    view! {
-                        <StdOutputFormatted
-                            output=extractor(&output)
-                            button_label=format!("Show {output_name}")
-                        />
-                    }
-                }
+                Ok(output) => view! { <CommandOutputFormatted output stream /> },
                 Err(e) => view! {
                     "Retrieval of error code failed: "
                     {e.to_string()}
@@ -300,18 +981,73 @@ fn StringOutputCell<A: ZfsRemoteHighLevel + 'static>(
         let command_result = command_resource.get();
         match command_result {
             OutputExecutionResult::InitialState => view! { <NothingToShowIcon /> }.into_view(),
-            OutputExecutionResult::Loading => view! { <RandomLoadingImage /> }.into_view(),
+            OutputExecutionResult::Loading => {
+                // The output shows up as it arrives, while the command runs
+                let command_resource = command_resource.clone();
+                let live_output = move || {
+                    let output = command_resource.live_output().output_of(stream).to_string();
+                    (!output.is_empty()).then(|| {
+                        view! { <pre class="custom-commands-live-output">{output}</pre> }
+                    })
+                };
+                view! {
+                    <RandomLoadingImage />
+                    {live_output}
+                }
+                .into_view()
+            }
             OutputExecutionResult::RanAtLeastOnce(output) => finished_view(output).into_view(),
         }
     }
 }
 
 #[component]
-fn ErrorCodeFromOutput(output: RunCommandOutput) -> impl IntoView {
-    if output.error_code == 0 {
-        view! { <CheckFor0ErrorCode /> }.into_view()
-    } else {
-        view! { <p style="color: red;">{output.error_code}</p> }.into_view()
+pub(super) fn ErrorCodeFromOutput(output: RunCommandOutput) -> impl IntoView {
+    let signal_text = output
+        .signal
+        .map(|s| format!("Terminated by signal {s}"))
+        .unwrap_or_default();
+
+    // In a chain, the command that failed is shown too, with its stderr when hovered
+    let failed_stage = output
+        .failed_stage()
+        .filter(|_| output.stages.len() > 1)
+        .map(|(idx, stage)| {
+            view! {
+                <p class="custom-command-failed-stage" title=stage.stderr.clone()>
+                    {format!(
+                        "Stage {} failed ({}): {}",
+                        idx + 1,
+                        stage.error_code,
+                        stage.argv.join(" "),
+                    )}
+                </p>
+            }
+        });
+
+    let code_view = match output.stopped {
+        Some(CommandStopReason::TimedOut) => {
+            view! { <p style="color: red;" title=signal_text>"Timed out"</p> }.into_view()
+        }
+        Some(CommandStopReason::Cancelled) => {
+            view! { <p style="color: red;" title=signal_text>"Cancelled"</p> }.into_view()
+        }
+        None if output.error_code == 0 => view! { <CheckFor0ErrorCode /> }.into_view(),
+        None if output.signal.is_some() => {
+            view! { <p style="color: red;" title=signal_text.clone()>{signal_text.clone()}</p> }
+                .into_view()
+        }
+        None => view! { <p style="color: red;">{output.error_code}</p> }.into_view(),
+    };
+
+    let meaning = output.exit_code_meaning.clone().map(|meaning| {
+        view! { <p class="custom-command-exit-code-meaning">{meaning}</p> }
+    });
+
+    view! {
+        {code_view}
+        {meaning}
+        {failed_stage}
     }
 }
 
@@ -352,7 +1088,7 @@ fn CheckFor0ErrorCode() -> impl IntoView {
 }
 
 #[component]
-fn NothingToShowIcon() -> impl IntoView {
+pub(super) fn NothingToShowIcon() -> impl IntoView {
     view! {
         <div style="font-size: 1em; color: #8f39d3;" title="Nothing to show">
             <Icon icon=icondata::LuCircleSlash2 style="color: gray" />
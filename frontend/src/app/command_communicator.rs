@@ -1,12 +1,23 @@
+use std::{collections::BTreeMap, time::Duration};
+
 use common::{
     api::traits::{ZfsRemoteAPI, ZfsRemoteHighLevel},
-    types::{CustomCommandPublicInfo, RunCommandOutput},
+    types::{
+        CommandHistory, CommandOutputChunk, CommandScheduleStatus, CustomCommandPublicInfo,
+        CustomCommandRunOptions, DatasetFullMountState, JobId, JobOutput, JobState, OutputStream,
+        RunCommandOutput, ServerEvent,
+    },
 };
 use leptos::{
-    create_local_resource, create_signal, ReadSignal, Resource, SignalGet, SignalGetUntracked,
-    SignalSet, WriteSignal,
+    create_effect, create_local_resource, create_rw_signal, create_signal, on_cleanup,
+    set_interval_with_handle, spawn_local, untrack, ReadSignal, Resource, RwSignal, SignalGet,
+    SignalGetUntracked, SignalSet, SignalUpdate, SignalWith, WriteSignal,
 };
 
+use super::server_events::use_server_events;
+
+const JOB_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone)]
 pub enum OutputExecutionResult<T> {
     InitialState,
@@ -19,13 +30,23 @@ pub enum OutputExecutionResult<T> {
 pub struct CommandResource<A: ZfsRemoteHighLevel> {
     command_info: CustomCommandPublicInfo,
     res: Resource<(), OutputExecutionResult<Result<RunCommandOutput, <A as ZfsRemoteAPI>::Error>>>,
-    set_stdin: WriteSignal<Option<String>>,
+    set_options: WriteSignal<CustomCommandRunOptions>,
+    live_output: RwSignal<RunCommandOutput>,
+    busy: RwSignal<bool>,
+    /// The job of the run that this client started or recovered, while it runs
+    job: RwSignal<Option<JobId>>,
+    schedule: RwSignal<Option<CommandScheduleStatus>>,
+    /// The states of the datasets of the preconditions, once they're known
+    dataset_states: RwSignal<Option<BTreeMap<String, DatasetFullMountState>>>,
+    api: A,
 }
 
 impl<A: ZfsRemoteHighLevel + 'static> CommandResource<A> {
     fn make_resource(
         api: A,
-        stdin_signal: ReadSignal<Option<String>>,
+        options_signal: ReadSignal<CustomCommandRunOptions>,
+        live_output: RwSignal<RunCommandOutput>,
+        job: RwSignal<Option<JobId>>,
         command_info: CustomCommandPublicInfo,
         log_func: &'static impl Fn(&str),
     ) -> Resource<(), OutputExecutionResult<Result<RunCommandOutput, <A as ZfsRemoteAPI>::Error>>>
@@ -39,9 +60,22 @@ impl<A: ZfsRemoteHighLevel + 'static> CommandResource<A> {
                 let endpoint = command_info.endpoint.clone();
                 async move {
                     if first_run.get_untracked() {
+                        let on_output = |chunk: CommandOutputChunk| {
+                            live_output.update(|output| match chunk.stream {
+                                OutputStream::Stdout => output.stdout.push_str(&chunk.data),
+                                OutputStream::Stderr => output.stderr.push_str(&chunk.data),
+                            })
+                        };
+                        let on_started = |job_id| job.set(Some(job_id));
                         let command_run_result = api
-                            .call_custom_command(&endpoint, stdin_signal.get().as_deref())
+                            .call_custom_command_streaming(
+                                &endpoint,
+                                &options_signal.get(),
+                                &on_started,
+                                &on_output,
+                            )
                             .await;
+                        job.set(None);
                         if let Err(ref op_err) = command_run_result {
                             log_func(&format!(
                                 "Request to retrieve datasets returned an error: {op_err}"
@@ -64,11 +98,207 @@ impl<A: ZfsRemoteHighLevel + 'static> CommandResource<A> {
         api: A,
         log_func: &'static impl Fn(&str),
     ) -> Self {
-        let (stdin, set_stdin) = create_signal(None);
-        Self {
-            res: Self::make_resource(api, stdin, command_info.clone(), log_func),
+        let (options, set_options) = create_signal(CustomCommandRunOptions::default());
+        let live_output = create_rw_signal(RunCommandOutput::default());
+        let busy = create_rw_signal(command_info.busy);
+        let job = create_rw_signal(None);
+        let schedule = create_rw_signal(None);
+        let dataset_states = create_rw_signal(None::<BTreeMap<_, _>>);
+        let res = Self::make_resource(
+            api.clone(),
+            options,
+            live_output,
+            job,
+            command_info.clone(),
+            log_func,
+        );
+
+        // Commands that other clients run show up here too
+        if let Some(server_events) = use_server_events() {
+            let endpoint = command_info.endpoint.clone();
+            let preconditions = command_info.preconditions.clone();
+            create_effect(move |_| match server_events.get() {
+                Some(ServerEvent::CommandStarted { endpoint: e }) if e == endpoint => {
+                    res.set(OutputExecutionResult::Loading);
+                }
+                Some(ServerEvent::CommandFinished {
+                    endpoint: e,
+                    output,
+                }) if e == endpoint => {
+                    res.set(OutputExecutionResult::RanAtLeastOnce(Ok(output)));
+                }
+                // The error type of the API can't be made from a string, and the client that ran
+                // the command gets the error anyway, so only other clients stop showing it as running
+                Some(ServerEvent::CommandFailed { endpoint: e, .. }) if e == endpoint => {
+                    if matches!(untrack(|| res.get()), Some(OutputExecutionResult::Loading)) {
+                        res.set(OutputExecutionResult::InitialState);
+                    }
+                }
+                Some(ServerEvent::CommandsBusy { endpoints }) => {
+                    busy.set(endpoints.contains(&endpoint));
+                }
+                Some(ServerEvent::ScheduleChanged { status }) if status.endpoint == endpoint => {
+                    schedule.set(Some(status));
+                }
+                Some(ServerEvent::DatasetChanged { state })
+                    if preconditions.involves(&state.dataset_name) =>
+                {
+                    dataset_states.update(|states| {
+                        if let Some(states) = states {
+                            states.insert(state.dataset_name.clone(), state);
+                        }
+                    });
+                }
+                Some(ServerEvent::DatasetRemoved { dataset_name })
+                    if preconditions.involves(&dataset_name) =>
+                {
+                    dataset_states.update(|states| {
+                        if let Some(states) = states {
+                            states.remove(&dataset_name);
+                        }
+                    });
+                }
+                _ => {}
+            });
+        }
+
+        let this = Self {
+            res,
             command_info,
-            set_stdin,
+            set_options,
+            live_output,
+            busy,
+            job,
+            schedule,
+            dataset_states,
+            api,
+        };
+        this.recover_last_job(log_func);
+        if this.command_info.schedule.is_some() {
+            this.retrieve_schedule(log_func);
+        }
+        if !this.command_info.preconditions.is_empty() {
+            this.retrieve_dataset_states(log_func);
+        }
+
+        this
+    }
+
+    /// Shows the last run of the command on the server, which survives a reload of the page.
+    /// If it's still running, it's polled until it finishes.
+    fn recover_last_job(&self, log_func: &'static impl Fn(&str)) {
+        let polled_job = create_rw_signal(None::<JobId>);
+
+        let this = self.clone();
+        spawn_local(async move {
+            let last_job = match this.api.list_jobs().await {
+                Ok(jobs) => jobs
+                    .jobs
+                    .into_iter()
+                    .find(|j| j.endpoint == this.command_info.endpoint),
+                Err(e) => {
+                    log_func(&format!("Request to list jobs returned an error: {e}"));
+                    None
+                }
+            };
+            if let Some(job) = last_job {
+                match this.api.job_output(job.job_id).await {
+                    Ok(job) => {
+                        if job.status.state == JobState::Running {
+                            polled_job.set(Some(job.status.job_id));
+                            this.job.set(Some(job.status.job_id));
+                        }
+                        this.show_job(job);
+                    }
+                    Err(e) => log_func(&format!("Request for job output returned an error: {e}")),
+                }
+            }
+        });
+
+        let this = self.clone();
+        let poll_handle = set_interval_with_handle(
+            move || {
+                let Some(job_id) = polled_job.get_untracked() else {
+                    return;
+                };
+                let this = this.clone();
+                spawn_local(async move {
+                    match this.api.job_output(job_id).await {
+                        Ok(job) => {
+                            if job.status.state != JobState::Running {
+                                polled_job.set(None);
+                                this.job.set(None);
+                            }
+                            this.show_job(job);
+                        }
+                        // The job is gone, e.g., because the server restarted
+                        Err(e) => {
+                            polled_job.set(None);
+                            this.job.set(None);
+                            log_func(&format!("Request for job output returned an error: {e}"));
+                        }
+                    }
+                });
+            },
+            JOB_POLL_INTERVAL,
+        );
+        if let Ok(handle) = poll_handle {
+            on_cleanup(move || handle.clear());
+        }
+    }
+
+    /// Gets the next run and the last results of the scheduled command, which are then
+    /// kept up to date by the server events
+    fn retrieve_schedule(&self, log_func: &'static impl Fn(&str)) {
+        let this = self.clone();
+        spawn_local(async move {
+            match this.api.schedule_status().await {
+                Ok(status) => this.schedule.set(
+                    status
+                        .commands
+                        .into_iter()
+                        .find(|c| c.endpoint == this.command_info.endpoint),
+                ),
+                Err(e) => log_func(&format!(
+                    "Request for schedule status returned an error: {e}"
+                )),
+            }
+        });
+    }
+
+    /// Gets the states of the datasets that the command needs, which are then kept up to date
+    /// by the server events
+    fn retrieve_dataset_states(&self, log_func: &'static impl Fn(&str)) {
+        let this = self.clone();
+        spawn_local(async move {
+            match this.api.encrypted_datasets_state().await {
+                Ok(datasets) => this.dataset_states.set(Some(datasets.states)),
+                Err(e) => log_func(&format!(
+                    "Request for datasets state returned an error: {e}"
+                )),
+            }
+        });
+    }
+
+    fn show_job(&self, job: JobOutput) {
+        match job.status.state {
+            JobState::Running => {
+                self.live_output.set(job.output);
+                self.res.set(OutputExecutionResult::Loading);
+            }
+            JobState::Finished => {
+                self.res
+                    .set(OutputExecutionResult::RanAtLeastOnce(Ok(job.output)));
+            }
+            // Like with the failure events, the error can't be made into the API's error type
+            JobState::Failed => {
+                if matches!(
+                    untrack(|| self.res.get()),
+                    Some(OutputExecutionResult::Loading)
+                ) {
+                    self.res.set(OutputExecutionResult::InitialState);
+                }
+            }
         }
     }
 
@@ -80,11 +310,56 @@ impl<A: ZfsRemoteHighLevel + 'static> CommandResource<A> {
         self.res.set(OutputExecutionResult::Loading);
     }
 
-    pub fn call_command(&self, stdin_string: Option<String>) {
-        self.set_stdin.set(stdin_string);
+    /// The output that arrived so far, while the command runs
+    pub fn live_output(&self) -> RunCommandOutput {
+        self.live_output.get()
+    }
+
+    /// Whether the server can't run the command now, because of its concurrency limits
+    pub fn busy(&self) -> bool {
+        self.busy.get()
+    }
+
+    /// The next run and the last results, if the server runs the command by itself
+    pub fn schedule(&self) -> Option<CommandScheduleStatus> {
+        self.schedule.get()
+    }
+
+    /// The last runs of the command, newest first, as the server keeps them
+    pub async fn history(&self) -> Result<CommandHistory, <A as ZfsRemoteAPI>::Error> {
+        self.api.command_history(&self.command_info.endpoint).await
+    }
+
+    /// Why the command can't run yet, because of the states of the datasets it needs.
+    /// Until the states are known, the server is left to check.
+    pub fn unmet_preconditions(&self) -> Vec<String> {
+        self.dataset_states.with(|states| match states {
+            Some(states) => self.command_info.preconditions.unmet(states),
+            None => Vec::new(),
+        })
+    }
+
+    pub fn call_command(&self, options: CustomCommandRunOptions) {
+        self.live_output.set(RunCommandOutput::default());
+        self.set_options.set(options);
         self.res.refetch();
     }
 
+    /// Whether the command runs because of this client, so that its run can be cancelled
+    pub fn can_cancel(&self) -> bool {
+        self.job.get().is_some()
+    }
+
+    /// Stops the run of this client, which then finishes with an output that shows it was
+    /// cancelled. The runs of other clients and of the schedule are left alone.
+    pub async fn cancel(&self) -> Result<(), <A as ZfsRemoteAPI>::Error> {
+        let Some(job_id) = self.job.get_untracked() else {
+            return Ok(());
+        };
+        let mut api = self.api.clone();
+        api.cancel_job(job_id).await.map(|_| ())
+    }
+
     pub fn get(
         &self,
     ) -> OutputExecutionResult<Result<RunCommandOutput, <A as ZfsRemoteAPI>::Error>> {
@@ -1,9 +1,14 @@
 use common::{
     api::traits::{ZfsRemoteAPI, ZfsRemoteHighLevel},
-    types::DatasetFullMountState,
+    types::{DatasetFullMountState, HookOutput, ServerEvent},
 };
 use futures::FutureExt;
-use leptos::{create_local_resource, Resource, SignalGet, SignalSet};
+use leptos::{
+    create_effect, create_local_resource, create_rw_signal, Resource, RwSignal, SignalGet,
+    SignalSet,
+};
+
+use super::server_events::use_server_events;
 
 #[must_use]
 #[derive(Debug, Clone)]
@@ -11,6 +16,8 @@ pub struct DatasetStateResource<A: ZfsRemoteHighLevel> {
     dataset_name: String,
     api: A,
     res: Resource<(), Option<Result<DatasetFullMountState, <A as ZfsRemoteAPI>::Error>>>,
+    /// The results of the hooks that ran after the last key load or mount
+    hook_outputs: RwSignal<Vec<HookOutput>>,
 }
 
 impl<A: ZfsRemoteHighLevel + 'static> DatasetStateResource<A> {
@@ -41,8 +48,23 @@ impl<A: ZfsRemoteHighLevel + 'static> DatasetStateResource<A> {
     }
 
     pub fn new(dataset_name: String, api: A, log_func: &'static impl Fn(&str)) -> Self {
+        let res = Self::make_resource(api.clone(), dataset_name.clone(), log_func);
+
+        // Changes that other clients make show up without refreshing
+        if let Some(server_events) = use_server_events() {
+            let dataset_name = dataset_name.clone();
+            create_effect(move |_| {
+                if let Some(ServerEvent::DatasetChanged { state }) = server_events.get() {
+                    if state.dataset_name == dataset_name {
+                        res.set(Some(Ok(state)));
+                    }
+                }
+            });
+        }
+
         Self {
-            res: Self::make_resource(api.clone(), dataset_name.clone(), log_func),
+            res,
+            hook_outputs: create_rw_signal(Vec::new()),
             dataset_name,
             api,
         }
@@ -71,4 +93,12 @@ impl<A: ZfsRemoteHighLevel + 'static> DatasetStateResource<A> {
         // 2. The Option that we manually added, so that we set it to None when the user clicks on "Submit"
         self.res.get().flatten()
     }
+
+    pub fn hook_outputs(&self) -> Vec<HookOutput> {
+        self.hook_outputs.get()
+    }
+
+    pub fn set_hook_outputs(&self, hook_outputs: Vec<HookOutput>) {
+        self.hook_outputs.set(hook_outputs);
+    }
 }
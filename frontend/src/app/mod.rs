@@ -1,22 +1,39 @@
+mod boot_status;
 mod browser_helpers;
 mod cmds;
 mod command_communicator;
 mod config_reader;
 mod dataset_state_retriever;
 mod modal;
+mod pools;
+mod server_events;
+mod status_strip;
+mod unlock_groups;
 mod zfs;
 
+use boot_status::BootStatusBanner;
 use browser_helpers::{get_value_from_storage, set_value_in_storage};
 use cmds::CommandsTable;
 use common::{
-    api::{api_wrapper::ApiAny, mock::ApiMock, routed::ApiRouteImpl, traits::ZfsRemoteHighLevel},
+    api::{
+        api_wrapper::ApiAny,
+        mock::ApiMock,
+        multi_host::{Host, MultiHostApi},
+        routed::ApiRouteImpl,
+        traits::ZfsRemoteHighLevel,
+    },
     config::WebPageConfig,
 };
 use config_reader::retrieve_config;
 use leptos::{
-    component, create_local_resource, create_signal, event_target_value, view, CollectView, Errors,
-    IntoView, RwSignal, SignalGet, SignalSet, SignalUpdate, SignalWith, WriteSignal,
+    component, create_local_resource, create_signal, create_trigger, event_target_value, view,
+    CollectView, Errors, IntoView, RwSignal, SignalGet, SignalSet, SignalUpdate, SignalWith,
+    WriteSignal,
 };
+use pools::ZfsImportablePoolsTable;
+use server_events::provide_server_events;
+use status_strip::StatusStrip;
+use unlock_groups::ZfsUnlockGroupsTable;
 use zfs::ZfsUnlockTable;
 
 use crate::images::RandomLoadingImage;
@@ -81,14 +98,14 @@ fn ContentsPage(
         create_local_resource(|| (), move |_| async { retrieve_config().await });
 
     let api_from_config_getter =
-        move || configuration_getter.and_then(|config| api_from_config(config.clone()));
+        move || configuration_getter.and_then(|config| hosts_from_config(config.clone()));
 
     let contents_page_on_config = {
         move || {
             view! {
                 {match api_from_config_getter() {
-                    Some(Ok(api)) => {
-                        view! { <TablesPage api=api.clone() contents_page_setter /> }.into_view()
+                    Some(Ok(hosts)) => {
+                        view! { <HostsPage hosts=hosts.clone() contents_page_setter /> }.into_view()
                     }
                     Some(Err(err)) => {
                         view! { <ConfigConnectError err contents_page_setter /> }.into_view()
@@ -107,8 +124,8 @@ fn ContentsPage(
     };
 
     let contents_page_on_base_url = move |url: &String| {
-        let api = api_from_config(WebPageConfig::from_base_url(url));
-        view! { <TablesPage api contents_page_setter /> }.into_view()
+        let hosts = hosts_from_config(WebPageConfig::from_base_url(url));
+        view! { <HostsPage hosts contents_page_setter /> }.into_view()
     };
 
     // Choose API from a given URL or load the info from a config file
@@ -155,47 +172,93 @@ fn EnterAPIAddress(contents_page_setter: WriteSignal<leptos::View>) -> impl Into
     }
 }
 
+/// Shows a section for every host. A single host is shown without a section,
+/// and failing to connect offers to enter another address.
 #[component]
-fn TablesPage<A: ZfsRemoteHighLevel + 'static>(
-    api: A,
+fn HostsPage<A: ZfsRemoteHighLevel + 'static>(
+    hosts: MultiHostApi<A>,
     contents_page_setter: WriteSignal<leptos::View>,
 ) -> impl IntoView {
-    let api_for_tester = api.clone();
-    let api_tester = create_local_resource(
+    let hosts_for_tester = hosts.clone();
+    let connections = create_local_resource(
         || (),
         move |_| {
-            let api = api_for_tester.clone();
-            async move { api.clone().test_connection().await }
+            let hosts = hosts_for_tester.clone();
+            async move { hosts.test_connections().await }
         },
     );
 
-    let main_page_view = view! {
-        {move || match api_tester.get() {
-            Some(Ok(_)) => {
-                view! {
-                    <h3 align="center">"Custom commands"</h3>
-                    <CommandsTable api=api.clone() />
-                    <hr />
-                    <h3 align="center">"ZFS datasets"</h3>
-                    <ZfsUnlockTable api=api.clone() />
-                }
-                    .into_view()
-            }
-            Some(Err(err)) => {
-                view! { <ConfigConnectError err contents_page_setter /> }
-            }
-            None => {
-                view! {
-                    <div class="config-loading-page">
-                        <RandomLoadingImage />
-                    </div>
+    let single_host = hosts.hosts().len() == 1;
+
+    move || match connections.get() {
+        Some(connections) => hosts
+            .hosts()
+            .iter()
+            .zip(connections)
+            .map(|(host, (_name, connection))| match connection {
+                Ok(()) if single_host => view! { <TablesPage api=host.api.clone() /> }.into_view(),
+                Err(err) if single_host => {
+                    view! { <ConfigConnectError err contents_page_setter /> }
                 }
-                    .into_view()
-            }
-        }}
+                connection => view! { <HostSection host=host.clone() connection /> }.into_view(),
+            })
+            .collect_view(),
+        None => view! {
+            <div class="config-loading-page">
+                <RandomLoadingImage />
+            </div>
+        }
+        .into_view(),
+    }
+}
+
+#[component]
+fn HostSection<A: ZfsRemoteHighLevel + 'static>(
+    host: Host<A>,
+    connection: Result<(), A::Error>,
+) -> impl IntoView {
+    let contents = match connection {
+        Ok(()) => view! {
+            <p class="host-status host-status-connected">"Connected"</p>
+            <TablesPage api=host.api />
+        }
+        .into_view(),
+        Err(err) => view! {
+            <p class="host-status host-status-error">"Connection failed"</p>
+            <ToggleText to_show=err.to_string() to_show_name="error".to_string() />
+        }
+        .into_view(),
     };
 
-    main_page_view.into_view()
+    view! {
+        <section class="host-section">
+            <h2 align="center">{host.name}</h2>
+            {contents}
+        </section>
+    }
+}
+
+#[component]
+fn TablesPage<A: ZfsRemoteHighLevel + 'static>(api: A) -> impl IntoView {
+    // Importing a pool makes new datasets available, and unlocking a group changes the state
+    // of many datasets, so the datasets table gets reloaded
+    let datasets_changed = create_trigger();
+
+    provide_server_events(&api);
+
+    view! {
+        <StatusStrip api=api.clone() />
+        <BootStatusBanner api=api.clone() datasets_changed />
+        <h3 align="center">"Custom commands"</h3>
+        <CommandsTable api=api.clone() />
+        <hr />
+        <ZfsUnlockGroupsTable api=api.clone() datasets_changed />
+        <h3 align="center">"ZFS datasets"</h3>
+        <ZfsUnlockTable api=api.clone() datasets_changed />
+        <hr />
+        <h3 align="center">"Importable ZFS pools"</h3>
+        <ZfsImportablePoolsTable api=api.clone() pool_imported=datasets_changed />
+    }
 }
 
 #[component]
@@ -214,17 +277,28 @@ fn ConfigConnectError(
     .into_view()
 }
 
-fn api_from_config(config: WebPageConfig) -> ApiAny {
-    match config.mode.clone() {
+fn hosts_from_config(config: WebPageConfig) -> MultiHostApi<ApiAny> {
+    let hosts = match config.mode.clone() {
         common::config::LiveOrMock::Live(s) => {
-            log("Initializing live object");
-            ApiAny::Live(ApiRouteImpl::new_from_config(s))
+            log("Initializing live objects");
+            s.all_hosts()
+                .into_iter()
+                .map(|h| Host {
+                    name: h.name.clone(),
+                    api: ApiAny::Live(ApiRouteImpl::new_from_config(h)),
+                })
+                .collect()
         }
         common::config::LiveOrMock::Mock(m) => {
             log("Initializing mock object");
-            ApiAny::Mock(ApiMock::new_from_config(m))
+            vec![Host {
+                name: "Mock".to_string(),
+                api: ApiAny::Mock(ApiMock::new_from_config(m)),
+            }]
         }
-    }
+    };
+
+    MultiHostApi::new(hosts)
 }
 
 #[component]
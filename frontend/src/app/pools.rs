@@ -0,0 +1,223 @@
+use common::{
+    api::traits::ZfsRemoteHighLevel,
+    types::{ImportablePool, ImportablePools, PoolImportOptions},
+};
+use leptos::{
+    component, create_action, create_local_resource, create_signal, event_target_checked,
+    event_target_value, view, CollectView, ErrorBoundary, IntoView, Show, SignalGet, SignalSet,
+    Transition, Trigger,
+};
+
+use crate::{
+    app::{error_fallback, log},
+    images::RandomLoadingImage,
+};
+
+async fn pools_table_initial_query<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+) -> Result<(A, ImportablePools), A::Error> {
+    let result = api.importable_pools().await;
+
+    result.map(|r| (api, r))
+}
+
+/// Shows the pools that can be imported, which is useful after an unclean reboot,
+/// where the pools may not be imported, and hence no datasets can be found.
+#[component]
+pub fn ZfsImportablePoolsTable<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    /// Notified after a pool is imported, so that the datasets can be reloaded
+    pool_imported: Trigger,
+) -> impl IntoView {
+    log("Creating ZFS importable pools table");
+
+    let pools_rows = create_local_resource(
+        move || pool_imported.track(),
+        move |_| {
+            let api = api.clone();
+            async move { pools_table_initial_query(api).await }
+        },
+    );
+
+    let pools_table_view = move || {
+        pools_rows.and_then(|(api, rows)| {
+            view! { <ZfsPoolsTable api=api.clone() importable_pools=rows pool_imported /> }
+        })
+    };
+
+    view! {
+        <ErrorBoundary fallback=error_fallback>
+            <Transition fallback=move || {
+                view! {
+                    <div class="zfs-loading-page">
+                        <RandomLoadingImage />
+                    </div>
+                }
+            }>
+                <div>{pools_table_view}</div>
+            </Transition>
+        </ErrorBoundary>
+    }
+}
+
+#[component]
+fn ZfsPoolImportInput<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    pool_name: String,
+    pool_imported: Trigger,
+) -> impl IntoView {
+    let (readonly, set_readonly) = create_signal(false);
+    let (altroot, set_altroot) = create_signal(String::new());
+    let (import_error, set_import_error) = create_signal(None::<String>);
+
+    // This action takes the action from the user, the click, and sends it to the API to import the pool
+    let import_pool = create_action(move |options: &PoolImportOptions| {
+        let mut api = api.clone();
+        let pool_name = pool_name.clone();
+        let options = options.clone();
+        async move {
+            match api.import_pool(&pool_name, &options).await {
+                Ok(_) => {
+                    log("Pool import success");
+                    set_import_error.set(None);
+                    pool_imported.notify();
+                }
+                Err(e) => {
+                    log(&format!("Pool import error: {e}"));
+                    set_import_error.set(Some(e.to_string()));
+                }
+            }
+        }
+    });
+
+    move || {
+        if import_pool.pending().get() {
+            return view! { <RandomLoadingImage /> }.into_view();
+        }
+
+        view! {
+            <label>
+                <input
+                    type="checkbox"
+                    on:change=move |ev| {
+                        set_readonly.set(event_target_checked(&ev));
+                    }
+                    prop:checked=readonly
+                />
+                "Read-only"
+            </label>
+            <input
+                type="text"
+                placeholder="Alternate root (optional)"
+                on:input=move |ev| {
+                    set_altroot.set(event_target_value(&ev));
+                }
+                prop:value=altroot
+            />
+            <button on:click=move |_| {
+                let altroot = altroot.get();
+                import_pool
+                    .dispatch(PoolImportOptions {
+                        readonly: readonly.get(),
+                        altroot: (!altroot.trim().is_empty()).then_some(altroot),
+                    });
+            }>"Import pool"</button>
+            {move || {
+                import_error
+                    .get()
+                    .map(|e| {
+                        view! {
+                            <p style="color: red;">"Import error: " {e}</p>
+                        }
+                    })
+            }}
+        }
+        .into_view()
+    }
+}
+
+#[component]
+fn ZfsPoolRow<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    pool: Option<ImportablePool>,
+    pool_imported: Trigger,
+) -> impl IntoView {
+    match pool {
+        Some(pool) => view! {
+            <tr>
+                <th>
+                    <div class="table-cell-dataset-name">
+                        <p>{pool.pool_name.clone()}</p>
+                    </div>
+                </th>
+                <th>
+                    <p>{pool.pool_id}</p>
+                </th>
+                <th>
+                    <p>{pool.state}</p>
+                </th>
+                <th>
+                    <ZfsPoolImportInput api pool_name=pool.pool_name pool_imported />
+                </th>
+            </tr>
+        }
+        .into_view(),
+        None => view! {
+            <tr>
+                <th>
+                    <p>"Pool name"</p>
+                </th>
+                <th>
+                    <p>"Pool ID"</p>
+                </th>
+                <th>
+                    <p>"State"</p>
+                </th>
+                <th>
+                    <p>"Import"</p>
+                </th>
+            </tr>
+        }
+        .into_view(),
+    }
+}
+
+#[allow(clippy::needless_lifetimes)]
+#[component]
+fn ZfsPoolsTable<'a, A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    importable_pools: &'a ImportablePools,
+    pool_imported: Trigger,
+) -> impl IntoView {
+    let has_pools = !importable_pools.pools.is_empty();
+
+    let pools = (*importable_pools).clone();
+
+    view! {
+        <div class="zfs-pools-table-container">
+            <Show when=move || has_pools fallback=|| view! { <NothingToImport /> }>
+                <table class="zfs-pools-table">
+                    <thead>
+                        <ZfsPoolRow api=api.clone() pool=None pool_imported />
+                    </thead>
+                    <tbody>
+                        {pools
+                            .pools
+                            .values()
+                            .map(|pool| {
+                                view! {
+                                    <ZfsPoolRow api=api.clone() pool=Some(pool.clone()) pool_imported />
+                                }
+                            })
+                            .collect_view()}
+                    </tbody>
+                </table>
+            </Show>
+        </div>
+    }
+}
+
+#[component]
+fn NothingToImport() -> impl IntoView {
+    view! { <p align="center">"No ZFS pools available to import"</p> }
+}
@@ -0,0 +1,46 @@
+use common::{api::traits::ZfsRemoteHighLevel, types::ServerEvent};
+use leptos::{create_signal, on_cleanup, provide_context, use_context, ReadSignal, SignalSet};
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{EventSource, MessageEvent};
+
+use crate::app::log;
+
+/// The latest event that the server broadcast. Provided as a context, so that any component
+/// can react to changes made by other clients.
+#[derive(Clone, Copy)]
+struct ServerEvents(ReadSignal<Option<ServerEvent>>);
+
+/// Connects to the server's events stream (if the API has one), and provides the events to the children
+pub fn provide_server_events<A: ZfsRemoteHighLevel + 'static>(api: &A) {
+    let (event, set_event) = create_signal(None);
+
+    if let Some(url) = api.events_url() {
+        match EventSource::new(&url) {
+            Ok(source) => {
+                let on_message = Closure::<dyn Fn(MessageEvent)>::new(move |ev: MessageEvent| {
+                    let Some(data) = ev.data().as_string() else {
+                        return;
+                    };
+                    match serde_json::from_str::<ServerEvent>(&data) {
+                        Ok(server_event) => set_event.set(Some(server_event)),
+                        Err(e) => log(&format!("Failed to parse server event: {e}")),
+                    }
+                });
+                source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+                on_cleanup(move || {
+                    source.close();
+                    drop(on_message);
+                });
+            }
+            Err(e) => log(&format!("Failed to connect to server events: {e:?}")),
+        }
+    }
+
+    provide_context(ServerEvents(event));
+}
+
+/// The latest server event, if events were provided
+pub fn use_server_events() -> Option<ReadSignal<Option<ServerEvent>>> {
+    use_context::<ServerEvents>().map(|events| events.0)
+}
@@ -0,0 +1,168 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use common::{
+    api::traits::ZfsRemoteHighLevel,
+    types::{CommandKind, CommandStatus, CustomCommandPublicInfo, ServerEvent},
+};
+use leptos::{
+    component, create_effect, create_local_resource, create_rw_signal, on_cleanup,
+    set_interval_with_handle, spawn_local, view, CollectView, IntoView, RwSignal, SignalGet,
+    SignalUpdate,
+};
+
+use crate::app::{log, server_events::use_server_events};
+
+/// Gets the statuses that the server checked last, and puts them with the known ones
+fn fetch_statuses<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    statuses: RwSignal<BTreeMap<String, CommandStatus>>,
+) {
+    spawn_local(async move {
+        match api.command_statuses().await {
+            Ok(fetched) => statuses.update(|statuses| {
+                for status in fetched.commands {
+                    statuses.insert(status.endpoint.clone(), status);
+                }
+            }),
+            Err(e) => log(&format!(
+                "Request for command statuses returned an error: {e}"
+            )),
+        }
+    });
+}
+
+/// The status commands of the server, as badges that are green when the command is healthy,
+/// and red otherwise. Nothing is shown if the server has no status commands.
+/// The server checks the commands by itself, and sends the results to all the clients.
+#[component]
+pub fn StatusStrip<A: ZfsRemoteHighLevel + 'static>(api: A) -> impl IntoView {
+    let statuses = create_rw_signal(BTreeMap::<String, CommandStatus>::new());
+
+    let status_commands = {
+        let api = api.clone();
+        create_local_resource(
+            || (),
+            move |_| {
+                let api = api.clone();
+                async move {
+                    match api.list_available_commands().await {
+                        Ok(commands) => Some(
+                            commands
+                                .commands
+                                .into_iter()
+                                .filter(|c| c.kind == CommandKind::Status)
+                                .collect::<Vec<_>>(),
+                        ),
+                        Err(e) => {
+                            log(&format!(
+                                "Request for status commands returned an error: {e}"
+                            ));
+                            None
+                        }
+                    }
+                }
+            },
+        )
+    };
+
+    fetch_statuses(api.clone(), statuses);
+
+    if let Some(server_events) = use_server_events() {
+        create_effect(move |_| {
+            if let Some(ServerEvent::StatusChanged { status }) = server_events.get() {
+                statuses.update(|statuses| {
+                    statuses.insert(status.endpoint.clone(), status);
+                });
+            }
+        });
+    }
+
+    // Without server events, the results are fetched as often as the most frequent check
+    if api.events_url().is_none() {
+        let api = api.clone();
+        create_effect(move |_| {
+            let Some(interval_secs) = status_commands
+                .get()
+                .flatten()
+                .and_then(|commands| commands.iter().filter_map(|c| c.status_interval_secs).min())
+            else {
+                return;
+            };
+
+            let api = api.clone();
+            let poll_handle = set_interval_with_handle(
+                move || fetch_statuses(api.clone(), statuses),
+                Duration::from_secs(interval_secs.max(1)),
+            );
+            if let Ok(handle) = poll_handle {
+                on_cleanup(move || handle.clear());
+            }
+        });
+    }
+
+    move || {
+        status_commands
+            .get()
+            .flatten()
+            .filter(|commands| !commands.is_empty())
+            .map(|commands| {
+                view! {
+                    <div class="status-strip">
+                        {commands
+                            .into_iter()
+                            .map(|command_info| {
+                                view! { <StatusBadge api=api.clone() command_info statuses /> }
+                            })
+                            .collect_view()}
+                    </div>
+                }
+            })
+    }
+}
+
+/// The last check of the status command. Clicking it gets the latest results again.
+#[component]
+fn StatusBadge<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    command_info: CustomCommandPublicInfo,
+    statuses: RwSignal<BTreeMap<String, CommandStatus>>,
+) -> impl IntoView {
+    let endpoint = command_info.endpoint.clone();
+    let status = move || statuses.get().get(&endpoint).cloned();
+
+    let class = {
+        let status = status.clone();
+        move || match status() {
+            None => "status-badge status-badge-unknown",
+            Some(status) if status.is_ok() => "status-badge status-badge-ok",
+            Some(_) => "status-badge status-badge-failed",
+        }
+    };
+
+    // The details show when hovering over the badge
+    let title = move || match status() {
+        None => "Checking...".to_string(),
+        Some(CommandStatus {
+            output: Some(output),
+            ..
+        }) => {
+            let meaning = output
+                .exit_code_meaning
+                .map(|meaning| format!(" ({meaning})"))
+                .unwrap_or_default();
+            format!(
+                "Exit code: {}{meaning}\n{}",
+                output.error_code,
+                output.stdout.trim()
+            )
+        }
+        Some(CommandStatus { error, .. }) => error.unwrap_or_default(),
+    };
+
+    view! {
+        <button class=class title=title on:click=move |_| fetch_statuses(api.clone(), statuses)>
+            {command_info.icon.map(|icon| view! { <span class="custom-command-icon">{icon}</span> })}
+            {command_info.label}
+        </button>
+    }
+}
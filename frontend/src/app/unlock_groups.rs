@@ -0,0 +1,213 @@
+use common::{
+    api::traits::ZfsRemoteHighLevel,
+    types::{UnlockGroup, UnlockGroupMemberOutcome, UnlockGroupResponse, UnlockGroups},
+};
+use leptos::{
+    component, create_action, create_local_resource, create_signal, event_target_value, view,
+    CollectView, ErrorBoundary, IntoView, SignalGet, SignalSet, Transition, Trigger,
+};
+
+use crate::{
+    app::{error_fallback, log},
+    images::RandomLoadingImage,
+};
+
+async fn unlock_groups_initial_query<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+) -> Result<(A, UnlockGroups), A::Error> {
+    let result = api.unlock_groups().await;
+
+    result.map(|r| (api, r))
+}
+
+/// Shows the groups of datasets that share one password, so that the password is typed once
+/// for all of them. Nothing is shown if no groups are configured.
+#[component]
+pub fn ZfsUnlockGroupsTable<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    /// Notified after a group is unlocked, so that the datasets can be reloaded
+    datasets_changed: Trigger,
+) -> impl IntoView {
+    log("Creating ZFS unlock groups table");
+
+    let groups_rows = create_local_resource(
+        || (),
+        move |_| {
+            let api = api.clone();
+            async move { unlock_groups_initial_query(api).await }
+        },
+    );
+
+    let groups_table_view = move || {
+        groups_rows.and_then(|(api, groups)| {
+            if groups.groups.is_empty() {
+                return view! {}.into_view();
+            }
+
+            view! {
+                <h3 align="center">"ZFS unlock groups"</h3>
+                <div class="zfs-unlock-groups-table-container">
+                    <table class="zfs-unlock-groups-table">
+                        <thead>
+                            <tr>
+                                <th>
+                                    <p>"Group"</p>
+                                </th>
+                                <th>
+                                    <p>"Datasets"</p>
+                                </th>
+                                <th>
+                                    <p>"Unlock"</p>
+                                </th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {groups
+                                .groups
+                                .iter()
+                                .map(|group| {
+                                    view! {
+                                        <ZfsUnlockGroupRow
+                                            api=api.clone()
+                                            group=group.clone()
+                                            datasets_changed
+                                        />
+                                    }
+                                })
+                                .collect_view()}
+                        </tbody>
+                    </table>
+                </div>
+                <hr />
+            }
+            .into_view()
+        })
+    };
+
+    view! {
+        <ErrorBoundary fallback=error_fallback>
+            <Transition fallback=move || {
+                view! {
+                    <div class="zfs-loading-page">
+                        <RandomLoadingImage />
+                    </div>
+                }
+            }>
+                <div>{groups_table_view}</div>
+            </Transition>
+        </ErrorBoundary>
+    }
+}
+
+#[component]
+fn ZfsUnlockGroupRow<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    group: UnlockGroup,
+    datasets_changed: Trigger,
+) -> impl IntoView {
+    view! {
+        <tr>
+            <th>
+                <div class="table-cell-dataset-name">
+                    <p>{group.name.clone()}</p>
+                </div>
+            </th>
+            <th>
+                <div class="table-cell-dataset-name">
+                    {group
+                        .datasets
+                        .iter()
+                        .map(|ds| view! { <p>{ds.clone()}</p> })
+                        .collect_view()}
+                </div>
+            </th>
+            <th>
+                <ZfsUnlockGroupInput api group_name=group.name datasets_changed />
+            </th>
+        </tr>
+    }
+}
+
+#[component]
+fn ZfsUnlockGroupInput<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    group_name: String,
+    datasets_changed: Trigger,
+) -> impl IntoView {
+    let (password_in_input, set_password_in_input) = create_signal(String::new());
+    let (unlock_result, set_unlock_result) =
+        create_signal(None::<Result<UnlockGroupResponse, String>>);
+
+    // This action takes the action from the user, the click, and sends it to the API to unlock the group
+    let unlock_group = create_action(move |password: &String| {
+        let mut api = api.clone();
+        let group_name = group_name.clone();
+        let password = password.clone();
+        async move {
+            match api.unlock_group(&group_name, &password).await {
+                Ok(response) => {
+                    log("Unlock group success");
+                    set_password_in_input.set(String::new());
+                    set_unlock_result.set(Some(Ok(response)));
+                    datasets_changed.notify();
+                }
+                Err(e) => {
+                    log(&format!("Unlock group error: {e}"));
+                    set_unlock_result.set(Some(Err(e.to_string())));
+                }
+            }
+        }
+    });
+
+    move || {
+        if unlock_group.pending().get() {
+            return view! { <RandomLoadingImage /> }.into_view();
+        }
+
+        view! {
+            <input
+                type="password"
+                placeholder="Group password"
+                on:input=move |ev| {
+                    set_password_in_input.set(event_target_value(&ev));
+                }
+                prop:value=password_in_input
+            />
+            <button on:click=move |_| {
+                unlock_group.dispatch(password_in_input.get());
+            }>"Unlock group"</button>
+            {move || match unlock_result.get() {
+                Some(Ok(response)) => {
+                    response
+                        .outcomes
+                        .into_iter()
+                        .map(|(dataset_name, outcome)| {
+                            view! { <ZfsUnlockGroupOutcome dataset_name outcome /> }
+                        })
+                        .collect_view()
+                }
+                Some(Err(e)) => view! { <p style="color: red;">"Unlock error: " {e}</p> }.into_view(),
+                None => view! {}.into_view(),
+            }}
+        }
+        .into_view()
+    }
+}
+
+#[component]
+fn ZfsUnlockGroupOutcome(dataset_name: String, outcome: UnlockGroupMemberOutcome) -> impl IntoView {
+    let status = match (&outcome.error, outcome.key_loaded, outcome.is_mounted) {
+        (Some(e), _, _) => format!("{dataset_name}: {e}"),
+        (None, true, true) => format!("{dataset_name}: Key loaded and mounted"),
+        (None, true, false) => format!("{dataset_name}: Key loaded"),
+        (None, false, _) => format!("{dataset_name}: Key not loaded"),
+    };
+
+    let color = if outcome.error.is_some() {
+        "color: red;"
+    } else {
+        "color: green;"
+    };
+
+    view! { <p style=color>{status}</p> }
+}
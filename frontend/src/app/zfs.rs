@@ -1,10 +1,11 @@
 use common::{
     api::traits::{ZfsRemoteAPI, ZfsRemoteHighLevel},
-    types::{DatasetFullMountState, DatasetsFullMountState},
+    types::{DatasetFullMountState, DatasetMountOptions, DatasetsFullMountState, HookOutput},
 };
 use leptos::{
-    component, create_action, create_local_resource, create_signal, event_target_value, view,
-    CollectView, ErrorBoundary, IntoView, Show, SignalGet, SignalSet, Transition,
+    component, create_action, create_local_resource, create_signal, event_target_checked,
+    event_target_value, view, CollectView, ErrorBoundary, IntoView, Show, SignalGet, SignalSet,
+    Transition, Trigger,
 };
 
 use crate::{
@@ -12,7 +13,10 @@ use crate::{
     images::RandomLoadingImage,
 };
 
-use super::dataset_state_retriever::DatasetStateResource;
+use super::{
+    cmds::{ErrorCodeFromOutput, NothingToShowIcon, StdOutputFormatted},
+    dataset_state_retriever::DatasetStateResource,
+};
 
 async fn zfs_table_initial_query<A: ZfsRemoteHighLevel + 'static>(
     api: A,
@@ -23,11 +27,15 @@ async fn zfs_table_initial_query<A: ZfsRemoteHighLevel + 'static>(
 }
 
 #[component]
-pub fn ZfsUnlockTable<A: ZfsRemoteHighLevel + 'static>(api: A) -> impl IntoView {
+pub fn ZfsUnlockTable<A: ZfsRemoteHighLevel + 'static>(
+    api: A,
+    /// When notified, the datasets list is reloaded (e.g., after a pool is imported)
+    datasets_changed: Trigger,
+) -> impl IntoView {
     log("Creating ZFS table");
 
     let zfs_rows = create_local_resource(
-        || (),
+        move || datasets_changed.track(),
         move |_| {
             let api = api.clone();
             async move { zfs_table_initial_query(api).await }
@@ -61,18 +69,26 @@ fn ZfsMountInput<A: ZfsRemoteHighLevel + 'static>(
 ) -> impl IntoView {
     let dataset_name_for_mount = dataset_state_resource.dataset_name().to_string();
 
+    // Advanced options, which by default mount the dataset read-write at its own mountpoint
+    let (readonly, set_readonly) = create_signal(false);
+    let (mountpoint, set_mountpoint) = create_signal(String::new());
+
     let dataset_state_resource_for_action = dataset_state_resource.clone();
     // This action takes the action from the user, the click, and sends it to the API to unlock the dataset
-    let mount_dataset = create_action(move |_: &()| {
+    let mount_dataset = create_action(move |options: &DatasetMountOptions| {
         let mut api_for_mount = dataset_state_resource_for_action.api().clone();
         let dataset_name = dataset_name_for_mount.clone();
         let dataset_state_resource = dataset_state_resource_for_action.clone();
+        let options = options.clone();
         async move {
             // We reset first, to trigger the loading animation
             dataset_state_resource.reset_dataset_state();
-            let mount_result = api_for_mount.mount_dataset(&dataset_name).await;
+            let mount_result = api_for_mount.mount_dataset(&dataset_name, &options).await;
             match mount_result {
-                Ok(_) => log("Mount success"),
+                Ok(response) => {
+                    log("Mount success");
+                    dataset_state_resource.set_hook_outputs(response.hooks);
+                }
                 Err(e) => log(&format!("Mount error: {e}")),
             }
             dataset_state_resource.refresh_dataset_state()
@@ -89,8 +105,35 @@ fn ZfsMountInput<A: ZfsRemoteHighLevel + 'static>(
                         {
                             view! {
                                 <button on:click=move |_| {
-                                    mount_dataset.dispatch(());
+                                    let mountpoint = mountpoint.get();
+                                    mount_dataset
+                                        .dispatch(DatasetMountOptions {
+                                            readonly: readonly.get(),
+                                            mountpoint: (!mountpoint.trim().is_empty())
+                                                .then_some(mountpoint),
+                                        });
                                 }>"Mount dataset"</button>
+                                <details class="zfs-mount-advanced">
+                                    <summary>"Advanced"</summary>
+                                    <label>
+                                        <input
+                                            type="checkbox"
+                                            on:change=move |ev| {
+                                                set_readonly.set(event_target_checked(&ev));
+                                            }
+                                            prop:checked=readonly
+                                        />
+                                        "Read-only"
+                                    </label>
+                                    <input
+                                        type="text"
+                                        placeholder="Temporary mountpoint (optional)"
+                                        on:input=move |ev| {
+                                            set_mountpoint.set(event_target_value(&ev));
+                                        }
+                                        prop:value=mountpoint
+                                    />
+                                </details>
                             }
                         }
                     </Show>
@@ -153,13 +196,49 @@ fn ZfsKeyPasswordInput<A: ZfsRemoteHighLevel + 'static>(
             dataset_state_resource.reset_dataset_state();
             let load_key_result = api_for_pw.load_key(&dataset_name, &password).await;
             match load_key_result {
-                Ok(_) => log("Load key success"),
+                Ok(response) => {
+                    log("Load key success");
+                    dataset_state_resource.set_hook_outputs(response.hooks);
+                }
                 Err(e) => log(&format!("Load key error: {e}")),
             }
             dataset_state_resource.refresh_dataset_state()
         }
     });
 
+    let api_for_verify = dataset_state_resource.api().clone();
+    let dataset_name_for_verify = dataset_state_resource.dataset_name().to_string();
+    // Checks the password without loading the key, for users who aren't sure they have the right one
+    let verify_passphrase = create_action(move |password: &String| {
+        let password = password.clone();
+        let api = api_for_verify.clone();
+        let dataset_name = dataset_name_for_verify.clone();
+        async move {
+            api.verify_passphrase(&dataset_name, &password)
+                .await
+                .map(|response| response.passphrase_correct)
+                .map_err(|e| e.to_string())
+        }
+    });
+
+    let verify_result_view = move || {
+        if verify_passphrase.pending().get() {
+            return view! { <span>"Checking..."</span> }.into_view();
+        }
+        match verify_passphrase.value().get() {
+            Some(Ok(true)) => {
+                view! { <span style="color: green;">"Password is correct"</span> }.into_view()
+            }
+            Some(Ok(false)) => {
+                view! { <span style="color: red;">"Password is wrong"</span> }.into_view()
+            }
+            Some(Err(e)) => {
+                view! { <span style="color: red;">"Password check error: " {e}</span> }.into_view()
+            }
+            None => view! {}.into_view(),
+        }
+    };
+
     // This contains the text field + submit button objects, depending on whether the key is loaded or not
     let password_field_or_key_already_loaded = move |key_loaded_result: Result<
         bool,
@@ -175,12 +254,17 @@ fn ZfsKeyPasswordInput<A: ZfsRemoteHighLevel + 'static>(
                                 placeholder="Dataset password"
                                 on:input=move |ev| {
                                     set_password_in_input.set(event_target_value(&ev));
+                                    verify_passphrase.value().set(None);
                                 }
                                 prop:value=password_in_input
                             />
                             <button on:click=move |_| {
                                 load_key_password.dispatch(password_in_input.get());
                             }>"Load key"</button>
+                            <button on:click=move |_| {
+                                verify_passphrase.dispatch(password_in_input.get());
+                            }>"Test password"</button>
+                            {verify_result_view}
                         }
                     }
                 </Show>
@@ -204,10 +288,39 @@ fn ZfsKeyPasswordInput<A: ZfsRemoteHighLevel + 'static>(
     }
 }
 
+#[component]
+fn ZfsHookOutputs<A: ZfsRemoteHighLevel + 'static>(
+    dataset_state_resource: DatasetStateResource<A>,
+) -> impl IntoView {
+    let hook_output_view = |hook: HookOutput| {
+        view! {
+            <div class="zfs-hook-output">
+                <p>{hook.label}</p>
+                <ErrorCodeFromOutput output=hook.output.clone() />
+                <StdOutputFormatted output=hook.output.stdout button_label="Show stdout".to_string() />
+                <StdOutputFormatted output=hook.output.stderr button_label="Show stderr".to_string() />
+            </div>
+        }
+    };
+
+    move || {
+        let hook_outputs = dataset_state_resource.hook_outputs();
+        if hook_outputs.is_empty() {
+            view! { <NothingToShowIcon /> }.into_view()
+        } else {
+            hook_outputs
+                .into_iter()
+                .map(hook_output_view)
+                .collect_view()
+        }
+    }
+}
+
 enum ZFSTableColumnDefinition {
     Name,
     KeyLoadPassword,
     MountButton,
+    HookOutputs,
     RefreshButton,
 }
 
@@ -234,6 +347,10 @@ fn ZfsDatasetTableCell<A: ZfsRemoteHighLevel + 'static>(
             Some(ds) => view! { <ZfsMountInput dataset_state_resource=ds /> }.into_view(),
             None => view! { <p>"Mount"</p> }.into_view(),
         },
+        ZFSTableColumnDefinition::HookOutputs => match dataset_state_resource {
+            Some(ds) => view! { <ZfsHookOutputs dataset_state_resource=ds /> }.into_view(),
+            None => view! { <p>"Hooks"</p> }.into_view(),
+        },
         ZFSTableColumnDefinition::RefreshButton => match dataset_state_resource {
             Some(ds) => view! { <ZfsRefreshInput dataset_state_resource=ds /> }.into_view(),
             None => view! { <p>"Refresh"</p> }.into_view(),
@@ -271,6 +388,12 @@ fn ZfsDatasetRow<'a, A: ZfsRemoteHighLevel + 'static>(
                     column=ZFSTableColumnDefinition::MountButton
                 />
             </th>
+            <th>
+                <ZfsDatasetTableCell
+                    dataset_state_resource=dataset_state_resource.clone()
+                    column=ZFSTableColumnDefinition::HookOutputs
+                />
+            </th>
             <th>
                 <ZfsDatasetTableCell
                     dataset_state_resource=dataset_state_resource.clone()
@@ -293,7 +416,7 @@ fn ZfsUnlocksTable<'a, A: ZfsRemoteHighLevel + 'static>(
 
     view! {
         <div class="zfs-datasets-table-container">
-            <Show when=move || (locked_count > 0) fallback=|| view! { <NothingToUnlock /> }>
+            <Show when={move || locked_count > 0} fallback=|| view! { <NothingToUnlock /> }>
                 <table class="zfs-datasets-table">
                     <thead>
                         <ZfsDatasetRow api=api.clone() initial_mount_state=None />
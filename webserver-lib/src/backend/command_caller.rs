@@ -1,7 +1,127 @@
-use common::types::RunCommandOutput;
+use std::{os::unix::process::ExitStatusExt, process::ExitStatus, time::Duration};
+
+use base64::prelude::*;
+use common::types::{
+    CommandOutputChunk, CommandStopReason, OutputEncoding, OutputStream, RunCommandOutput,
+    StageOutput,
+};
+use nix::{
+    sys::signal::{killpg, Signal},
+    unistd::{pipe, Pid},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    net::unix::pipe::Receiver,
+    process::ChildStdin,
+    sync::{mpsc, watch},
+    time::Instant,
+};
+
+use crate::run_options::config::ChainOptions;
 
 use super::error::Error;
 
+/// Receives the output of commands while they run
+pub type OutputSender = mpsc::UnboundedSender<CommandOutputChunk>;
+
+/// The size of the reads from the output pipes, which is also the largest chunk that gets sent
+const OUTPUT_READ_BUFFER_SIZE: usize = 4096;
+
+/// How long to wait for the output pipes to close after a command is killed. Processes that
+/// left the process group of the command can keep the pipes open.
+const KILLED_COMMAND_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// How a chain of commands is run, watched and stopped
+#[derive(Default)]
+pub struct RunControl {
+    /// Receives the output while the commands run
+    pub output: Option<OutputSender>,
+    /// The time after which the commands are killed, for the whole chain
+    pub timeout: Option<Duration>,
+    /// Kills the commands when it changes
+    pub cancel: Option<watch::Receiver<()>>,
+    /// How the commands are connected, and which of them run
+    pub chain: ChainOptions,
+    /// The most bytes that are kept, and sent, of each output of the result. The stages count
+    /// against it together, and the stdout that is piped to the next command is never cut.
+    pub max_output_bytes: Option<usize>,
+    /// How the output bytes are turned into the strings of the result
+    pub encoding: OutputEncoding,
+}
+
+/// How the output of one command is captured
+#[derive(Clone, Copy)]
+struct OutputCapture<'a> {
+    /// Receives the output while the command runs
+    sender: Option<&'a OutputSender>,
+    /// Whether the stdout is sent too, and not only the stderr
+    stream_stdout: bool,
+    /// Whether the stderr goes to the same pipe as the stdout
+    merge_stderr: bool,
+    /// Whether the whole stdout is kept, to pipe it to the next command
+    pipe_stdout: bool,
+    max_bytes: Option<usize>,
+}
+
+/// The output of a pipe, where only a prefix, up to the size limit, is kept for the result
+#[derive(Default)]
+struct CapturedOutput {
+    /// The whole output, if it's piped to the next command, and otherwise only the kept prefix
+    bytes: Vec<u8>,
+    /// The size of the kept prefix
+    kept: usize,
+    truncated: bool,
+}
+
+impl CapturedOutput {
+    fn kept(&self) -> &[u8] {
+        &self.bytes[..self.kept]
+    }
+}
+
+/// The result of one command, with its raw output
+#[derive(Default)]
+struct CommandRun {
+    stdout: CapturedOutput,
+    stderr: CapturedOutput,
+    error_code: i32,
+    stopped: Option<CommandStopReason>,
+    signal: Option<i32>,
+}
+
+impl CommandRun {
+    fn failed(message: String, error_code: i32, stopped: Option<CommandStopReason>) -> Self {
+        Self {
+            stderr: CapturedOutput {
+                kept: message.len(),
+                bytes: message.into_bytes(),
+                truncated: false,
+            },
+            error_code,
+            stopped,
+            ..Default::default()
+        }
+    }
+}
+
+fn encode_output(bytes: &[u8], encoding: OutputEncoding) -> String {
+    match encoding {
+        OutputEncoding::Text => String::from_utf8_lossy(bytes).into_owned(),
+        OutputEncoding::Base64 => BASE64_STANDARD.encode(bytes),
+    }
+}
+
+/// Takes the prefix of `bytes` that fits in the room that is left, and counts it against the room.
+/// Returns whether the bytes were cut.
+fn take_within<'a>(bytes: &'a [u8], room: &mut Option<usize>) -> (&'a [u8], bool) {
+    let Some(room) = room else {
+        return (bytes, false);
+    };
+    let taken = bytes.len().min(*room);
+    *room -= taken;
+    (&bytes[..taken], taken < bytes.len())
+}
+
 #[derive(thiserror::Error, Debug, Clone)]
 pub enum CommandError {
     #[error("Attempted to call empty command")]
@@ -14,15 +134,162 @@ pub enum CommandError {
     StdinPipe,
 }
 
-#[allow(dead_code)]
+/// Takes the longest prefix of `pending` that is valid UTF-8, and leaves an incomplete character
+/// at the end for the next read to complete. Invalid bytes are replaced.
+fn take_utf8_prefix(pending: &mut Vec<u8>) -> String {
+    let valid_up_to = match std::str::from_utf8(pending) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => pending.len(),
+    };
+    let rest = pending.split_off(valid_up_to);
+    let chunk = String::from_utf8_lossy(pending).into_owned();
+    *pending = rest;
+    chunk
+}
+
+/// Reads the whole output of a pipe, where every read is also sent to `output`, if given.
+/// Only the first `max_bytes` are kept and sent, while the rest is read and dropped, so that
+/// the command doesn't block on a full pipe. With `keep_all`, the rest is kept too, but only
+/// to be piped, and it's neither sent nor part of the kept prefix.
+async fn read_output(
+    mut pipe: impl AsyncRead + Unpin,
+    stream: OutputStream,
+    output: Option<&OutputSender>,
+    max_bytes: Option<usize>,
+    keep_all: bool,
+) -> std::io::Result<CapturedOutput> {
+    let mut result = CapturedOutput::default();
+    let mut pending = Vec::new();
+    let mut buffer = [0; OUTPUT_READ_BUFFER_SIZE];
+
+    loop {
+        let read_size = pipe.read(&mut buffer).await?;
+        if read_size == 0 {
+            break;
+        }
+
+        let room = max_bytes.map_or(read_size, |max| max.saturating_sub(result.kept));
+        let kept = &buffer[..read_size.min(room)];
+        result.truncated |= kept.len() < read_size;
+        result.kept += kept.len();
+        result
+            .bytes
+            .extend_from_slice(if keep_all { &buffer[..read_size] } else { kept });
+
+        if let (Some(output), false) = (output, kept.is_empty()) {
+            pending.extend_from_slice(kept);
+            let data = take_utf8_prefix(&mut pending);
+            if !data.is_empty() {
+                // Sending fails only if the receiver is gone, and then the output is not needed
+                let _ = output.send(CommandOutputChunk { stream, data });
+            }
+        }
+    }
+
+    if let (Some(output), false) = (output, pending.is_empty()) {
+        let _ = output.send(CommandOutputChunk {
+            stream,
+            data: String::from_utf8_lossy(&pending).into_owned(),
+        });
+    }
+
+    Ok(result)
+}
+
+async fn write_stdin(
+    child_stdin: Option<ChildStdin>,
+    stdin: Option<Vec<u8>>,
+) -> Result<(), CommandError> {
+    use tokio::io::{AsyncWriteExt, BufWriter};
+
+    // Pipe stdin, if desired by the caller
+    let mut child_stdin = child_stdin;
+    if let Some(stdin_data) = stdin {
+        match child_stdin.as_mut() {
+            Some(stdin_pipe) => {
+                // A command that exits without reading its input closes the pipe, which is fine,
+                // e.g., a stage of a chain that ignores the output of the stage before it
+                let write_error = |e: std::io::Error| match e.kind() {
+                    std::io::ErrorKind::BrokenPipe => Ok(()),
+                    _ => Err(CommandError::SystemError(e.to_string())),
+                };
+
+                // Write the data to stdin asynchronously
+                let mut async_writer = BufWriter::new(stdin_pipe);
+                if let Err(e) = async_writer.write_all(&stdin_data).await {
+                    return write_error(e);
+                }
+
+                // Flush the writer to ensure all data is sent
+                if let Err(e) = async_writer.flush().await {
+                    return write_error(e);
+                }
+            }
+            None => return Err(CommandError::StdinPipe),
+        }
+    }
+
+    // Signal we're done with stdin by dropping it
+    drop(child_stdin);
+
+    Ok(())
+}
+
+async fn wait_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+pub(crate) async fn wait_for_cancel(cancel: Option<&mut watch::Receiver<()>>) {
+    if let Some(cancel) = cancel {
+        // An error means that nobody can cancel anymore
+        if cancel.changed().await.is_ok() {
+            return;
+        }
+    }
+    std::future::pending().await
+}
+
+/// Kills the command and everything it started, which are all in the process group of the command
+fn kill_process_group(pid: Option<u32>) {
+    let Some(pid) = pid.and_then(|pid| i32::try_from(pid).ok()) else {
+        return;
+    };
+
+    if let Err(e) = killpg(Pid::from_raw(pid), Signal::SIGKILL) {
+        log::warn!("Failed to kill the process group {pid}: {e}");
+    }
+}
+
+fn make_run(
+    stdout: CapturedOutput,
+    stderr: CapturedOutput,
+    status: ExitStatus,
+    stopped: Option<CommandStopReason>,
+) -> CommandRun {
+    CommandRun {
+        stdout,
+        stderr,
+        error_code: status
+            .code()
+            .unwrap_or(if status.success() { 0 } else { 255 }),
+        stopped,
+        signal: status.signal(),
+    }
+}
+
+/// Runs a command, where its output is captured and sent as described by `capture`.
+/// The command runs in its own process group, which is killed at the deadline or when cancelled.
 async fn run_command(
     cmd_with_args: &[String],
-    stdin: Option<String>,
-) -> Result<RunCommandOutput, CommandError> {
-    use tokio::{
-        io::{AsyncReadExt, AsyncWriteExt, BufWriter},
-        try_join,
-    };
+    stdin: Option<Vec<u8>>,
+    capture: OutputCapture<'_>,
+    deadline: Option<Instant>,
+    cancel: Option<&mut watch::Receiver<()>>,
+) -> Result<CommandRun, CommandError> {
+    use tokio::try_join;
 
     let (program, args) = cmd_with_args
         .split_first()
@@ -36,116 +303,480 @@ async fn run_command(
             cmd
         });
 
+    let system_error = |e: std::io::Error| CommandError::SystemError(e.to_string());
+
+    // Both outputs write to the same pipe, in the order they're written, like `2>&1`
+    let merged_output = if capture.merge_stderr {
+        let (reader, writer) = pipe().map_err(|e| CommandError::SystemError(e.to_string()))?;
+        cmd.stdout(writer.try_clone().map_err(system_error)?)
+            .stderr(writer);
+        Some(reader)
+    } else {
+        cmd.stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        None
+    };
+
     let mut child = cmd
         .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
+        .process_group(0)
+        .kill_on_drop(true)
         .spawn()
         .map_err(|e| CommandError::CallFailed(e.to_string()))?;
 
-    // Pipe stdin, if desired by the caller
-    let mut child_stdin = child.stdin.take();
-    if let Some(stdin_string) = stdin {
-        match child_stdin.as_mut() {
-            Some(stdin_pipe) => {
-                // Create Vec from the input string
-                let stdin_data = {
-                    use std::io::Write;
-                    let mut write_buffer = Vec::new();
-                    let mut writer = std::io::BufWriter::new(&mut write_buffer);
-                    writeln!(&mut writer, "{}", stdin_string).expect("Cannot fail in memory write");
-                    drop(writer);
-                    write_buffer
-                };
+    // The command keeps the write end of the merged pipe, which must be closed for the reads to end
+    drop(cmd);
 
-                // Write the data to stdin asynchronously
-                let mut async_writer = BufWriter::new(stdin_pipe);
-                async_writer
-                    .write_all(&stdin_data)
-                    .await
-                    .map_err(|e| CommandError::SystemError(e.to_string()))?;
+    let pid = child.id();
+    let child_stdin = child.stdin.take();
 
-                // Flush the writer to ensure all data is sent
-                async_writer
-                    .flush()
+    // Capture the output handles of the child process
+    let stdout: Box<dyn AsyncRead + Unpin + Send> = match merged_output {
+        Some(reader) => Box::new(Receiver::from_owned_fd(reader).map_err(system_error)?),
+        None => Box::new(child.stdout.take().expect("Failed to capture stdout")),
+    };
+    let stderr = child.stderr.take();
+
+    let run = async {
+        let ((), stdout, stderr) = try_join!(
+            write_stdin(child_stdin, stdin),
+            async {
+                read_output(
+                    stdout,
+                    OutputStream::Stdout,
+                    capture.sender.filter(|_| capture.stream_stdout),
+                    capture.max_bytes,
+                    capture.pipe_stdout,
+                )
+                .await
+                .map_err(system_error)
+            },
+            async {
+                match stderr {
+                    Some(stderr) => read_output(
+                        stderr,
+                        OutputStream::Stderr,
+                        capture.sender,
+                        capture.max_bytes,
+                        false,
+                    )
                     .await
-                    .map_err(|e| CommandError::SystemError(e.to_string()))?;
-            }
-            None => return Err(CommandError::StdinPipe),
-        }
-    }
+                    .map_err(system_error),
+                    None => Ok(CapturedOutput::default()),
+                }
+            },
+        )?;
 
-    // Signal we're done with stdin by dropping it
-    drop(child_stdin);
+        // Wait for the command to complete
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| CommandError::SystemError(e.to_string()))?;
 
-    // Capture the stdout handle of the child process
-    let mut stdout = child.stdout.take().expect("Failed to capture stdout");
-    let mut stderr = child.stderr.take().expect("Failed to capture stderr");
+        Ok::<_, CommandError>((stdout, stderr, status))
+    };
+    tokio::pin!(run);
 
-    let mut stdout_string = String::new();
-    let mut stderr_string = String::new();
+    let stop_reason = tokio::select! {
+        result = &mut run => {
+            let (stdout, stderr, status) = result?;
+            return Ok(make_run(stdout, stderr, status, None));
+        }
+        () = wait_until(deadline) => CommandStopReason::TimedOut,
+        () = wait_for_cancel(cancel) => CommandStopReason::Cancelled,
+    };
 
-    let (_, _) = try_join!(
-        // Read stdout/stderr to a string
-        AsyncReadExt::read_to_string(&mut stdout, &mut stdout_string),
-        AsyncReadExt::read_to_string(&mut stderr, &mut stderr_string),
-    )
-    .map_err(|e| CommandError::SystemError(e.to_string()))?;
+    kill_process_group(pid);
 
-    // Wait for the command to complete
-    let status = child
-        .wait()
-        .await
-        .map_err(|e| CommandError::SystemError(e.to_string()))?;
-
-    if status.success() {
-        Ok(RunCommandOutput {
-            stdout: stdout_string,
-            stderr: stderr_string,
-            error_code: status.code().unwrap_or(0),
-        })
-    } else {
-        Ok(RunCommandOutput {
-            stdout: stdout_string,
-            stderr: stderr_string,
-            error_code: status.code().unwrap_or(255),
-        })
-    }
+    // The output that the command wrote before it was killed is still collected
+    Ok(
+        match tokio::time::timeout(KILLED_COMMAND_GRACE_PERIOD, run).await {
+            Ok(Ok((stdout, stderr, status))) => make_run(stdout, stderr, status, Some(stop_reason)),
+            Ok(Err(e)) => CommandRun::failed(e.to_string(), 255, Some(stop_reason)),
+            Err(_) => CommandRun::failed(
+                "The output pipes were not closed after the command was killed".to_string(),
+                255,
+                Some(stop_reason),
+            ),
+        },
+    )
 }
 
-pub async fn chain_commands(
-    commands: &Vec<Vec<String>>,
-    initial_stdin: Option<String>,
+/// Runs the commands one after the other, where they're watched and stopped according to `control`.
+/// The stderr of every command is sent to the output, but only the stdout of the last one,
+/// since the stdout of the others is piped to the next command rather than being part of the result.
+///
+/// The whole stdout of the last command that ran is piped to the next one, even if the result
+/// only keeps a part of it. After a failure, the chain stops, unless it continues on errors or
+/// the next command runs on failure. The combined result has the exit code and stdout of the
+/// last command that ran, and the stderr of all of them. The combined stderr is cut to the size
+/// limit as a whole, and so are the outputs of all the stages together.
+///
+/// The stdin is piped to the first command exactly as it's given.
+pub async fn chain_commands_with_control(
+    commands: &[Vec<String>],
+    initial_stdin: Option<Vec<u8>>,
+    mut control: RunControl,
 ) -> Result<RunCommandOutput, Error> {
     if commands.is_empty() {
         return Err(Error::NoCommandsProvided);
     }
     let mut current_stdin = initial_stdin;
 
-    let mut result = RunCommandOutput {
-        stdout: String::new(),
-        stderr: String::new(),
-        error_code: 254,
-    };
+    let mut last_run = CommandRun::failed(String::new(), 254, None);
+    let mut stages = Vec::with_capacity(commands.len());
+    let mut stderr = Vec::new();
+    let mut stderr_truncated = false;
+    let mut stages_room = control.max_output_bytes;
+    let mut previous_failed = false;
 
-    for command in commands {
-        result = match run_command(command, current_stdin).await {
-            Ok(result) => result,
-            Err(e) => {
-                return Ok(RunCommandOutput {
-                    stdout: String::new(),
-                    stderr: e.to_string(),
-                    error_code: 253,
-                })
-            }
+    let deadline = control.timeout.map(|timeout| Instant::now() + timeout);
+    let encoding = control.encoding;
+
+    for (idx, command) in commands.iter().enumerate() {
+        let runs_on_failure = control.chain.runs_on_failure(idx);
+        let should_run = match (runs_on_failure, previous_failed) {
+            (true, failed) => failed,
+            (false, failed) => !failed || control.chain.continue_on_error,
         };
+        let stopped = last_run.stopped.is_some();
 
-        if result.error_code != 0 {
-            break;
+        if !should_run || stopped {
+            stages.push(StageOutput {
+                argv: command.clone(),
+                skipped: true,
+                ..Default::default()
+            });
+            continue;
         }
 
-        current_stdin = Some(result.stdout.clone());
+        let capture = OutputCapture {
+            sender: control.output.as_ref(),
+            stream_stdout: idx + 1 == commands.len(),
+            merge_stderr: control.chain.merge_stderr,
+            pipe_stdout: idx + 1 < commands.len(),
+            max_bytes: control.max_output_bytes,
+        };
+        let started = Instant::now();
+        last_run = run_command(
+            command,
+            current_stdin.take(),
+            capture,
+            deadline,
+            control.cancel.as_mut(),
+        )
+        .await
+        .unwrap_or_else(|e| CommandRun::failed(e.to_string(), 253, None));
+
+        let (stage_stdout, stdout_cut) = take_within(last_run.stdout.kept(), &mut stages_room);
+        let (stage_stderr, stderr_cut) = take_within(last_run.stderr.kept(), &mut stages_room);
+        stages.push(StageOutput {
+            argv: command.clone(),
+            stdout: encode_output(stage_stdout, encoding),
+            stderr: encode_output(stage_stderr, encoding),
+            error_code: last_run.error_code,
+            duration_ms: started.elapsed().as_millis().try_into().unwrap_or(u64::MAX),
+            skipped: false,
+            truncated: stdout_cut
+                || stderr_cut
+                || last_run.stdout.truncated
+                || last_run.stderr.truncated,
+        });
+
+        let mut stderr_room = control.max_output_bytes.map(|max| max - stderr.len());
+        let (kept_stderr, cut) = take_within(last_run.stderr.kept(), &mut stderr_room);
+        stderr.extend_from_slice(kept_stderr);
+        stderr_truncated |= cut || last_run.stderr.truncated;
+
+        previous_failed = last_run.error_code != 0;
+
+        // Only the kept prefix stays with the run, for the result
+        let piped = std::mem::take(&mut last_run.stdout.bytes);
+        last_run.stdout.bytes = piped[..last_run.stdout.kept].to_vec();
+        current_stdin = Some(piped);
     }
 
-    Ok(result)
+    Ok(RunCommandOutput {
+        stdout: encode_output(last_run.stdout.kept(), encoding),
+        stderr: encode_output(&stderr, encoding),
+        error_code: last_run.error_code,
+        stopped: last_run.stopped,
+        signal: last_run.signal,
+        stages,
+        encoding,
+        stdout_truncated: last_run.stdout.truncated,
+        stderr_truncated,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_prefix_keeps_incomplete_characters() {
+        let mut pending = "aé".as_bytes()[..2].to_vec();
+        assert_eq!(take_utf8_prefix(&mut pending), "a");
+        assert_eq!(pending, vec![0xC3]);
+
+        pending.push(0xA9);
+        assert_eq!(take_utf8_prefix(&mut pending), "é");
+        assert!(pending.is_empty());
+
+        let mut invalid = vec![b'a', 0xFF, b'b'];
+        assert_eq!(take_utf8_prefix(&mut invalid), "a\u{FFFD}b");
+    }
+
+    #[tokio::test]
+    async fn streams_stderr_of_all_and_stdout_of_last() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let commands = vec![
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo first; echo first-err >&2".to_string(),
+            ],
+            vec!["head".to_string(), "-n".to_string(), "1".to_string()],
+        ];
+
+        let control = RunControl {
+            output: Some(sender),
+            ..Default::default()
+        };
+        let result = chain_commands_with_control(&commands, None, control)
+            .await
+            .unwrap();
+        assert_eq!(result.stdout, "first\n");
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = receiver.recv().await {
+            chunks.push((chunk.stream, chunk.data));
+        }
+        assert_eq!(
+            chunks,
+            vec![
+                (OutputStream::Stderr, "first-err\n".to_string()),
+                (OutputStream::Stdout, "first\n".to_string()),
+            ]
+        );
+    }
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    #[tokio::test]
+    async fn timeout_kills_the_whole_process_group() {
+        // The background sleep keeps stdout open, so the command only finishes if it's killed too
+        let commands = vec![sh("echo started; sleep 30 & sleep 30")];
+        let control = RunControl {
+            timeout: Some(Duration::from_millis(300)),
+            ..Default::default()
+        };
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            chain_commands_with_control(&commands, None, control),
+        )
+        .await
+        .expect("The process group was not killed")
+        .unwrap();
+
+        assert_eq!(result.stdout, "started\n");
+        assert_eq!(result.stopped, Some(CommandStopReason::TimedOut));
+        assert_eq!(result.signal, Some(Signal::SIGKILL as i32));
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_the_chain() {
+        let (cancel_sender, cancel) = watch::channel(());
+        let commands = vec![sh("sleep 30"), sh("echo never")];
+        let control = RunControl {
+            cancel: Some(cancel),
+            ..Default::default()
+        };
+
+        let run =
+            tokio::spawn(
+                async move { chain_commands_with_control(&commands, None, control).await },
+            );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cancel_sender.send_replace(());
+
+        let result = tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.stopped, Some(CommandStopReason::Cancelled));
+        assert_eq!(result.stdout, "");
+    }
+
+    #[tokio::test]
+    async fn stages_are_reported_and_stderr_is_kept_for_all() {
+        let commands = vec![
+            sh("echo out; echo err1 >&2"),
+            sh("head -n 1; echo err2 >&2; exit 3"),
+            sh("echo never"),
+        ];
+
+        let result = chain_commands_with_control(&commands, None, RunControl::default())
+            .await
+            .unwrap();
+
+        assert_eq!(result.error_code, 3);
+        assert_eq!(result.stdout, "out\n");
+        assert_eq!(result.stderr, "err1\nerr2\n");
+        assert_eq!(
+            result
+                .stages
+                .iter()
+                .map(|s| (s.error_code, s.skipped))
+                .collect::<Vec<_>>(),
+            vec![(0, false), (3, false), (0, true)]
+        );
+        assert_eq!(result.failed_stage().map(|(idx, _)| idx), Some(1));
+        assert_eq!(result.stages[1].argv, commands[1]);
+    }
+
+    #[tokio::test]
+    async fn merged_stderr_goes_to_the_next_command() {
+        let commands = vec![sh("echo out; echo err >&2"), sh("head -n 2")];
+        let control = RunControl {
+            chain: ChainOptions {
+                merge_stderr: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let result = chain_commands_with_control(&commands, None, control)
+            .await
+            .unwrap();
+
+        assert_eq!(result.stdout, "out\nerr\n");
+        assert_eq!(result.stderr, "");
+    }
+
+    #[tokio::test]
+    async fn on_failure_stages_run_only_after_failures() {
+        let commands = vec![sh("exit 1"), sh("echo recovered"), sh("head -n 1")];
+        let control = RunControl {
+            chain: ChainOptions {
+                on_failure_stages: vec![2],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = chain_commands_with_control(&commands, None, control)
+            .await
+            .unwrap();
+        assert_eq!(result.error_code, 0);
+        assert_eq!(result.stdout, "recovered\n");
+
+        // Without a failure, the on-failure stage is skipped and the chain goes on
+        let commands = vec![sh("echo fine"), sh("echo recovered"), sh("head -n 1")];
+        let control = RunControl {
+            chain: ChainOptions {
+                on_failure_stages: vec![2],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = chain_commands_with_control(&commands, None, control)
+            .await
+            .unwrap();
+        assert_eq!(result.stdout, "fine\n");
+        assert!(result.stages[1].skipped);
+
+        // Continuing on errors runs the next stage with the output of the failed one
+        let commands = vec![sh("echo partial; exit 2"), sh("head -n 1")];
+        let control = RunControl {
+            chain: ChainOptions {
+                continue_on_error: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = chain_commands_with_control(&commands, None, control)
+            .await
+            .unwrap();
+        assert_eq!(result.error_code, 0);
+        assert_eq!(result.stdout, "partial\n");
+        assert_eq!(result.failed_stage().map(|(idx, _)| idx), Some(0));
+    }
+
+    #[tokio::test]
+    async fn binary_output_is_kept_and_capped() {
+        let commands = vec![sh("printf 'a\\377b'; printf '0123456789' >&2")];
+        let run = |encoding| {
+            let control = RunControl {
+                max_output_bytes: Some(4),
+                encoding,
+                ..Default::default()
+            };
+            chain_commands_with_control(&commands, None, control)
+        };
+
+        let result = run(OutputEncoding::Text).await.unwrap();
+        assert_eq!(result.error_code, 0);
+        assert_eq!(result.stdout, "a\u{FFFD}b");
+        assert!(!result.stdout_truncated);
+        assert_eq!(result.stderr, "0123");
+        assert!(result.stderr_truncated);
+
+        let result = run(OutputEncoding::Base64).await.unwrap();
+        assert_eq!(result.encoding, OutputEncoding::Base64);
+        assert_eq!(
+            BASE64_STANDARD.decode(&result.stdout).unwrap(),
+            vec![b'a', 0xFF, b'b']
+        );
+    }
+
+    #[tokio::test]
+    async fn the_whole_output_is_piped_despite_the_cap() {
+        let commands = vec![sh("head -c 100000 /dev/zero"), sh("wc -c | tr -d ' '")];
+        let control = RunControl {
+            max_output_bytes: Some(10),
+            ..Default::default()
+        };
+
+        let result = chain_commands_with_control(&commands, None, control)
+            .await
+            .unwrap();
+
+        assert_eq!(result.stdout, "100000\n");
+        assert!(!result.stdout_truncated);
+        assert_eq!(result.stages[0].stdout.len(), 10);
+        assert!(result.stages[0].truncated);
+    }
+
+    #[tokio::test]
+    async fn stderr_and_stages_are_capped_as_a_whole() {
+        let commands = vec![
+            sh("echo aaaaaaa >&2"),
+            sh("echo bbbbbbb >&2"),
+            sh("echo ccccccc >&2"),
+        ];
+        let control = RunControl {
+            max_output_bytes: Some(12),
+            ..Default::default()
+        };
+
+        let result = chain_commands_with_control(&commands, None, control)
+            .await
+            .unwrap();
+
+        assert_eq!(result.stderr, "aaaaaaa\nbbbb");
+        assert!(result.stderr_truncated);
+        assert_eq!(
+            result
+                .stages
+                .iter()
+                .map(|s| (s.stderr.as_str(), s.truncated))
+                .collect::<Vec<_>>(),
+            vec![("aaaaaaa\n", false), ("bbbb", true), ("", true)]
+        );
+    }
 }
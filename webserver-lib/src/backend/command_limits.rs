@@ -0,0 +1,170 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+
+use crate::run_options::config::WhenBusy;
+
+use super::{error::Error, routable_command::RoutableCommand};
+
+/// Limits how many custom commands run at the same time: per command, per exclusive group
+/// (where only one command of the group runs at a time), and in total.
+#[derive(Clone)]
+pub struct CommandLimits {
+    /// The semaphores that a run of each command needs, in the order they're acquired
+    semaphores: Arc<BTreeMap<String, Vec<Arc<Semaphore>>>>,
+    when_busy: WhenBusy,
+    changes: Arc<watch::Sender<()>>,
+}
+
+/// Keeps a run of a command counted against the limits until it's dropped
+pub struct CommandPermit {
+    permits: Vec<OwnedSemaphorePermit>,
+    changes: Arc<watch::Sender<()>>,
+}
+
+impl Drop for CommandPermit {
+    fn drop(&mut self) {
+        // Released before notifying, so that whoever is notified sees the places as free
+        self.permits.clear();
+        self.changes.send_replace(());
+    }
+}
+
+impl CommandLimits {
+    pub fn new<'a>(
+        commands: impl IntoIterator<Item = &'a RoutableCommand>,
+        max_concurrent_commands: Option<usize>,
+        when_busy: WhenBusy,
+    ) -> Self {
+        let global = max_concurrent_commands.map(|max| Arc::new(Semaphore::new(max)));
+        let mut groups = BTreeMap::<String, Arc<Semaphore>>::new();
+
+        let semaphores = commands
+            .into_iter()
+            .map(|cmd| {
+                // Always acquired in the same order, so that runs waiting for each other can't deadlock
+                let own = cmd.max_concurrent.map(|max| Arc::new(Semaphore::new(max)));
+                let group = cmd.exclusive_group.as_ref().map(|group| {
+                    groups
+                        .entry(group.clone())
+                        .or_insert_with(|| Arc::new(Semaphore::new(1)))
+                        .clone()
+                });
+
+                let semaphores = [own, group, global.clone()]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>();
+
+                (cmd.url_endpoint.clone(), semaphores)
+            })
+            .collect();
+
+        Self {
+            semaphores: Arc::new(semaphores),
+            when_busy,
+            changes: Arc::new(watch::channel(()).0),
+        }
+    }
+
+    /// Takes a place for a run of the command. If the limits are reached, either waits for a place,
+    /// or fails immediately, depending on the configuration.
+    pub async fn acquire(&self, endpoint: &str) -> Result<CommandPermit, Error> {
+        let semaphores = self.semaphores.get(endpoint).cloned().unwrap_or_default();
+
+        let mut permits = Vec::with_capacity(semaphores.len());
+        for semaphore in semaphores {
+            let permit = match self.when_busy {
+                WhenBusy::Reject => semaphore
+                    .try_acquire_owned()
+                    .map_err(|_| Error::CommandBusy(endpoint.to_string()))?,
+                WhenBusy::Queue => semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("Semaphores are never closed"),
+            };
+            permits.push(permit);
+        }
+
+        self.changes.send_replace(());
+
+        Ok(CommandPermit {
+            permits,
+            changes: self.changes.clone(),
+        })
+    }
+
+    /// The commands that can't start now without waiting or being rejected
+    pub fn busy(&self) -> Vec<String> {
+        self.semaphores
+            .iter()
+            .filter(|(_, semaphores)| semaphores.iter().any(|s| s.available_permits() == 0))
+            .map(|(endpoint, _)| endpoint.clone())
+            .collect()
+    }
+
+    /// Changes whenever a run of a command starts or finishes, which may change the busy commands
+    pub fn changes(&self) -> watch::Receiver<()> {
+        self.changes.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::run_options::config::CustomCommand;
+
+    use super::*;
+
+    fn command(endpoint: &str, extra: &str) -> RoutableCommand {
+        let toml = format!(
+            "label = \"{endpoint}\"\nurl_endpoint = \"{endpoint}\"\nrun_cmd = [\"true\"]\n{extra}"
+        );
+        RoutableCommand::from(toml::from_str::<CustomCommand>(&toml).unwrap())
+    }
+
+    #[tokio::test]
+    async fn exclusive_group_and_own_limit_are_enforced() {
+        let commands = [
+            command("start", "exclusive_group = \"docker\""),
+            command("stop", "exclusive_group = \"docker\""),
+            command("status", "max_concurrent = 2"),
+        ];
+        let limits = CommandLimits::new(&commands, None, WhenBusy::Reject);
+
+        let start = limits.acquire("start").await.unwrap();
+        assert!(matches!(
+            limits.acquire("stop").await,
+            Err(Error::CommandBusy(_))
+        ));
+        assert_eq!(limits.busy(), vec!["start", "stop"]);
+
+        let _status1 = limits.acquire("status").await.unwrap();
+        let _status2 = limits.acquire("status").await.unwrap();
+        assert!(limits.acquire("status").await.is_err());
+
+        drop(start);
+        let _stop = limits.acquire("stop").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn queued_runs_wait_for_the_global_limit() {
+        let commands = [command("a", ""), command("b", "")];
+        let limits = CommandLimits::new(&commands, Some(1), WhenBusy::Queue);
+
+        let a = limits.acquire("a").await.unwrap();
+        assert_eq!(limits.busy(), vec!["a", "b"]);
+
+        tokio::time::timeout(Duration::from_millis(100), limits.acquire("b"))
+            .await
+            .err()
+            .expect("The second command should wait");
+
+        drop(a);
+        let _b = tokio::time::timeout(Duration::from_secs(1), limits.acquire("b"))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
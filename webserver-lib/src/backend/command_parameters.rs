@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use common::types::{CommandParameter, CommandParameterType};
+use regex::Regex;
+
+use super::error::Error;
+
+#[derive(Clone, Debug)]
+struct CheckedParameter {
+    definition: CommandParameter,
+    /// The regex of the definition, anchored to match the whole value
+    regex: Option<Regex>,
+}
+
+impl CheckedParameter {
+    fn check_value(&self, value: &str) -> Result<(), String> {
+        let name = &self.definition.name;
+
+        match self.definition.kind {
+            CommandParameterType::String => {
+                if value.starts_with('-') && !self.definition.allow_leading_dash {
+                    return Err(format!(
+                        "Parameter `{name}` can't start with `-`, found `{value}`"
+                    ));
+                }
+            }
+            CommandParameterType::Int => {
+                value.parse::<i64>().map_err(|_| {
+                    format!("Parameter `{name}` must be an integer, found `{value}`")
+                })?;
+            }
+            CommandParameterType::Enum => {
+                if !self.definition.values.iter().any(|v| v == value) {
+                    return Err(format!(
+                        "Parameter `{name}` must be one of [{}], found `{value}`",
+                        self.definition.values.join(", ")
+                    ));
+                }
+            }
+            CommandParameterType::Bool => {
+                if value != "true" && value != "false" {
+                    return Err(format!(
+                        "Parameter `{name}` must be `true` or `false`, found `{value}`"
+                    ));
+                }
+            }
+        }
+
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(value) {
+                return Err(format!(
+                    "Parameter `{name}` doesn't match the pattern `{}`, found `{value}`",
+                    self.definition.regex.as_deref().unwrap_or_default()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The parameters of a custom command, checked to be well defined
+#[derive(Clone, Debug, Default)]
+pub struct ParameterSet {
+    parameters: Vec<CheckedParameter>,
+}
+
+impl ParameterSet {
+    /// Fails if the definitions are inconsistent, e.g., a regex doesn't compile, or a default value isn't valid
+    pub fn new(definitions: &[CommandParameter]) -> Result<Self, String> {
+        let mut names = BTreeSet::new();
+        let mut parameters = Vec::new();
+
+        for definition in definitions {
+            let name = &definition.name;
+
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!(
+                    "Parameter name `{name}` must only contain alphanumeric characters and underscores"
+                ));
+            }
+            if !names.insert(name) {
+                return Err(format!("Parameter `{name}` is defined more than once"));
+            }
+
+            match definition.kind {
+                CommandParameterType::Enum if definition.values.is_empty() => {
+                    return Err(format!(
+                        "Parameter `{name}` is an enum, and must have `values`"
+                    ));
+                }
+                CommandParameterType::Enum => {}
+                _ if !definition.values.is_empty() => {
+                    return Err(format!(
+                        "Parameter `{name}` has `values`, which are only for enums"
+                    ));
+                }
+                _ => {}
+            }
+
+            let regex = definition
+                .regex
+                .as_ref()
+                .map(|r| Regex::new(&format!("^(?:{r})$")))
+                .transpose()
+                .map_err(|e| format!("Parameter `{name}` has an invalid regex: {e}"))?;
+
+            let parameter = CheckedParameter {
+                definition: definition.clone(),
+                regex,
+            };
+
+            if let Some(default) = &definition.default {
+                parameter
+                    .check_value(default)
+                    .map_err(|e| format!("Invalid default value. {e}"))?;
+            }
+
+            parameters.push(parameter);
+        }
+
+        Ok(Self { parameters })
+    }
+
+    /// Whether the command can run without any given values
+    pub fn all_have_defaults(&self) -> bool {
+        self.parameters
+            .iter()
+            .all(|p| p.definition.default.is_some())
+    }
+
+    pub fn definitions(&self) -> Vec<CommandParameter> {
+        self.parameters
+            .iter()
+            .map(|p| p.definition.clone())
+            .collect()
+    }
+
+    /// Checks the given values, and fills in the defaults of the missing ones
+    fn resolve_values(
+        &self,
+        values: &BTreeMap<String, String>,
+    ) -> Result<BTreeMap<String, String>, String> {
+        if let Some(unknown) = values
+            .keys()
+            .find(|name| !self.parameters.iter().any(|p| &p.definition.name == *name))
+        {
+            return Err(format!("Unknown parameter `{unknown}`"));
+        }
+
+        self.parameters
+            .iter()
+            .map(|p| {
+                let name = &p.definition.name;
+                let value = values
+                    .get(name)
+                    .or(p.definition.default.as_ref())
+                    .ok_or_else(|| format!("Parameter `{name}` is required"))?;
+                p.check_value(value)?;
+                Ok((name.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    /// Replaces `{name}` in the arguments with the values of the parameters. The values become
+    /// whole or parts of arguments, and are never interpreted by a shell. Braces that don't
+    /// contain a parameter name are left as they are.
+    pub fn substitute(
+        &self,
+        run_cmd: &[Vec<String>],
+        values: &BTreeMap<String, String>,
+    ) -> Result<Vec<Vec<String>>, Error> {
+        let values = self
+            .resolve_values(values)
+            .map_err(Error::InvalidCommandParameter)?;
+
+        let result = run_cmd
+            .iter()
+            .map(|cmd| cmd.iter().map(|arg| substitute_arg(arg, &values)).collect())
+            .collect();
+
+        Ok(result)
+    }
+}
+
+/// One pass over the argument, so that values that contain placeholders aren't substituted again
+fn substitute_arg(arg: &str, values: &BTreeMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut rest = arg;
+
+    while let Some(start) = rest.find('{') {
+        result += &rest[..start];
+        rest = &rest[start..];
+
+        let value = rest
+            .find('}')
+            .and_then(|end| values.get(&rest[1..end]).map(|v| (end, v)));
+
+        match value {
+            Some((end, value)) => {
+                result += value;
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result + rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameter(name: &str, kind: CommandParameterType) -> CommandParameter {
+        CommandParameter {
+            name: name.to_string(),
+            label: name.to_string(),
+            kind,
+            values: Vec::new(),
+            regex: None,
+            allow_leading_dash: false,
+            default: None,
+        }
+    }
+
+    #[test]
+    fn substitution() {
+        let set = ParameterSet::new(&[
+            CommandParameter {
+                values: vec!["docker".to_string(), "nginx".to_string()],
+                ..parameter("service", CommandParameterType::Enum)
+            },
+            CommandParameter {
+                default: Some("10".to_string()),
+                ..parameter("lines", CommandParameterType::Int)
+            },
+        ])
+        .unwrap();
+        let run_cmd = vec![
+            vec![
+                "journalctl".to_string(),
+                "-u".to_string(),
+                "{service}".to_string(),
+            ],
+            vec!["tail".to_string(), "-n{lines}".to_string()],
+            vec!["awk".to_string(), "{print $2}".to_string()],
+        ];
+
+        let values = [("service".to_string(), "nginx".to_string())].into();
+        assert_eq!(
+            set.substitute(&run_cmd, &values).unwrap(),
+            vec![
+                vec!["journalctl", "-u", "nginx"],
+                vec!["tail", "-n10"],
+                vec!["awk", "{print $2}"],
+            ]
+        );
+
+        let values = [("service".to_string(), "sshd".to_string())].into();
+        assert!(set.substitute(&run_cmd, &values).is_err());
+        assert!(set.substitute(&run_cmd, &BTreeMap::new()).is_err());
+        let values = [
+            ("service".to_string(), "docker".to_string()),
+            ("other".to_string(), "x".to_string()),
+        ]
+        .into();
+        assert!(set.substitute(&run_cmd, &values).is_err());
+    }
+
+    #[test]
+    fn values_are_substituted_once() {
+        let set = ParameterSet::new(&[
+            parameter("a", CommandParameterType::String),
+            parameter("b", CommandParameterType::String),
+        ])
+        .unwrap();
+        let values = [
+            ("a".to_string(), "{b}".to_string()),
+            ("b".to_string(), "x".to_string()),
+        ]
+        .into();
+
+        assert_eq!(
+            set.substitute(&[vec!["{a}{b}".to_string()]], &values)
+                .unwrap(),
+            vec![vec!["{b}x"]]
+        );
+    }
+
+    #[test]
+    fn definitions_are_checked() {
+        let with_regex = |regex: &str, default: &str| CommandParameter {
+            regex: Some(regex.to_string()),
+            default: Some(default.to_string()),
+            ..parameter("p", CommandParameterType::String)
+        };
+        assert!(ParameterSet::new(&[with_regex("[a-z]+", "abc")]).is_ok());
+        // The whole value must match
+        assert!(ParameterSet::new(&[with_regex("[a-z]+", "abc1")]).is_err());
+        assert!(ParameterSet::new(&[with_regex("(", "abc")]).is_err());
+
+        assert!(ParameterSet::new(&[parameter("p", CommandParameterType::Enum)]).is_err());
+        assert!(ParameterSet::new(&[parameter("with space", CommandParameterType::Int)]).is_err());
+        assert!(ParameterSet::new(&[
+            parameter("p", CommandParameterType::Bool),
+            parameter("p", CommandParameterType::Bool)
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn strings_that_look_like_options_are_rejected() {
+        let run_cmd = [vec![
+            "systemctl".to_string(),
+            "status".to_string(),
+            "{unit}".to_string(),
+        ]];
+        let values = |value: &str| [("unit".to_string(), value.to_string())].into();
+
+        // Even when the pattern would let them through
+        let set = ParameterSet::new(&[CommandParameter {
+            regex: Some("[a-z-]+".to_string()),
+            ..parameter("unit", CommandParameterType::String)
+        }])
+        .unwrap();
+        assert!(set.substitute(&run_cmd, &values("--all")).is_err());
+        assert!(set.substitute(&run_cmd, &values("-")).is_err());
+        assert_eq!(
+            set.substitute(&run_cmd, &values("docker-compose")).unwrap(),
+            vec![vec!["systemctl", "status", "docker-compose"]]
+        );
+
+        let set = ParameterSet::new(&[CommandParameter {
+            allow_leading_dash: true,
+            ..parameter("unit", CommandParameterType::String)
+        }])
+        .unwrap();
+        assert_eq!(
+            set.substitute(&run_cmd, &values("--all")).unwrap(),
+            vec![vec!["systemctl", "status", "--all"]]
+        );
+
+        // Negative numbers are still integers
+        let set = ParameterSet::new(&[parameter("unit", CommandParameterType::Int)]).unwrap();
+        assert!(set.substitute(&run_cmd, &values("-5")).is_ok());
+
+        // Defaults are checked like the values
+        assert!(ParameterSet::new(&[CommandParameter {
+            default: Some("-x".to_string()),
+            ..parameter("unit", CommandParameterType::String)
+        }])
+        .is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::OwnedMutexGuard;
+
+/// Locks that serialize operations on the same dataset (or pool), while operations on
+/// different datasets can proceed in parallel.
+#[derive(Clone, Default)]
+pub struct DatasetLocks {
+    locks: Arc<Mutex<BTreeMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl DatasetLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits until no other operation is running on the given dataset, and returns a guard
+    /// that keeps the dataset locked until it's dropped.
+    pub async fn lock(&self, dataset_name: impl Into<String>) -> OwnedMutexGuard<()> {
+        let dataset_lock = {
+            let mut locks = self.locks.lock().expect("Poisoned mutex");
+
+            // Locks that nobody holds or waits for are removed, so that the map doesn't grow forever
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+
+            locks.entry(dataset_name.into()).or_default().clone()
+        };
+
+        dataset_lock.lock_owned().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn same_dataset_is_serialized_different_datasets_are_not() {
+        let locks = DatasetLocks::new();
+
+        let guard = locks.lock("pool/ds1").await;
+
+        // A different dataset is not blocked
+        let _other_guard = tokio::time::timeout(Duration::from_secs(1), locks.lock("pool/ds2"))
+            .await
+            .unwrap();
+
+        // The same dataset is blocked until the guard is dropped
+        tokio::time::timeout(Duration::from_millis(100), locks.lock("pool/ds1"))
+            .await
+            .unwrap_err();
+
+        drop(guard);
+
+        let _guard = tokio::time::timeout(Duration::from_secs(1), locks.lock("pool/ds1"))
+            .await
+            .unwrap();
+    }
+}
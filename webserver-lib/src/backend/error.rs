@@ -6,6 +6,8 @@ use hyper::StatusCode;
 use sam_zfs_unlocker::ZfsError;
 use serde_json::json;
 
+use super::{zfs_key::ZfsKeyError, zfs_mount::ZfsMountError, zpool::ZpoolError};
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("ZFS error: {0}")]
@@ -26,6 +28,40 @@ pub enum Error {
     BlacklistedDataset(String),
     #[error("Internal invariant error: A registered command was not found: {0}")]
     RegisteredCmdMissing(String),
+    #[error("Zpool error: {0}")]
+    Zpool(#[from] ZpoolError),
+    #[error("ZFS pool {0} is not available for import")]
+    PoolNotImportable(String),
+    #[error("Blocking task failed: {0}")]
+    BlockingTask(String),
+    #[error("Unlock group {0} not found")]
+    UnlockGroupNotFound(String),
+    #[error("Invalid command parameter: {0}")]
+    InvalidCommandParameter(String),
+    #[error("Job {0} not found")]
+    JobNotFound(u64),
+    #[error(
+        "Command {0} can't run now, because it or a command that excludes it is already running"
+    )]
+    CommandBusy(String),
+    #[error("Command {0} must be confirmed, with the `confirmation` of its run options")]
+    ConfirmationRequired(String),
+    #[error("Custom command {0} not found")]
+    CustomCommandNotFound(String),
+    #[error("Invalid stdin: {0}")]
+    InvalidStdin(String),
+    #[error("The stdin is larger than the limit of the command, which is {0} bytes")]
+    StdinTooLarge(usize),
+    #[error("Command {0} can't run yet: {1}")]
+    PreconditionNotMet(String, String),
+    #[error("ZFS mount error: {0}")]
+    ZfsMount(#[from] ZfsMountError),
+    #[error("ZFS key error: {0}")]
+    ZfsKey(#[from] ZfsKeyError),
+    #[error(
+        "Mounting at {0} is not allowed. Allowed mountpoint prefixes are configured in the server"
+    )]
+    MountpointNotAllowed(String),
 }
 
 impl IntoResponse for Error {
@@ -40,6 +76,21 @@ impl IntoResponse for Error {
             Error::ZfsDisabled => (StatusCode::UNAUTHORIZED, self.to_string()),
             Error::BlacklistedDataset(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
             Error::RegisteredCmdMissing(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            Error::Zpool(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
+            Error::PoolNotImportable(_) => (StatusCode::NOT_FOUND, self.to_string()),
+            Error::BlockingTask(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            Error::UnlockGroupNotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
+            Error::InvalidCommandParameter(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            Error::JobNotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
+            Error::CommandBusy(_) => (StatusCode::CONFLICT, self.to_string()),
+            Error::ConfirmationRequired(_) => (StatusCode::PRECONDITION_REQUIRED, self.to_string()),
+            Error::CustomCommandNotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
+            Error::InvalidStdin(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            Error::StdinTooLarge(_) => (StatusCode::PAYLOAD_TOO_LARGE, self.to_string()),
+            Error::PreconditionNotMet(_, _) => (StatusCode::PRECONDITION_FAILED, self.to_string()),
+            Error::ZfsMount(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
+            Error::ZfsKey(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
+            Error::MountpointNotAllowed(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
         };
 
         (status, Json(json!({ "error": message }))).into_response()
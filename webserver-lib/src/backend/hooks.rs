@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+
+use crate::run_options::config::DatasetHook;
+
+use super::routable_command::RoutableCommand;
+
+/// What a hook runs
+#[derive(Clone, Debug)]
+pub enum HookCommand {
+    /// A custom command, which runs like when it's called, within its limits
+    CustomCommand(String),
+    /// Commands given in the hook itself
+    Inline(Vec<Vec<String>>),
+}
+
+/// A hook with its commands resolved, whether they were given inline or by a custom command endpoint
+#[derive(Clone, Debug)]
+pub struct ResolvedHook {
+    pub dataset_pattern: String,
+    pub label: String,
+    pub command: HookCommand,
+}
+
+impl ResolvedHook {
+    pub fn resolve(
+        hook: &DatasetHook,
+        custom_commands_routables: &BTreeMap<String, RoutableCommand>,
+    ) -> Option<Self> {
+        let (default_label, command) = match (&hook.custom_command, &hook.run_cmd) {
+            (Some(endpoint), _) => {
+                let cmd = custom_commands_routables.get(endpoint)?;
+                (
+                    cmd.label.clone(),
+                    HookCommand::CustomCommand(endpoint.clone()),
+                )
+            }
+            (None, Some(run_cmd)) => (run_cmd.as_string(), HookCommand::Inline(run_cmd.commands())),
+            (None, None) => return None,
+        };
+
+        Some(Self {
+            dataset_pattern: hook.dataset.clone(),
+            label: hook.label.clone().unwrap_or(default_label),
+            command,
+        })
+    }
+}
+
+/// Matches a dataset name against a pattern, where `*` matches any sequence of characters
+pub fn dataset_pattern_matches(pattern: &str, dataset_name: &str) -> bool {
+    let mut parts = pattern.split('*');
+
+    // There's always a first part, even if empty
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = dataset_name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*` in the pattern
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dataset_pattern_matches;
+
+    #[test]
+    fn pattern_matching() {
+        assert!(dataset_pattern_matches("tank/docker", "tank/docker"));
+        assert!(!dataset_pattern_matches("tank/docker", "tank/docker/data"));
+        assert!(!dataset_pattern_matches("tank/docker", "tank"));
+
+        assert!(dataset_pattern_matches("*", "tank/docker"));
+        assert!(dataset_pattern_matches("tank/*", "tank/docker"));
+        assert!(dataset_pattern_matches("tank/*", "tank/docker/data"));
+        assert!(!dataset_pattern_matches("tank/*", "tank"));
+        assert!(!dataset_pattern_matches("tank/*", "backup/tank/docker"));
+
+        assert!(dataset_pattern_matches("*/docker", "tank/docker"));
+        assert!(!dataset_pattern_matches("*/docker", "tank/docker/data"));
+        assert!(dataset_pattern_matches("tank/*/data", "tank/docker/data"));
+        assert!(dataset_pattern_matches("tank/*/*", "tank/docker/data"));
+        assert!(!dataset_pattern_matches("tank/*/*", "tank/docker"));
+        assert!(!dataset_pattern_matches("tank/*ab*ab", "tank/ab"));
+    }
+}
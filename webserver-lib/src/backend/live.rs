@@ -1,43 +1,109 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, path::Path, sync::Arc, time::Duration};
 
+use crate::run_options::config::{ApiServerConfig, DatasetHook};
 use axum::async_trait;
 use common::types::{
-    AvailableCustomCommands, CustomCommandPublicInfo, DatasetFullMountState,
-    DatasetMountedResponse, DatasetsFullMountState, KeyLoadedResponse, RunCommandOutput,
+    AvailableCustomCommands, CommandKind, CommandStopReason, CustomCommandPublicInfo,
+    CustomCommandRunOptions, DatasetFullMountState, DatasetMountOptions, DatasetMountedResponse,
+    DatasetsFullMountState, HookOutput, ImportablePools, JobId, KeyLoadedResponse,
+    PassphraseVerifiedResponse, PoolImportOptions, PoolImportedResponse, RunCommandOutput,
+    UnlockGroup, UnlockGroupMemberOutcome, UnlockGroupResponse, UnlockGroups,
 };
-use sam_zfs_unlocker::{
-    zfs_is_dataset_mounted, zfs_is_key_loaded, zfs_load_key, zfs_mount_dataset,
-};
-
-use crate::run_options::config::ApiServerConfig;
+use tokio::sync::watch;
 
 use super::{
-    command_caller::chain_commands,
+    command_caller::{chain_commands_with_control, wait_for_cancel, OutputSender, RunControl},
+    command_limits::CommandLimits,
+    dataset_locks::DatasetLocks,
     error::Error,
+    hooks::{dataset_pattern_matches, HookCommand, ResolvedHook},
     routable_command::RoutableCommand,
+    running_commands::RunningCommands,
     traits::{ExecutionBackend, ExtraRequestErrors},
+    zfs_driver::{system::SystemZfsDriver, ZfsDriver},
+    zfs_mount::check_mountpoint,
 };
 
-pub struct LiveExecutionBackend {
-    config: ApiServerConfig,
-    custom_commands_routables: BTreeMap<String, RoutableCommand>,
+pub struct LiveExecutionBackend<D: ZfsDriver = SystemZfsDriver> {
+    config: Arc<ApiServerConfig>,
+    custom_commands_routables: Arc<BTreeMap<String, RoutableCommand>>,
+    running_commands: RunningCommands,
+    command_limits: CommandLimits,
+    dataset_locks: DatasetLocks,
+    on_key_loaded_hooks: Arc<Vec<ResolvedHook>>,
+    on_mounted_hooks: Arc<Vec<ResolvedHook>>,
+    driver: Arc<D>,
+}
+
+// Manually implemented, because deriving requires the driver to be Clone
+impl<D: ZfsDriver> Clone for LiveExecutionBackend<D> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            custom_commands_routables: self.custom_commands_routables.clone(),
+            running_commands: self.running_commands.clone(),
+            command_limits: self.command_limits.clone(),
+            dataset_locks: self.dataset_locks.clone(),
+            on_key_loaded_hooks: self.on_key_loaded_hooks.clone(),
+            on_mounted_hooks: self.on_mounted_hooks.clone(),
+            driver: self.driver.clone(),
+        }
+    }
 }
 
 impl LiveExecutionBackend {
     pub fn new(config: ApiServerConfig) -> Self {
+        Self::new_with_driver(config, SystemZfsDriver)
+    }
+}
+
+impl<D: ZfsDriver> LiveExecutionBackend<D> {
+    pub fn new_with_driver(config: ApiServerConfig, driver: D) -> Self {
+        let default_command_timeout = config
+            .custom_commands_config
+            .default_command_timeout_secs
+            .map(Duration::from_secs);
+
         let custom_commands_routables = config
             .custom_commands_config
             .custom_commands
             .clone()
             .unwrap_or_default()
             .into_iter()
-            .map(RoutableCommand::from)
+            .filter(|cmd| cmd.enabled)
+            .enumerate()
+            .map(|(position, cmd)| RoutableCommand::from(cmd).at_position(position))
+            .map(|cmd| cmd.with_default_timeout(default_command_timeout))
+            .map(|cmd| {
+                cmd.with_default_max_output_bytes(config.custom_commands_config.max_output_bytes)
+            })
             .map(|cmd| (cmd.url_endpoint.clone(), cmd))
             .collect::<BTreeMap<_, _>>();
 
+        let resolve_hooks = |hooks: &[DatasetHook]| {
+            hooks
+                .iter()
+                .filter_map(|h| ResolvedHook::resolve(h, &custom_commands_routables))
+                .collect::<Vec<_>>()
+        };
+        let on_key_loaded_hooks = resolve_hooks(&config.zfs_config.on_key_loaded);
+        let on_mounted_hooks = resolve_hooks(&config.zfs_config.on_mounted);
+
+        let command_limits = CommandLimits::new(
+            custom_commands_routables.values(),
+            config.custom_commands_config.max_concurrent_commands,
+            config.custom_commands_config.when_busy,
+        );
+
         Self {
-            custom_commands_routables,
-            config,
+            custom_commands_routables: Arc::new(custom_commands_routables),
+            config: Arc::new(config),
+            running_commands: RunningCommands::new(),
+            command_limits,
+            dataset_locks: DatasetLocks::new(),
+            on_key_loaded_hooks: Arc::new(on_key_loaded_hooks),
+            on_mounted_hooks: Arc::new(on_mounted_hooks),
+            driver: Arc::new(driver),
         }
     }
 
@@ -71,6 +137,213 @@ impl LiveExecutionBackend {
         Ok(())
     }
 
+    /// Alternate mountpoints are only allowed under the configured prefixes
+    fn mountpoint_allowed_or_error(&self, options: &DatasetMountOptions) -> Result<(), Error> {
+        let Some(mountpoint) = &options.mountpoint else {
+            return Ok(());
+        };
+
+        let mountpoint = check_mountpoint(mountpoint)?;
+
+        if !self
+            .config
+            .zfs_config
+            .allowed_mountpoint_prefixes
+            .iter()
+            .any(|prefix| Path::new(&mountpoint).starts_with(prefix))
+        {
+            return Err(Error::MountpointNotAllowed(mountpoint));
+        }
+
+        Ok(())
+    }
+
+    fn custom_cmd_or_error(&self, endpoint: &str) -> Result<&RoutableCommand, Error> {
+        self.custom_commands_routables.get(endpoint).ok_or(
+            <Error as ExtraRequestErrors<Self>>::make_error_internetl_custom_command_error(
+                endpoint.to_string(),
+            ),
+        )
+    }
+
+    /// Runs a custom command with its parameters substituted, within the concurrency limits,
+    /// where it can be cancelled and is killed after its timeout
+    async fn run_custom_cmd(
+        &self,
+        endpoint: &str,
+        options: CustomCommandRunOptions,
+        job_id: Option<JobId>,
+        output: Option<OutputSender>,
+    ) -> Result<RunCommandOutput, Error> {
+        let cmd = self.custom_cmd_or_error(endpoint)?;
+
+        if !cmd.confirmed_by(&options) {
+            return Err(Error::ConfirmationRequired(endpoint.to_string()));
+        }
+
+        if !cmd.preconditions.is_empty() {
+            let datasets = self.zfs_encrypted_datasets_state().await?;
+            let unmet = cmd.preconditions.unmet(&datasets.states);
+            if !unmet.is_empty() {
+                return Err(Error::PreconditionNotMet(
+                    endpoint.to_string(),
+                    unmet.join("; "),
+                ));
+            }
+        }
+
+        let run_cmd = cmd.commands_with_parameters(&options.parameters)?;
+        let stdin = cmd.stdin_bytes(&options)?;
+
+        // Only the runs of jobs can be cancelled
+        let mut cancel = job_id.map(|job_id| self.running_commands.start(job_id));
+
+        // A run that waits for its turn can be cancelled too
+        let _permit = tokio::select! {
+            permit = self.command_limits.acquire(endpoint) => permit?,
+            () = wait_for_cancel(cancel.as_mut()) => {
+                return Ok(RunCommandOutput {
+                    error_code: 255,
+                    stopped: Some(CommandStopReason::Cancelled),
+                    ..Default::default()
+                })
+            }
+        };
+
+        let control = RunControl {
+            output,
+            timeout: cmd.timeout,
+            cancel,
+            chain: cmd.chain.clone(),
+            max_output_bytes: cmd.max_output_bytes,
+            encoding: cmd.output_encoding,
+        };
+
+        chain_commands_with_control(&run_cmd, stdin, control)
+            .await
+            .map(|output| cmd.describe_output(output))
+    }
+
+    /// Runs commands that are written in the config rather than being custom commands, where
+    /// they're killed after the default timeout, like custom commands without their own
+    async fn run_config_cmds(
+        &self,
+        commands: &[Vec<String>],
+        stdin: Option<String>,
+    ) -> Result<RunCommandOutput, Error> {
+        // The input ends with a new line, as if it was typed in a terminal
+        let stdin = stdin.map(|stdin| format!("{stdin}\n").into_bytes());
+        let control = RunControl {
+            timeout: self
+                .config
+                .custom_commands_config
+                .default_command_timeout_secs
+                .map(Duration::from_secs),
+            max_output_bytes: Some(self.config.custom_commands_config.max_output_bytes),
+            ..Default::default()
+        };
+
+        chain_commands_with_control(commands, stdin, control).await
+    }
+
+    /// Runs, in order, the hooks that match the given dataset. A failing hook doesn't stop the ones
+    /// after it. Hooks that reference a custom command run it like a call would, within its limits.
+    async fn run_hooks(&self, hooks: &[ResolvedHook], dataset_name: &str) -> Vec<HookOutput> {
+        let mut result = Vec::new();
+
+        for hook in hooks
+            .iter()
+            .filter(|h| dataset_pattern_matches(&h.dataset_pattern, dataset_name))
+        {
+            let output = match &hook.command {
+                HookCommand::CustomCommand(endpoint) => {
+                    // The command was configured to run here, which confirms it
+                    let options = CustomCommandRunOptions {
+                        confirmation: self
+                            .custom_commands_routables
+                            .get(endpoint)
+                            .and_then(|c| c.confirmation_token())
+                            .map(String::from),
+                        ..Default::default()
+                    };
+                    self.run_custom_cmd(endpoint, options, None, None).await
+                }
+                HookCommand::Inline(run_cmd) => self.run_config_cmds(run_cmd, None).await,
+            };
+
+            result.push(HookOutput {
+                label: hook.label.clone(),
+                output: output.unwrap_or_else(|e| RunCommandOutput {
+                    stdout: String::new(),
+                    stderr: e.to_string(),
+                    error_code: 253,
+                    ..Default::default()
+                }),
+            });
+        }
+
+        result
+    }
+
+    /// The unlock groups, without their blacklisted datasets
+    fn unlock_groups(&self) -> Vec<UnlockGroup> {
+        self.config
+            .zfs_config
+            .unlock_groups
+            .iter()
+            .map(|g| UnlockGroup {
+                name: g.name.clone(),
+                datasets: g
+                    .datasets
+                    .iter()
+                    .filter(|ds| !self.zfs_dataset_blacklisted(ds))
+                    .cloned()
+                    .collect(),
+                mount_after: g.mount_after,
+            })
+            .collect()
+    }
+
+    async fn unlock_group_member(
+        &self,
+        dataset_name: &str,
+        passphrase: &str,
+        mount_after: bool,
+    ) -> UnlockGroupMemberOutcome {
+        let mut outcome = UnlockGroupMemberOutcome::default();
+
+        match self.zfs_load_key(dataset_name, passphrase).await {
+            Ok(response) => {
+                outcome.key_loaded = true;
+                outcome.hooks.extend(response.hooks);
+            }
+            Err(e) => {
+                outcome.error = Some(e.to_string());
+                return outcome;
+            }
+        }
+
+        if mount_after {
+            match self
+                .zfs_mount_dataset(dataset_name, &DatasetMountOptions::default())
+                .await
+            {
+                Ok(response) => {
+                    outcome.is_mounted = true;
+                    outcome.hooks.extend(response.hooks);
+                }
+                Err(e) => outcome.error = Some(e.to_string()),
+            }
+        } else {
+            match self.zfs_encrypted_dataset_state(dataset_name).await {
+                Ok(state) => outcome.is_mounted = state.is_mounted,
+                Err(e) => outcome.error = Some(e.to_string()),
+            }
+        }
+
+        outcome
+    }
+
     fn internal_get_encrypted_datasets_state(&self) -> Result<DatasetsFullMountState, Error> {
         let config = &self.config.zfs_config;
         if !config.zfs_enabled {
@@ -79,20 +352,10 @@ impl LiveExecutionBackend {
             });
         }
 
-        let mount_states = sam_zfs_unlocker::zfs_list_encrypted_datasets()?;
-
-        let mount_states = mount_states
+        let mount_states = self
+            .driver
+            .list_encrypted_datasets()?
             .into_iter()
-            .map(|(ds_name, m)| {
-                (
-                    ds_name,
-                    DatasetFullMountState {
-                        dataset_name: m.dataset_name,
-                        key_loaded: m.is_key_loaded,
-                        is_mounted: m.is_mounted,
-                    },
-                )
-            })
             .filter(|(ds_name, _m)| !self.zfs_dataset_blacklisted(ds_name))
             .collect::<BTreeMap<_, _>>();
 
@@ -100,24 +363,11 @@ impl LiveExecutionBackend {
             states: mount_states,
         })
     }
-}
-
-#[async_trait]
-impl ExecutionBackend for LiveExecutionBackend {
-    type Error = super::error::Error;
-
-    fn zfs_encrypted_datasets_state(&self) -> Result<DatasetsFullMountState, Self::Error> {
-        let result = self.internal_get_encrypted_datasets_state()?;
-
-        Ok(result)
-    }
 
-    fn zfs_encrypted_dataset_state(
+    fn blocking_zfs_encrypted_dataset_state(
         &self,
-        dataset_name: impl AsRef<str>,
-    ) -> Result<DatasetFullMountState, Self::Error> {
-        let dataset_name = dataset_name.as_ref();
-
+        dataset_name: &str,
+    ) -> Result<DatasetFullMountState, Error> {
         let mut all_datasets_states = self.internal_get_encrypted_datasets_state()?;
 
         let result = all_datasets_states
@@ -128,77 +378,306 @@ impl ExecutionBackend for LiveExecutionBackend {
         Ok(result)
     }
 
-    fn zfs_load_key(
-        &self,
-        dataset_name: impl AsRef<str>,
-        passphrase: impl AsRef<str>,
-    ) -> Result<KeyLoadedResponse, Self::Error> {
+    /// Returns whether the key was loaded by this call, as opposed to being already loaded
+    fn blocking_zfs_load_key(&self, dataset_name: &str, passphrase: &str) -> Result<bool, Error> {
         self.zfs_enabled_or_error()?;
 
-        let dataset_name = dataset_name.as_ref();
-
         self.zfs_dataset_not_blacklisted_or_error(dataset_name)?;
 
-        if zfs_is_key_loaded(dataset_name)?
+        if self
+            .driver
+            .is_key_loaded(dataset_name)?
             .ok_or(Error::DatasetNotFound(dataset_name.to_string()))?
         {
-            return Ok(KeyLoadedResponse {
-                dataset_name: dataset_name.to_string(),
-                key_loaded: true,
-            });
+            return Ok(false);
         }
 
-        zfs_load_key(dataset_name, passphrase)?;
+        self.driver.load_key(dataset_name, passphrase)?;
 
-        Ok(KeyLoadedResponse {
+        Ok(true)
+    }
+
+    fn blocking_zfs_verify_passphrase(
+        &self,
+        dataset_name: &str,
+        passphrase: &str,
+    ) -> Result<PassphraseVerifiedResponse, Error> {
+        self.zfs_enabled_or_error()?;
+
+        self.zfs_dataset_not_blacklisted_or_error(dataset_name)?;
+
+        self.driver
+            .is_key_loaded(dataset_name)?
+            .ok_or(Error::DatasetNotFound(dataset_name.to_string()))?;
+
+        let passphrase_correct = self.driver.verify_passphrase(dataset_name, passphrase)?;
+
+        Ok(PassphraseVerifiedResponse {
             dataset_name: dataset_name.to_string(),
-            key_loaded: true,
+            passphrase_correct,
         })
     }
 
-    fn zfs_mount_dataset(
+    /// Returns whether the dataset was mounted by this call, as opposed to being already mounted
+    fn blocking_zfs_mount_dataset(
         &self,
-        dataset_name: impl AsRef<str>,
-    ) -> Result<DatasetMountedResponse, Self::Error> {
+        dataset_name: &str,
+        options: &DatasetMountOptions,
+    ) -> Result<bool, Error> {
         self.zfs_enabled_or_error()?;
 
-        let dataset_name = dataset_name.as_ref();
-
         self.zfs_dataset_not_blacklisted_or_error(dataset_name)?;
 
-        if zfs_is_dataset_mounted(dataset_name)?
+        self.mountpoint_allowed_or_error(options)?;
+
+        if self
+            .driver
+            .is_mounted(dataset_name)?
             .ok_or(Error::DatasetNotFound(dataset_name.to_string()))?
         {
-            return Ok(DatasetMountedResponse {
-                dataset_name: dataset_name.to_string(),
-                is_mounted: true,
-            });
+            return Ok(false);
         }
 
-        if !zfs_is_key_loaded(dataset_name)?
+        if !self
+            .driver
+            .is_key_loaded(dataset_name)?
             .ok_or(Error::DatasetNotFound(dataset_name.to_string()))?
         {
             return Err(Error::KeyNotLoadedForDataset(dataset_name.to_string()));
         }
 
-        zfs_mount_dataset(dataset_name)?;
+        self.driver.mount(dataset_name, options)?;
+
+        Ok(true)
+    }
+
+    fn blocking_zfs_importable_pools(&self) -> Result<ImportablePools, Error> {
+        if !self.zfs_enabled() {
+            return Ok(ImportablePools {
+                pools: Default::default(),
+            });
+        }
+
+        let pools = self
+            .driver
+            .list_importable_pools()?
+            .into_iter()
+            .filter(|p| !self.zfs_dataset_blacklisted(&p.pool_name))
+            .map(|p| (p.pool_name.clone(), p))
+            .collect::<BTreeMap<_, _>>();
+
+        Ok(ImportablePools { pools })
+    }
+
+    fn blocking_zfs_import_pool(
+        &self,
+        pool_name: &str,
+        options: &PoolImportOptions,
+    ) -> Result<PoolImportedResponse, Error> {
+        self.zfs_enabled_or_error()?;
+
+        self.zfs_dataset_not_blacklisted_or_error(pool_name)?;
+
+        if !self
+            .driver
+            .list_importable_pools()?
+            .iter()
+            .any(|p| p.pool_name == pool_name)
+        {
+            return Err(Error::PoolNotImportable(pool_name.to_string()));
+        }
+
+        self.driver.import_pool(pool_name, options)?;
+
+        Ok(PoolImportedResponse {
+            pool_name: pool_name.to_string(),
+            is_imported: true,
+        })
+    }
+}
+
+#[async_trait]
+impl<D: ZfsDriver> ExecutionBackend for LiveExecutionBackend<D> {
+    type Error = super::error::Error;
+
+    async fn zfs_encrypted_datasets_state(&self) -> Result<DatasetsFullMountState, Self::Error> {
+        let this = self.clone();
+
+        run_blocking(move || this.internal_get_encrypted_datasets_state()).await
+    }
+
+    async fn zfs_encrypted_dataset_state(
+        &self,
+        dataset_name: &str,
+    ) -> Result<DatasetFullMountState, Self::Error> {
+        let this = self.clone();
+        let dataset_name = dataset_name.to_string();
+
+        run_blocking(move || this.blocking_zfs_encrypted_dataset_state(&dataset_name)).await
+    }
+
+    async fn zfs_load_key(
+        &self,
+        dataset_name: &str,
+        passphrase: &str,
+    ) -> Result<KeyLoadedResponse, Self::Error> {
+        let dataset_guard = self.dataset_locks.lock(dataset_name).await;
+
+        let this = self.clone();
+        let ds_name = dataset_name.to_string();
+        let passphrase = passphrase.to_string();
+
+        let key_loaded_now =
+            run_blocking(move || this.blocking_zfs_load_key(&ds_name, &passphrase)).await?;
+
+        // Hooks can take long, and shouldn't keep other operations on the dataset waiting
+        drop(dataset_guard);
+
+        let hooks = if key_loaded_now {
+            self.run_hooks(&self.on_key_loaded_hooks, dataset_name)
+                .await
+        } else {
+            Vec::new()
+        };
+
+        Ok(KeyLoadedResponse {
+            dataset_name: dataset_name.to_string(),
+            key_loaded: true,
+            hooks,
+        })
+    }
+
+    async fn zfs_verify_passphrase(
+        &self,
+        dataset_name: &str,
+        passphrase: &str,
+    ) -> Result<PassphraseVerifiedResponse, Self::Error> {
+        // Nothing changes, so there's no need to wait for other operations on the dataset
+        let this = self.clone();
+        let ds_name = dataset_name.to_string();
+        let passphrase = passphrase.to_string();
+
+        run_blocking(move || this.blocking_zfs_verify_passphrase(&ds_name, &passphrase)).await
+    }
+
+    async fn zfs_mount_dataset(
+        &self,
+        dataset_name: &str,
+        options: &DatasetMountOptions,
+    ) -> Result<DatasetMountedResponse, Self::Error> {
+        let dataset_guard = self.dataset_locks.lock(dataset_name).await;
+
+        let this = self.clone();
+        let ds_name = dataset_name.to_string();
+        let mount_options = options.clone();
+
+        let mounted_now =
+            run_blocking(move || this.blocking_zfs_mount_dataset(&ds_name, &mount_options)).await?;
+
+        // Hooks can take long, and shouldn't keep other operations on the dataset waiting
+        drop(dataset_guard);
+
+        // Hooks are meant for the regular mounts of datasets, not for temporary mounts to inspect them
+        let hooks = if mounted_now && options.is_default() {
+            self.run_hooks(&self.on_mounted_hooks, dataset_name).await
+        } else {
+            Vec::new()
+        };
 
         Ok(DatasetMountedResponse {
             dataset_name: dataset_name.to_string(),
             is_mounted: true,
+            hooks,
+        })
+    }
+
+    async fn zfs_importable_pools(&self) -> Result<ImportablePools, Self::Error> {
+        let this = self.clone();
+
+        run_blocking(move || this.blocking_zfs_importable_pools()).await
+    }
+
+    async fn zfs_import_pool(
+        &self,
+        pool_name: &str,
+        options: &PoolImportOptions,
+    ) -> Result<PoolImportedResponse, Self::Error> {
+        let _pool_guard = self.dataset_locks.lock(pool_name).await;
+
+        let this = self.clone();
+        let pool_name = pool_name.to_string();
+        let options = options.clone();
+
+        run_blocking(move || this.blocking_zfs_import_pool(&pool_name, &options)).await
+    }
+
+    async fn zfs_unlock_groups(&self) -> Result<UnlockGroups, Self::Error> {
+        if !self.zfs_enabled() {
+            return Ok(UnlockGroups { groups: Vec::new() });
+        }
+
+        Ok(UnlockGroups {
+            groups: self.unlock_groups(),
+        })
+    }
+
+    async fn zfs_unlock_group(
+        &self,
+        group_name: &str,
+        passphrase: &str,
+    ) -> Result<UnlockGroupResponse, Self::Error> {
+        self.zfs_enabled_or_error()?;
+
+        let group = self
+            .unlock_groups()
+            .into_iter()
+            .find(|g| g.name == group_name)
+            .ok_or(Error::UnlockGroupNotFound(group_name.to_string()))?;
+
+        // Members are unlocked one after the other, as every key load is CPU heavy anyway
+        let mut outcomes = BTreeMap::new();
+        for dataset_name in &group.datasets {
+            let outcome = self
+                .unlock_group_member(dataset_name, passphrase, group.mount_after)
+                .await;
+            outcomes.insert(dataset_name.to_string(), outcome);
+        }
+
+        Ok(UnlockGroupResponse {
+            group_name: group.name,
+            outcomes,
         })
     }
 
     fn custom_cmds_list(&self) -> Result<AvailableCustomCommands, Self::Error> {
-        let commands = self
-            .custom_commands_routables
-            .values()
+        let busy = self.command_limits.busy();
+
+        // The routables are keyed by endpoint, which is often a hash, so the order comes from the config
+        let mut routables = self.custom_commands_routables.values().collect::<Vec<_>>();
+        routables.sort_by_key(|c| (c.order, c.position));
+
+        let commands = routables
+            .into_iter()
             .map(|c| CustomCommandPublicInfo {
                 label: c.label.to_string(),
                 endpoint: c.url_endpoint.to_string(),
                 stdin_allow: c.stdin_allow,
                 stdin_text_placeholder: c.stdin_placeholder_text.to_string(),
                 stdin_is_password: c.stdin_is_password,
+                stdin_mode: c.stdin_mode,
+                stdin_max_bytes: c.stdin_max_bytes,
+                parameters: c.parameters.definitions(),
+                busy: busy.contains(&c.url_endpoint),
+                schedule: c.schedule.as_ref().map(|s| s.describe()),
+                group: c.group.clone(),
+                order: c.order,
+                description: c.description.clone(),
+                icon: c.icon.clone(),
+                confirm: c.confirm.clone(),
+                kind: c.kind,
+                status_interval_secs: (c.kind == CommandKind::Status)
+                    .then_some(c.status_interval_secs),
+                preconditions: c.preconditions.clone(),
             })
             .collect::<Vec<_>>();
 
@@ -214,19 +693,56 @@ impl ExecutionBackend for LiveExecutionBackend {
     async fn custom_cmd_call(
         &self,
         endpoint: &str,
-        initial_stdin_input: Option<String>,
+        options: CustomCommandRunOptions,
     ) -> Result<RunCommandOutput, Self::Error> {
-        let cmd = self.custom_commands_routables.get(endpoint).ok_or(
-            Error::make_error_internetl_custom_command_error(endpoint.to_string()),
-        )?;
+        self.run_custom_cmd(endpoint, options, None, None).await
+    }
 
-        let result = chain_commands(&cmd.run_cmd, initial_stdin_input).await?;
+    async fn custom_cmd_call_streaming(
+        &self,
+        endpoint: &str,
+        options: CustomCommandRunOptions,
+        job_id: JobId,
+        output: OutputSender,
+    ) -> Result<RunCommandOutput, Self::Error> {
+        self.run_custom_cmd(endpoint, options, Some(job_id), Some(output))
+            .await
+    }
 
-        Ok(result)
+    fn custom_cmds_busy(&self) -> Vec<String> {
+        self.command_limits.busy()
     }
+
+    fn custom_cmds_busy_changes(&self) -> watch::Receiver<()> {
+        self.command_limits.changes()
+    }
+
+    fn custom_cmd_cancel_job(&self, job_id: JobId) -> bool {
+        self.running_commands.cancel(job_id)
+    }
+
+    async fn run_commands_chain(
+        &self,
+        commands: &[Vec<String>],
+        initial_stdin_input: Option<String>,
+    ) -> Result<RunCommandOutput, Self::Error> {
+        self.run_config_cmds(commands, initial_stdin_input).await
+    }
+}
+
+/// ZFS commands are blocking (and `zfs load-key` can take a while to run the KDF),
+/// so they're run on the blocking thread pool to keep the async runtime responsive.
+async fn run_blocking<T, F>(f: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::BlockingTask(e.to_string()))?
 }
 
-impl ExtraRequestErrors<LiveExecutionBackend> for Error {
+impl<D: ZfsDriver> ExtraRequestErrors<LiveExecutionBackend<D>> for Error {
     fn make_error_passphrase_missing(dataset_name: impl Into<String>) -> Error {
         Error::PassphraseNotProvided(dataset_name.into())
     }
@@ -1,5 +1,15 @@
-mod command_caller;
+pub(crate) mod command_caller;
+mod command_limits;
+pub(crate) mod command_parameters;
+mod dataset_locks;
 pub mod error;
+mod hooks;
 pub mod live;
 mod routable_command;
+mod running_commands;
+mod system_command;
 pub mod traits;
+pub mod zfs_driver;
+mod zfs_key;
+mod zfs_mount;
+mod zpool;
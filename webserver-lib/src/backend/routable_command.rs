@@ -1,4 +1,15 @@
-use crate::run_options::config::CustomCommand;
+use std::{collections::BTreeMap, time::Duration};
+
+use base64::prelude::*;
+
+use common::types::{
+    CommandConfirmation, CommandKind, CommandPreconditions, CustomCommandRunOptions,
+    OutputEncoding, OutputFormat, RunCommandOutput, StdinMode, DEFAULT_STATUS_INTERVAL_SECS,
+};
+
+use crate::run_options::config::{ChainOptions, CommandSchedule, CustomCommand, StatusSuccess};
+
+use super::{command_parameters::ParameterSet, error::Error};
 
 fn hash_string(s: impl AsRef<str>) -> String {
     use blake2::{Blake2b512, Digest};
@@ -19,6 +30,119 @@ pub struct RoutableCommand {
     pub stdin_allow: bool,
     pub stdin_placeholder_text: String,
     pub stdin_is_password: bool,
+    pub stdin_mode: StdinMode,
+    pub stdin_trailing_newline: bool,
+    pub stdin_max_bytes: usize,
+    pub parameters: ParameterSet,
+    /// The time after which the command is killed
+    pub timeout: Option<Duration>,
+    pub max_concurrent: Option<usize>,
+    pub exclusive_group: Option<String>,
+    pub chain: ChainOptions,
+    /// The most bytes that are kept of each output
+    pub max_output_bytes: Option<usize>,
+    pub output_encoding: OutputEncoding,
+    pub output_format: OutputFormat,
+    /// The meanings of exit codes
+    pub exit_codes: BTreeMap<i32, String>,
+    /// When the server runs the command by itself
+    pub schedule: Option<CommandSchedule>,
+    pub group: Option<String>,
+    pub order: i32,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub confirm: Option<CommandConfirmation>,
+    pub kind: CommandKind,
+    /// When a status command is healthy
+    pub success: StatusSuccess,
+    pub status_interval_secs: u64,
+    /// The states of datasets that the command needs
+    pub preconditions: CommandPreconditions,
+    /// The position of the command in the config, which orders the commands with the same `order`
+    pub position: usize,
+}
+
+impl RoutableCommand {
+    /// The commands to run, with the parameters' placeholders replaced by the given values or the defaults
+    pub fn commands_with_parameters(
+        &self,
+        values: &BTreeMap<String, String>,
+    ) -> Result<Vec<Vec<String>>, Error> {
+        self.parameters.substitute(&self.run_cmd, values)
+    }
+
+    /// What a run must have as its confirmation, if the command requires one
+    pub fn confirmation_token(&self) -> Option<&str> {
+        self.confirm
+            .as_ref()
+            .map(|confirm| confirm.token(&self.url_endpoint))
+    }
+
+    /// Whether the run options have the confirmation that the command requires, if any
+    pub fn confirmed_by(&self, options: &CustomCommandRunOptions) -> bool {
+        match self.confirmation_token() {
+            Some(token) => options.confirmation.as_deref() == Some(token),
+            None => true,
+        }
+    }
+
+    /// The bytes to pipe to the command, decoded from the run options, within the size limit
+    /// and with the new line at the end if the command has one
+    pub fn stdin_bytes(&self, options: &CustomCommandRunOptions) -> Result<Option<Vec<u8>>, Error> {
+        let Some(stdin) = &options.stdin else {
+            return Ok(None);
+        };
+        if !self.stdin_allow {
+            return Err(Error::InvalidStdin(format!(
+                "Command {} doesn't take stdin",
+                self.url_endpoint
+            )));
+        }
+
+        let mut bytes = match options.stdin_encoding {
+            OutputEncoding::Text => stdin.as_bytes().to_vec(),
+            OutputEncoding::Base64 => BASE64_STANDARD
+                .decode(stdin)
+                .map_err(|e| Error::InvalidStdin(e.to_string()))?,
+        };
+        if bytes.len() > self.stdin_max_bytes {
+            return Err(Error::StdinTooLarge(self.stdin_max_bytes));
+        }
+        if self.stdin_trailing_newline {
+            bytes.push(b'\n');
+        }
+
+        Ok(Some(bytes))
+    }
+
+    /// Adds what the UI needs to show the output: its format, the meaning of the exit code,
+    /// and whether a status command is healthy
+    pub fn describe_output(&self, mut output: RunCommandOutput) -> RunCommandOutput {
+        output.format = self.output_format;
+        output.exit_code_meaning = self.exit_codes.get(&output.error_code).cloned();
+        output.status_ok = match self.kind {
+            CommandKind::Status => Some(self.success.is_met(&output)),
+            CommandKind::Action => None,
+        };
+        output
+    }
+
+    pub fn at_position(mut self, position: usize) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Uses the given timeout if the command doesn't have its own
+    pub fn with_default_timeout(mut self, default_timeout: Option<Duration>) -> Self {
+        self.timeout = self.timeout.or(default_timeout);
+        self
+    }
+
+    /// Uses the given output size limit if the command doesn't have its own
+    pub fn with_default_max_output_bytes(mut self, default_max_output_bytes: usize) -> Self {
+        self.max_output_bytes = self.max_output_bytes.or(Some(default_max_output_bytes));
+        self
+    }
 }
 
 fn endpoint_from_custom_command(cmd: &CustomCommand) -> String {
@@ -32,10 +156,43 @@ impl From<CustomCommand> for RoutableCommand {
         RoutableCommand {
             url_endpoint: endpoint_from_custom_command(&cmd),
             label: cmd.label,
+            parameters: ParameterSet::new(&cmd.parameters)
+                .expect("Parameters are checked when the config is loaded"),
             run_cmd: cmd.run_cmd.take_commands(),
             stdin_allow: cmd.stdin_allow,
             stdin_placeholder_text: cmd.stdin_placeholder_text,
-            stdin_is_password: cmd.stdin_is_password,
+            stdin_is_password: cmd
+                .stdin_is_password
+                .unwrap_or(cmd.stdin_mode == StdinMode::Line),
+            stdin_mode: cmd.stdin_mode,
+            stdin_trailing_newline: cmd
+                .stdin_trailing_newline
+                .unwrap_or(cmd.stdin_mode != StdinMode::File),
+            stdin_max_bytes: cmd.stdin_max_bytes,
+            timeout: cmd.timeout_secs.map(Duration::from_secs),
+            max_concurrent: cmd.max_concurrent,
+            exclusive_group: cmd.exclusive_group,
+            chain: cmd.chain,
+            max_output_bytes: cmd.max_output_bytes,
+            output_encoding: cmd.output_encoding,
+            output_format: cmd.output_format,
+            exit_codes: cmd.exit_codes,
+            schedule: cmd.schedule,
+            group: cmd.group,
+            order: cmd.order,
+            description: cmd.description,
+            icon: cmd.icon,
+            confirm: cmd.confirm,
+            kind: cmd.kind,
+            success: cmd.success.unwrap_or_default(),
+            status_interval_secs: cmd
+                .status_interval_secs
+                .unwrap_or(DEFAULT_STATUS_INTERVAL_SECS),
+            preconditions: CommandPreconditions {
+                requires_mounted: cmd.requires_mounted,
+                requires_key_loaded: cmd.requires_key_loaded,
+            },
+            position: 0,
         }
     }
 }
@@ -0,0 +1,74 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use common::types::JobId;
+use tokio::sync::watch;
+
+/// Lets the runs of custom commands be cancelled, one job at a time, so that cancelling
+/// a run never stops the other runs of the same command, e.g., the ones of other clients
+#[derive(Clone, Default)]
+pub struct RunningCommands {
+    cancellations: Arc<Mutex<BTreeMap<JobId, watch::Sender<()>>>>,
+}
+
+impl RunningCommands {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the run of the job, that is cancelled when the returned receiver changes.
+    /// The run stops being counted when the receiver is dropped.
+    pub fn start(&self, job_id: JobId) -> watch::Receiver<()> {
+        let mut cancellations = self.cancellations.lock().expect("Poisoned mutex");
+
+        // The runs that are over aren't kept
+        cancellations.retain(|_, sender| sender.receiver_count() > 0);
+
+        let (sender, receiver) = watch::channel(());
+        cancellations.insert(job_id, sender);
+
+        receiver
+    }
+
+    /// Cancels the run of the job, and returns whether it was running
+    pub fn cancel(&self, job_id: JobId) -> bool {
+        let cancellations = self.cancellations.lock().expect("Poisoned mutex");
+
+        match cancellations.get(&job_id) {
+            Some(sender) if sender.receiver_count() > 0 => {
+                sender.send_replace(());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn cancel_notifies_only_the_run_of_the_job() {
+        let running = RunningCommands::new();
+
+        let mut first = running.start(1);
+        let mut second = running.start(2);
+
+        assert!(running.cancel(1));
+
+        first.changed().await.unwrap();
+        tokio::time::timeout(Duration::from_millis(100), second.changed())
+            .await
+            .unwrap_err();
+
+        drop(first);
+        assert!(!running.cancel(1));
+        assert!(!running.cancel(3));
+        assert!(running.cancel(2));
+    }
+}
@@ -0,0 +1,56 @@
+use std::{io::Write, process::Command};
+
+/// Runs a command and returns (success, stdout, stderr)
+pub fn run_to_completion(cmd: Command) -> std::io::Result<(bool, String, String)> {
+    run_to_completion_with_stdin(cmd, None)
+}
+
+/// Runs a command, with the given line written to its stdin, and returns (success, stdout, stderr)
+pub fn run_to_completion_with_stdin(
+    mut cmd: Command,
+    stdin_line: Option<&str>,
+) -> std::io::Result<(bool, String, String)> {
+    let stdin = match stdin_line {
+        Some(_) => std::process::Stdio::piped(),
+        None => std::process::Stdio::null(),
+    };
+
+    let mut child = cmd
+        .stdin(stdin)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+
+    if let (Some(line), Some(mut stdin)) = (stdin_line, child.stdin.take()) {
+        writeln!(stdin, "{line}")?;
+        // stdin is dropped here, so that the child sees the end of the input
+    }
+
+    // Both pipes are read at once, as a child that fills one would wait forever for it to be read
+    let output = child.wait_with_output()?;
+
+    Ok((
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_stderr_does_not_block_the_command() {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "head -c 1000000 /dev/zero | tr '\\0' e >&2; echo done",
+        ]);
+
+        let (success, stdout, stderr) = run_to_completion(cmd).unwrap();
+        assert!(success);
+        assert_eq!(stdout, "done\n");
+        assert_eq!(stderr.len(), 1_000_000);
+    }
+}
@@ -2,30 +2,55 @@ use std::collections::BTreeMap;
 
 use axum::{async_trait, response::IntoResponse};
 use common::types::{
-    AvailableCustomCommands, DatasetFullMountState, DatasetMountedResponse, DatasetsFullMountState,
-    KeyLoadedResponse, RunCommandOutput,
+    AvailableCustomCommands, CustomCommandRunOptions, DatasetFullMountState, DatasetMountOptions,
+    DatasetMountedResponse, DatasetsFullMountState, ImportablePools, JobId, KeyLoadedResponse,
+    PassphraseVerifiedResponse, PoolImportOptions, PoolImportedResponse, RunCommandOutput,
+    UnlockGroupResponse, UnlockGroups,
 };
 
-use super::routable_command::RoutableCommand;
+use tokio::sync::watch;
 
+use super::{command_caller::OutputSender, routable_command::RoutableCommand};
+
+/// The backend is shared by all requests without a global lock. Implementations must not block
+/// the async runtime, and must serialize state-changing operations on the same dataset,
+/// while operations on different datasets can run in parallel.
 #[async_trait]
 pub trait ExecutionBackend: Send + Sync + 'static {
     type Error: std::error::Error + Send + Sync + 'static + IntoResponse + ExtraRequestErrors<Self>;
 
-    fn zfs_encrypted_datasets_state(&self) -> Result<DatasetsFullMountState, Self::Error>;
-    fn zfs_encrypted_dataset_state(
+    async fn zfs_encrypted_datasets_state(&self) -> Result<DatasetsFullMountState, Self::Error>;
+    async fn zfs_encrypted_dataset_state(
         &self,
-        dataset_name: impl AsRef<str>,
+        dataset_name: &str,
     ) -> Result<DatasetFullMountState, Self::Error>;
-    fn zfs_load_key(
+    async fn zfs_load_key(
         &self,
-        dataset_name: impl AsRef<str>,
-        passphrase: impl AsRef<str>,
+        dataset_name: &str,
+        passphrase: &str,
     ) -> Result<KeyLoadedResponse, Self::Error>;
-    fn zfs_mount_dataset(
+    async fn zfs_verify_passphrase(
+        &self,
+        dataset_name: &str,
+        passphrase: &str,
+    ) -> Result<PassphraseVerifiedResponse, Self::Error>;
+    async fn zfs_mount_dataset(
         &self,
-        dataset_name: impl AsRef<str>,
+        dataset_name: &str,
+        options: &DatasetMountOptions,
     ) -> Result<DatasetMountedResponse, Self::Error>;
+    async fn zfs_importable_pools(&self) -> Result<ImportablePools, Self::Error>;
+    async fn zfs_import_pool(
+        &self,
+        pool_name: &str,
+        options: &PoolImportOptions,
+    ) -> Result<PoolImportedResponse, Self::Error>;
+    async fn zfs_unlock_groups(&self) -> Result<UnlockGroups, Self::Error>;
+    async fn zfs_unlock_group(
+        &self,
+        group_name: &str,
+        passphrase: &str,
+    ) -> Result<UnlockGroupResponse, Self::Error>;
 
     fn custom_cmds_list(&self) -> Result<AvailableCustomCommands, Self::Error>;
 
@@ -34,6 +59,34 @@ pub trait ExecutionBackend: Send + Sync + 'static {
     async fn custom_cmd_call(
         &self,
         endpoint: &str,
+        options: CustomCommandRunOptions,
+    ) -> Result<RunCommandOutput, Self::Error>;
+
+    /// Like `custom_cmd_call`, as the run of the job, which can be cancelled, and where
+    /// the output is sent to `output` while the command runs
+    async fn custom_cmd_call_streaming(
+        &self,
+        endpoint: &str,
+        options: CustomCommandRunOptions,
+        job_id: JobId,
+        output: OutputSender,
+    ) -> Result<RunCommandOutput, Self::Error>;
+
+    /// The commands that would have to wait, or be rejected, if they were called now
+    fn custom_cmds_busy(&self) -> Vec<String>;
+
+    /// Changes when the busy commands may have changed
+    fn custom_cmds_busy_changes(&self) -> watch::Receiver<()>;
+
+    /// Kills the run of the job, and returns whether it was running
+    fn custom_cmd_cancel_job(&self, job_id: JobId) -> bool;
+
+    /// Runs commands that come from the server config rather than from a registered endpoint,
+    /// where the stdout of every command is piped to the next one. They're killed after
+    /// the default timeout of custom commands.
+    async fn run_commands_chain(
+        &self,
+        commands: &[Vec<String>],
         initial_stdin_input: Option<String>,
     ) -> Result<RunCommandOutput, Self::Error>;
 }
@@ -0,0 +1,273 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use common::types::{
+    DatasetFullMountState, DatasetMountOptions, ImportablePool, PoolImportOptions,
+};
+use sam_zfs_unlocker::ZfsError;
+
+use crate::backend::{error::Error, zpool::ZpoolError};
+
+use super::ZfsDriver;
+
+#[derive(Debug, Clone)]
+struct FakeDataset {
+    encryption_root: String,
+    is_mounted: bool,
+}
+
+#[derive(Debug, Clone)]
+struct FakeEncryptionRoot {
+    passphrase: String,
+    key_loaded: bool,
+}
+
+#[derive(Debug, Default)]
+struct FakeZfsState {
+    datasets: BTreeMap<String, FakeDataset>,
+    encryption_roots: BTreeMap<String, FakeEncryptionRoot>,
+    /// Pools that exist, but are not imported. Their datasets are invisible until they're imported.
+    exported_pools: BTreeSet<String>,
+    imports: Vec<(String, PoolImportOptions)>,
+    mounts: Vec<(String, DatasetMountOptions)>,
+    load_key_calls: usize,
+}
+
+fn pool_of(dataset_name: &str) -> &str {
+    dataset_name.split('/').next().unwrap_or(dataset_name)
+}
+
+impl FakeZfsState {
+    fn visible_dataset(&self, dataset_name: &str) -> Option<&FakeDataset> {
+        self.datasets
+            .get(dataset_name)
+            .filter(|_| !self.exported_pools.contains(pool_of(dataset_name)))
+    }
+
+    fn key_loaded(&self, ds: &FakeDataset) -> bool {
+        self.encryption_roots
+            .get(&ds.encryption_root)
+            .map(|r| r.key_loaded)
+            .unwrap_or(false)
+    }
+}
+
+/// An in-memory ZFS driver, that models encryption roots (where all the datasets under
+/// an encryption root share its key), passphrases, mount state and importable pools.
+#[derive(Clone, Default)]
+pub struct FakeZfsDriver {
+    state: Arc<Mutex<FakeZfsState>>,
+    load_key_delay: Option<Duration>,
+}
+
+impl FakeZfsDriver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an encrypted dataset that is its own encryption root
+    pub fn with_encryption_root(self, dataset_name: &str, passphrase: &str) -> Self {
+        {
+            let mut state = self.state.lock().expect("Poisoned mutex");
+            state.encryption_roots.insert(
+                dataset_name.to_string(),
+                FakeEncryptionRoot {
+                    passphrase: passphrase.to_string(),
+                    key_loaded: false,
+                },
+            );
+            state.datasets.insert(
+                dataset_name.to_string(),
+                FakeDataset {
+                    encryption_root: dataset_name.to_string(),
+                    is_mounted: false,
+                },
+            );
+        }
+        self
+    }
+
+    /// Adds an encrypted dataset that inherits its key from the given encryption root
+    pub fn with_child_dataset(self, dataset_name: &str, encryption_root: &str) -> Self {
+        self.state.lock().expect("Poisoned mutex").datasets.insert(
+            dataset_name.to_string(),
+            FakeDataset {
+                encryption_root: encryption_root.to_string(),
+                is_mounted: false,
+            },
+        );
+        self
+    }
+
+    /// Marks a pool as not imported. Its datasets become visible after it's imported.
+    pub fn with_exported_pool(self, pool_name: &str) -> Self {
+        self.state
+            .lock()
+            .expect("Poisoned mutex")
+            .exported_pools
+            .insert(pool_name.to_string());
+        self
+    }
+
+    /// Makes loading keys slow, like a real KDF
+    pub fn with_load_key_delay(mut self, delay: Duration) -> Self {
+        self.load_key_delay = Some(delay);
+        self
+    }
+
+    pub fn load_key_calls(&self) -> usize {
+        self.state.lock().expect("Poisoned mutex").load_key_calls
+    }
+
+    pub fn imports(&self) -> Vec<(String, PoolImportOptions)> {
+        self.state.lock().expect("Poisoned mutex").imports.clone()
+    }
+
+    pub fn mounts(&self) -> Vec<(String, DatasetMountOptions)> {
+        self.state.lock().expect("Poisoned mutex").mounts.clone()
+    }
+}
+
+impl ZfsDriver for FakeZfsDriver {
+    fn list_encrypted_datasets(&self) -> Result<BTreeMap<String, DatasetFullMountState>, Error> {
+        let state = self.state.lock().expect("Poisoned mutex");
+
+        let result = state
+            .datasets
+            .keys()
+            .filter_map(|name| state.visible_dataset(name).map(|ds| (name, ds)))
+            .map(|(name, ds)| {
+                (
+                    name.to_string(),
+                    DatasetFullMountState {
+                        dataset_name: name.to_string(),
+                        key_loaded: state.key_loaded(ds),
+                        is_mounted: ds.is_mounted,
+                    },
+                )
+            })
+            .collect();
+
+        Ok(result)
+    }
+
+    fn is_key_loaded(&self, dataset_name: &str) -> Result<Option<bool>, Error> {
+        let state = self.state.lock().expect("Poisoned mutex");
+
+        Ok(state
+            .visible_dataset(dataset_name)
+            .map(|ds| state.key_loaded(ds)))
+    }
+
+    fn is_mounted(&self, dataset_name: &str) -> Result<Option<bool>, Error> {
+        let state = self.state.lock().expect("Poisoned mutex");
+
+        Ok(state.visible_dataset(dataset_name).map(|ds| ds.is_mounted))
+    }
+
+    fn load_key(&self, dataset_name: &str, passphrase: &str) -> Result<(), Error> {
+        if let Some(delay) = self.load_key_delay {
+            std::thread::sleep(delay);
+        }
+
+        let mut state = self.state.lock().expect("Poisoned mutex");
+        state.load_key_calls += 1;
+
+        let encryption_root = state
+            .visible_dataset(dataset_name)
+            .ok_or(ZfsError::DatasetNotFound(dataset_name.to_string()))?
+            .encryption_root
+            .clone();
+
+        let root = state
+            .encryption_roots
+            .get_mut(&encryption_root)
+            .ok_or(ZfsError::DatasetNotFound(encryption_root.clone()))?;
+
+        if root.passphrase != passphrase {
+            return Err(ZfsError::LoadKeyCmdFailed(
+                dataset_name.to_string(),
+                format!("Key load error: Incorrect key provided for '{encryption_root}'."),
+            )
+            .into());
+        }
+
+        root.key_loaded = true;
+
+        Ok(())
+    }
+
+    fn verify_passphrase(&self, dataset_name: &str, passphrase: &str) -> Result<bool, Error> {
+        let state = self.state.lock().expect("Poisoned mutex");
+
+        let ds = state
+            .visible_dataset(dataset_name)
+            .ok_or(ZfsError::DatasetNotFound(dataset_name.to_string()))?;
+
+        let root = state
+            .encryption_roots
+            .get(&ds.encryption_root)
+            .ok_or(ZfsError::DatasetNotFound(ds.encryption_root.clone()))?;
+
+        Ok(root.passphrase == passphrase)
+    }
+
+    fn mount(&self, dataset_name: &str, options: &DatasetMountOptions) -> Result<(), Error> {
+        let mut state = self.state.lock().expect("Poisoned mutex");
+
+        let ds = state
+            .visible_dataset(dataset_name)
+            .ok_or(ZfsError::DatasetNotFound(dataset_name.to_string()))?;
+
+        if !state.key_loaded(ds) {
+            return Err(ZfsError::KeyNotLoadedForMount(dataset_name.to_string()).into());
+        }
+
+        state
+            .datasets
+            .get_mut(dataset_name)
+            .expect("Checked above")
+            .is_mounted = true;
+        state
+            .mounts
+            .push((dataset_name.to_string(), options.clone()));
+
+        Ok(())
+    }
+
+    fn list_importable_pools(&self) -> Result<Vec<ImportablePool>, Error> {
+        let state = self.state.lock().expect("Poisoned mutex");
+
+        let result = state
+            .exported_pools
+            .iter()
+            .enumerate()
+            .map(|(idx, pool_name)| ImportablePool {
+                pool_name: pool_name.to_string(),
+                pool_id: (idx + 1).to_string(),
+                state: "ONLINE".to_string(),
+            })
+            .collect();
+
+        Ok(result)
+    }
+
+    fn import_pool(&self, pool_name: &str, options: &PoolImportOptions) -> Result<(), Error> {
+        let mut state = self.state.lock().expect("Poisoned mutex");
+
+        if !state.exported_pools.remove(pool_name) {
+            return Err(ZpoolError::ImportCmdFailed(
+                pool_name.to_string(),
+                format!("cannot import '{pool_name}': no such pool available"),
+            )
+            .into());
+        }
+
+        state.imports.push((pool_name.to_string(), options.clone()));
+
+        Ok(())
+    }
+}
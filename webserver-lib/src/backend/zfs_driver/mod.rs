@@ -0,0 +1,41 @@
+#[cfg(test)]
+pub mod fake;
+pub mod system;
+
+use std::collections::BTreeMap;
+
+use common::types::{
+    DatasetFullMountState, DatasetMountOptions, ImportablePool, PoolImportOptions,
+};
+
+use super::error::Error;
+
+/// The low level ZFS operations used by the backend. Calls are blocking,
+/// and the backend takes care of running them on the blocking thread pool.
+///
+/// Checks related to the server's configuration (blacklisting, whether ZFS is enabled, etc)
+/// are the backend's responsibility, not the driver's.
+pub trait ZfsDriver: Send + Sync + 'static {
+    /// Lists the encrypted datasets, with their mount and key states
+    fn list_encrypted_datasets(&self) -> Result<BTreeMap<String, DatasetFullMountState>, Error>;
+
+    /// Returns None if the dataset is not found
+    fn is_key_loaded(&self, dataset_name: &str) -> Result<Option<bool>, Error>;
+
+    /// Returns None if the dataset is not found
+    fn is_mounted(&self, dataset_name: &str) -> Result<Option<bool>, Error>;
+
+    fn load_key(&self, dataset_name: &str, passphrase: &str) -> Result<(), Error>;
+
+    /// Checks the passphrase without loading the key. Returns whether the passphrase is correct.
+    fn verify_passphrase(&self, dataset_name: &str, passphrase: &str) -> Result<bool, Error>;
+
+    /// Mounts the dataset. Mountpoints in the options are already checked against the configuration.
+    fn mount(&self, dataset_name: &str, options: &DatasetMountOptions) -> Result<(), Error>;
+
+    /// Lists the pools that are not imported, but can be imported
+    fn list_importable_pools(&self) -> Result<Vec<ImportablePool>, Error>;
+
+    /// Imports a pool. Importing is never forced.
+    fn import_pool(&self, pool_name: &str, options: &PoolImportOptions) -> Result<(), Error>;
+}
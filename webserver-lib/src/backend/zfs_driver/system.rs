@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+
+use common::types::{
+    DatasetFullMountState, DatasetMountOptions, ImportablePool, PoolImportOptions,
+};
+use sam_zfs_unlocker::{
+    zfs_is_dataset_mounted, zfs_is_key_loaded, zfs_list_encrypted_datasets, zfs_load_key,
+    zfs_mount_dataset,
+};
+
+use crate::backend::{
+    error::Error,
+    zfs_key::zfs_verify_passphrase,
+    zfs_mount::zfs_mount_dataset_with_options,
+    zpool::{zpool_import_pool, zpool_list_importable_pools},
+};
+
+use super::ZfsDriver;
+
+/// The driver that controls the ZFS pools of the system, by calling the `zfs` and `zpool` commands
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemZfsDriver;
+
+impl ZfsDriver for SystemZfsDriver {
+    fn list_encrypted_datasets(&self) -> Result<BTreeMap<String, DatasetFullMountState>, Error> {
+        let result = zfs_list_encrypted_datasets()?
+            .into_iter()
+            .map(|(ds_name, m)| {
+                (
+                    ds_name,
+                    DatasetFullMountState {
+                        dataset_name: m.dataset_name,
+                        key_loaded: m.is_key_loaded,
+                        is_mounted: m.is_mounted,
+                    },
+                )
+            })
+            .collect();
+
+        Ok(result)
+    }
+
+    fn is_key_loaded(&self, dataset_name: &str) -> Result<Option<bool>, Error> {
+        Ok(zfs_is_key_loaded(dataset_name)?)
+    }
+
+    fn is_mounted(&self, dataset_name: &str) -> Result<Option<bool>, Error> {
+        Ok(zfs_is_dataset_mounted(dataset_name)?)
+    }
+
+    fn load_key(&self, dataset_name: &str, passphrase: &str) -> Result<(), Error> {
+        Ok(zfs_load_key(dataset_name, passphrase)?)
+    }
+
+    fn verify_passphrase(&self, dataset_name: &str, passphrase: &str) -> Result<bool, Error> {
+        Ok(zfs_verify_passphrase(dataset_name, passphrase)?)
+    }
+
+    fn mount(&self, dataset_name: &str, options: &DatasetMountOptions) -> Result<(), Error> {
+        if options.is_default() {
+            Ok(zfs_mount_dataset(dataset_name)?)
+        } else {
+            Ok(zfs_mount_dataset_with_options(dataset_name, options)?)
+        }
+    }
+
+    fn list_importable_pools(&self) -> Result<Vec<ImportablePool>, Error> {
+        Ok(zpool_list_importable_pools()?)
+    }
+
+    fn import_pool(&self, pool_name: &str, options: &PoolImportOptions) -> Result<(), Error> {
+        Ok(zpool_import_pool(pool_name, options)?)
+    }
+}
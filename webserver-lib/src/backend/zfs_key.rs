@@ -0,0 +1,48 @@
+use std::process::Command;
+
+use super::{
+    system_command::run_to_completion_with_stdin, zfs_mount::check_and_sanitize_dataset_name,
+};
+
+/// What ZFS prints when the passphrase is wrong, e.g., "Key load error: Incorrect key provided for 'tank'."
+const INCORRECT_KEY_MESSAGE: &str = "Incorrect key provided";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ZfsKeyError {
+    #[error("System error: {0}")]
+    SystemError(String),
+    #[error("Dataset name is invalid: {0}")]
+    DatasetNameIsInvalid(String),
+    #[error("Passphrase verification command for dataset {0} failed: {1}")]
+    VerifyCmdFailed(String, String),
+}
+
+/// Checks whether the passphrase is correct for the dataset, without loading the key, with `zfs load-key -n`.
+/// This works whether the key is loaded or not.
+/// Returns Ok(false) if the passphrase is wrong, and Err if the check itself failed.
+/// The command `zfs load-key -n <dataset-name>` should be authorized with visudo.
+pub fn zfs_verify_passphrase(
+    dataset_name: impl AsRef<str>,
+    passphrase: impl AsRef<str>,
+) -> Result<bool, ZfsKeyError> {
+    let dataset_name = check_and_sanitize_dataset_name(dataset_name.as_ref())
+        .map_err(|_| ZfsKeyError::DatasetNameIsInvalid(dataset_name.as_ref().to_string()))?;
+
+    let mut cmd = Command::new("sudo");
+    cmd.arg("-n") // sudo isn't interactive
+        .arg("zfs")
+        .arg("load-key")
+        .arg("-n")
+        .arg(&dataset_name);
+
+    let (success, _stdout, stderr) = run_to_completion_with_stdin(cmd, Some(passphrase.as_ref()))
+        .map_err(|e| ZfsKeyError::SystemError(e.to_string()))?;
+
+    if success {
+        Ok(true)
+    } else if stderr.contains(INCORRECT_KEY_MESSAGE) {
+        Ok(false)
+    } else {
+        Err(ZfsKeyError::VerifyCmdFailed(dataset_name, stderr))
+    }
+}
@@ -0,0 +1,124 @@
+use std::process::Command;
+
+use common::types::DatasetMountOptions;
+
+use super::system_command::run_to_completion;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ZfsMountError {
+    #[error("System error: {0}")]
+    SystemError(String),
+    #[error("Mount command for dataset {0} failed: {1}")]
+    MountCmdFailed(String, String),
+    #[error("Dataset name is invalid: {0}")]
+    DatasetNameIsInvalid(String),
+    #[error("Mountpoint must be an absolute path, without whitespace or `..`: {0}")]
+    InvalidMountpoint(String),
+}
+
+/// Similar to pools, the purpose of the sanitization is to prevent injection, not to mimic ZFS specs.
+pub(super) fn check_and_sanitize_dataset_name(
+    dataset_name: impl AsRef<str>,
+) -> Result<String, ZfsMountError> {
+    const ALLOWED_SYMBOLS: [char; 5] = ['-', '_', '.', ':', '/'];
+
+    let dataset_name = dataset_name.as_ref().trim();
+
+    let is_valid = !dataset_name.is_empty()
+        && dataset_name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && dataset_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ALLOWED_SYMBOLS.contains(&c));
+
+    if is_valid {
+        Ok(dataset_name.to_string())
+    } else {
+        Err(ZfsMountError::DatasetNameIsInvalid(
+            dataset_name.to_string(),
+        ))
+    }
+}
+
+pub fn check_mountpoint(mountpoint: impl AsRef<str>) -> Result<String, ZfsMountError> {
+    let mountpoint = mountpoint.as_ref().trim();
+
+    let is_valid = mountpoint.starts_with('/')
+        && !mountpoint.chars().any(char::is_whitespace)
+        && !mountpoint.split('/').any(|part| part == "..");
+
+    if is_valid {
+        Ok(mountpoint.to_string())
+    } else {
+        Err(ZfsMountError::InvalidMountpoint(mountpoint.to_string()))
+    }
+}
+
+/// Mounts a dataset with options that only apply to this mount, without changing the dataset's properties.
+///
+/// With a mountpoint, `mount -t zfs` is used, since `zfs mount` can only mount at the dataset's mountpoint.
+/// The mountpoint directory must exist. The commands `zfs mount -o ro <dataset>` and
+/// `mount -t zfs -o zfsutil[,ro] <dataset> <mountpoint>` should be authorized with visudo, with the options in use.
+pub fn zfs_mount_dataset_with_options(
+    dataset_name: impl AsRef<str>,
+    options: &DatasetMountOptions,
+) -> Result<(), ZfsMountError> {
+    let dataset_name = check_and_sanitize_dataset_name(dataset_name)?;
+    let mountpoint = options
+        .mountpoint
+        .as_ref()
+        .map(check_mountpoint)
+        .transpose()?;
+
+    let mut cmd = Command::new("sudo");
+    cmd.arg("-n"); // sudo isn't interactive
+
+    match mountpoint {
+        Some(mountpoint) => {
+            let mount_options = if options.readonly {
+                "zfsutil,ro"
+            } else {
+                "zfsutil"
+            };
+            cmd.arg("mount")
+                .arg("-t")
+                .arg("zfs")
+                .arg("-o")
+                .arg(mount_options)
+                .arg(&dataset_name)
+                .arg(mountpoint);
+        }
+        None => {
+            cmd.arg("zfs").arg("mount");
+            if options.readonly {
+                cmd.arg("-o").arg("ro");
+            }
+            cmd.arg(&dataset_name);
+        }
+    }
+
+    let (success, _stdout, stderr) =
+        run_to_completion(cmd).map_err(|e| ZfsMountError::SystemError(e.to_string()))?;
+
+    if success {
+        Ok(())
+    } else {
+        Err(ZfsMountError::MountCmdFailed(dataset_name, stderr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_mountpoints() {
+        assert!(check_and_sanitize_dataset_name("tank/docker-data_1.0").is_ok());
+        assert!(check_and_sanitize_dataset_name("tank/docker; rm -rf /").is_err());
+        assert!(check_and_sanitize_dataset_name("-o tank").is_err());
+
+        assert!(check_mountpoint("/mnt/inspect").is_ok());
+        assert!(check_mountpoint("mnt/inspect").is_err());
+        assert!(check_mountpoint("/mnt/../etc").is_err());
+        assert!(check_mountpoint("/mnt/a b").is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use std::process::Command;
+
+use common::types::{ImportablePool, PoolImportOptions};
+
+use super::system_command::run_to_completion;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ZpoolError {
+    #[error("System error: {0}")]
+    SystemError(String),
+    #[error("Command to list importable pools failed: {0}")]
+    ListImportablePoolsCallFailed(String),
+    #[error("Import command for pool {0} failed: {1}")]
+    ImportCmdFailed(String, String),
+    #[error("Pool name is invalid: {0}")]
+    PoolNameIsInvalid(String),
+    #[error("Alternate root for pool import must be an absolute path: {0}")]
+    InvalidAltroot(String),
+}
+
+/// Similar to datasets, the purpose of the sanitization is to prevent injection, not to mimic ZFS specs.
+fn check_and_sanitize_pool_name(pool_name: impl AsRef<str>) -> Result<String, ZpoolError> {
+    const ALLOWED_SYMBOLS: [char; 4] = ['-', '_', '.', ':'];
+
+    let pool_name = pool_name.as_ref().trim();
+
+    let is_valid = !pool_name.is_empty()
+        && pool_name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && pool_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ALLOWED_SYMBOLS.contains(&c));
+
+    if is_valid {
+        Ok(pool_name.to_string())
+    } else {
+        Err(ZpoolError::PoolNameIsInvalid(pool_name.to_string()))
+    }
+}
+
+fn check_altroot(altroot: impl AsRef<str>) -> Result<String, ZpoolError> {
+    let altroot = altroot.as_ref().trim();
+
+    if altroot.starts_with('/') && !altroot.chars().any(char::is_whitespace) {
+        Ok(altroot.to_string())
+    } else {
+        Err(ZpoolError::InvalidAltroot(altroot.to_string()))
+    }
+}
+
+/// Parses the output of `zpool import` (without arguments), which looks like:
+///
+/// ```text
+///    pool: tank
+///      id: 15451357997522795478
+///   state: ONLINE
+///  action: The pool can be imported using its name or numeric identifier.
+///  config:
+///         tank        ONLINE
+/// ```
+fn parse_importable_pools(output: &str) -> Vec<ImportablePool> {
+    let mut result: Vec<ImportablePool> = Vec::new();
+
+    for line in output.lines() {
+        let (key, value) = match line.trim().split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+
+        match key {
+            "pool" => result.push(ImportablePool {
+                pool_name: value.to_string(),
+                pool_id: String::new(),
+                state: String::new(),
+            }),
+            "id" => {
+                if let Some(pool) = result.last_mut() {
+                    pool.pool_id = value.to_string();
+                }
+            }
+            "state" => {
+                if let Some(pool) = result.last_mut() {
+                    pool.state = value.to_string();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    result
+}
+
+/// Lists the pools that can be imported, by scanning the devices.
+/// The command `zpool import` should be authorized with visudo.
+pub fn zpool_list_importable_pools() -> Result<Vec<ImportablePool>, ZpoolError> {
+    let mut cmd = Command::new("sudo");
+    cmd.arg("-n") // sudo isn't interactive
+        .arg("zpool")
+        .arg("import");
+
+    let (success, stdout, stderr) =
+        run_to_completion(cmd).map_err(|e| ZpoolError::SystemError(e.to_string()))?;
+
+    if success {
+        Ok(parse_importable_pools(&stdout))
+    } else if stderr.contains("no pools available to import") {
+        Ok(Vec::new())
+    } else {
+        Err(ZpoolError::ListImportablePoolsCallFailed(stderr))
+    }
+}
+
+/// Imports the pool with the given name. Importing is never forced.
+/// The command `zpool import <pool-name>` should be authorized with visudo, with the options in use.
+pub fn zpool_import_pool(
+    pool_name: impl AsRef<str>,
+    options: &PoolImportOptions,
+) -> Result<(), ZpoolError> {
+    let pool_name = check_and_sanitize_pool_name(pool_name)?;
+    let altroot = options.altroot.as_ref().map(check_altroot).transpose()?;
+
+    let mut cmd = Command::new("sudo");
+    cmd.arg("-n") // sudo isn't interactive
+        .arg("zpool")
+        .arg("import");
+
+    if options.readonly {
+        cmd.arg("-o").arg("readonly=on");
+    }
+
+    if let Some(altroot) = altroot {
+        cmd.arg("-R").arg(altroot);
+    }
+
+    cmd.arg(&pool_name);
+
+    let (success, _stdout, stderr) =
+        run_to_completion(cmd).map_err(|e| ZpoolError::SystemError(e.to_string()))?;
+
+    if success {
+        Ok(())
+    } else {
+        Err(ZpoolError::ImportCmdFailed(pool_name, stderr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_zpool_import_output() {
+        let output = r"   pool: tank
+     id: 15451357997522795478
+  state: ONLINE
+ action: The pool can be imported using its name or numeric identifier.
+ config:
+
+        tank        ONLINE
+          mirror-0  ONLINE
+            sda     ONLINE
+            sdb     ONLINE
+
+   pool: backup
+     id: 9876
+  state: DEGRADED
+ status: One or more devices are missing from the system.
+ action: The pool can be imported despite missing or damaged devices.
+ config:
+
+        backup      DEGRADED
+          sdc       UNAVAIL
+";
+
+        let pools = parse_importable_pools(output);
+        assert_eq!(
+            pools,
+            vec![
+                ImportablePool {
+                    pool_name: "tank".to_string(),
+                    pool_id: "15451357997522795478".to_string(),
+                    state: "ONLINE".to_string(),
+                },
+                ImportablePool {
+                    pool_name: "backup".to_string(),
+                    pool_id: "9876".to_string(),
+                    state: "DEGRADED".to_string(),
+                },
+            ]
+        );
+
+        assert!(parse_importable_pools("").is_empty());
+    }
+
+    #[test]
+    fn pool_names() {
+        check_and_sanitize_pool_name("tank").unwrap();
+        check_and_sanitize_pool_name(" tank.backup-1 ").unwrap();
+
+        check_and_sanitize_pool_name("").unwrap_err();
+        check_and_sanitize_pool_name("-f").unwrap_err();
+        check_and_sanitize_pool_name("1tank").unwrap_err();
+        check_and_sanitize_pool_name("tank/ds").unwrap_err();
+        check_and_sanitize_pool_name("tank pool").unwrap_err();
+
+        check_altroot("/mnt/recovery").unwrap();
+        check_altroot("mnt").unwrap_err();
+        check_altroot("/mnt/some dir").unwrap_err();
+    }
+}
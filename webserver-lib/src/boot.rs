@@ -0,0 +1,269 @@
+use std::{sync::Mutex, time::Duration};
+
+use axum::{extract::State, response::IntoResponse, routing::get, Json, Router};
+use common::types::{
+    BootState, BootStatus, CustomCommandRunOptions, DatasetMountOptions, HookOutput,
+    RunCommandOutput,
+};
+use tokio::sync::Notify;
+
+use crate::{
+    backend::{error::Error, traits::ExecutionBackend},
+    run_options::config::{BootCommand, BootConfig},
+    StateType,
+};
+
+const BOOT_STATUS_ENDPOINT: &str = "/boot-status";
+
+/// Drives the recovery after a reboot, as a state machine:
+///
+/// `Checking` -> `WaitingForUnlock` (notifications are sent once) -> `Mounting` -> `RunningStartupCommands` -> `Ready`
+///
+/// where any failure moves to `Failed`. If no dataset is locked, `WaitingForUnlock` is skipped.
+pub struct BootOrchestrator {
+    config: BootConfig,
+    status: Mutex<BootStatus>,
+    keys_provided: Notify,
+}
+
+impl BootOrchestrator {
+    pub fn new(config: BootConfig) -> Self {
+        let state = if config.enabled {
+            BootState::Checking
+        } else {
+            BootState::Disabled
+        };
+
+        Self {
+            config,
+            status: Mutex::new(BootStatus {
+                state,
+                notifications: Vec::new(),
+                startup_commands: Vec::new(),
+            }),
+            keys_provided: Notify::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn status(&self) -> BootStatus {
+        self.status.lock().expect("Poisoned mutex").clone()
+    }
+
+    /// Wakes up the orchestrator when it's waiting for keys, so that it doesn't wait for the next poll
+    pub fn keys_provided(&self) {
+        self.keys_provided.notify_one();
+    }
+
+    fn set_state(&self, state: BootState) {
+        log::info!("Boot orchestration state: {state:?}");
+        self.status.lock().expect("Poisoned mutex").state = state;
+    }
+
+    pub async fn run<B: ExecutionBackend>(&self, backend: &B) {
+        if let Err(error) = self.drive(backend).await {
+            log::error!("Boot orchestration failed: {error}");
+            self.set_state(BootState::Failed { error });
+        }
+    }
+
+    async fn drive<B: ExecutionBackend>(&self, backend: &B) -> Result<(), String> {
+        self.set_state(BootState::Checking);
+
+        let mut notified = false;
+        loop {
+            let locked_datasets = self.locked_datasets(backend).await?;
+            if locked_datasets.is_empty() {
+                break;
+            }
+
+            self.set_state(BootState::WaitingForUnlock {
+                locked_datasets: locked_datasets.clone(),
+            });
+
+            if !notified {
+                self.send_notifications(backend, &locked_datasets).await;
+                notified = true;
+            }
+
+            tokio::select! {
+                _ = self.keys_provided.notified() => {}
+                _ = tokio::time::sleep(Duration::from_secs(self.config.poll_interval_secs)) => {}
+            }
+        }
+
+        self.set_state(BootState::Mounting);
+        for dataset_name in self.required_datasets(backend).await? {
+            backend
+                .zfs_mount_dataset(&dataset_name, &DatasetMountOptions::default())
+                .await
+                .map_err(|e| format!("Mounting dataset {dataset_name} failed: {e}"))?;
+        }
+
+        self.set_state(BootState::RunningStartupCommands);
+        for cmd in &self.config.startup_commands {
+            let output = run_boot_command(backend, cmd, None).await;
+            let error_code = output.output.error_code;
+            let label = output.label.clone();
+
+            self.status
+                .lock()
+                .expect("Poisoned mutex")
+                .startup_commands
+                .push(output);
+
+            if error_code != 0 {
+                return Err(format!(
+                    "Start-up command `{label}` failed with exit code {error_code}"
+                ));
+            }
+        }
+
+        self.set_state(BootState::Ready);
+
+        Ok(())
+    }
+
+    /// The configured datasets, or all the encrypted datasets if none are configured
+    async fn required_datasets<B: ExecutionBackend>(
+        &self,
+        backend: &B,
+    ) -> Result<Vec<String>, String> {
+        if !self.config.datasets.is_empty() {
+            return Ok(self.config.datasets.clone());
+        }
+
+        let states = backend
+            .zfs_encrypted_datasets_state()
+            .await
+            .map_err(|e| format!("Listing datasets failed: {e}"))?;
+
+        Ok(states.states.into_keys().collect())
+    }
+
+    /// Required datasets that don't exist yet, because their pool isn't imported, count as locked.
+    /// Any other dataset that doesn't exist (e.g., it's misspelled) is an error, as it would never unlock.
+    async fn locked_datasets<B: ExecutionBackend>(
+        &self,
+        backend: &B,
+    ) -> Result<Vec<String>, String> {
+        let states = backend
+            .zfs_encrypted_datasets_state()
+            .await
+            .map_err(|e| format!("Listing datasets failed: {e}"))?
+            .states;
+
+        let required = self.required_datasets(backend).await?;
+
+        let missing = required
+            .iter()
+            .filter(|ds| !states.contains_key(*ds))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            let importable_pools = backend
+                .zfs_importable_pools()
+                .await
+                .map_err(|e| format!("Listing importable pools failed: {e}"))?
+                .pools;
+            let not_found = missing
+                .into_iter()
+                .filter(|ds| {
+                    let pool = ds.split('/').next().unwrap_or_default();
+                    !importable_pools.values().any(|p| p.pool_name == pool)
+                })
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            if !not_found.is_empty() {
+                return Err(format!(
+                    "Encrypted datasets not found, and not in a pool that can be imported: {}",
+                    not_found.join(", ")
+                ));
+            }
+        }
+
+        let locked = required
+            .into_iter()
+            .filter(|ds| !states.get(ds).map(|s| s.key_loaded).unwrap_or(false))
+            .collect();
+
+        Ok(locked)
+    }
+
+    async fn send_notifications<B: ExecutionBackend>(
+        &self,
+        backend: &B,
+        locked_datasets: &[String],
+    ) {
+        let mut message = format!(
+            "The server restarted and is waiting for the keys of the datasets: {}",
+            locked_datasets.join(", ")
+        );
+        if let Some(url) = &self.config.unlock_url {
+            message += &format!("\nUnlock them at: {url}");
+        }
+
+        for cmd in &self.config.notifications {
+            let output = run_boot_command(backend, cmd, Some(message.clone())).await;
+
+            self.status
+                .lock()
+                .expect("Poisoned mutex")
+                .notifications
+                .push(output);
+        }
+    }
+}
+
+async fn run_boot_command<B: ExecutionBackend>(
+    backend: &B,
+    cmd: &BootCommand,
+    stdin: Option<String>,
+) -> HookOutput {
+    let (default_label, result) = match (&cmd.custom_command, &cmd.run_cmd) {
+        (Some(endpoint), _) => {
+            let routable = backend.custom_cmds_routables().get(endpoint);
+            let label = routable
+                .map(|c| c.label.clone())
+                .unwrap_or(endpoint.clone());
+            // The command was configured to run here, which confirms it
+            let options = CustomCommandRunOptions {
+                stdin,
+                confirmation: routable
+                    .and_then(|c| c.confirmation_token())
+                    .map(String::from),
+                ..Default::default()
+            };
+            (label, backend.custom_cmd_call(endpoint, options).await)
+        }
+        (None, Some(run_cmd)) => (
+            run_cmd.as_string(),
+            backend.run_commands_chain(&run_cmd.commands(), stdin).await,
+        ),
+        (None, None) => (String::new(), Ok(RunCommandOutput::default())),
+    };
+
+    let output = result.unwrap_or_else(|e| RunCommandOutput {
+        stdout: String::new(),
+        stderr: e.to_string(),
+        error_code: 253,
+        ..Default::default()
+    });
+
+    HookOutput {
+        label: cmd.label.clone().unwrap_or(default_label),
+        output,
+    }
+}
+
+async fn boot_status<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+) -> Result<impl IntoResponse, Error> {
+    Ok(Json::from(state.boot.status()))
+}
+
+pub fn boot_routes<B: ExecutionBackend>() -> Router<StateType<B>> {
+    Router::new().route(BOOT_STATUS_ENDPOINT, get(boot_status))
+}
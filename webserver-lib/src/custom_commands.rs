@@ -1,34 +1,263 @@
-use std::sync::Arc;
-
-use axum::{extract::State, response::IntoResponse, routing::post, Json, Router};
-use common::types::CustomCommandRunOptions;
-use tokio::sync::Mutex;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        State, WebSocketUpgrade,
+    },
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use common::types::{
+    CommandHistoryEntry, CommandKind, CommandStreamMessage, CustomCommandRunOptions, JobId,
+    JobStartedResponse, RunCommandOutput, ServerEvent, StdinMode,
+};
+use tokio::{sync::mpsc, time::Instant};
 
 use crate::{
-    backend::traits::ExecutionBackend, state::ServerState, StateType, CUSTOM_COMMANDS_DIR,
+    backend::{command_caller::OutputSender, traits::ExecutionBackend},
+    history::RequestCaller,
+    jobs::now_unix_secs,
+    state::ServerState,
+    StateType, CUSTOM_COMMANDS_DIR,
 };
 
 async fn route_handler_from_command<B: ExecutionBackend>(
-    State(state): State<Arc<Mutex<ServerState<B>>>>,
+    State(state): State<StateType<B>>,
+    RequestCaller(caller): RequestCaller,
     json_body: Option<Json<CustomCommandRunOptions>>,
     url_endpoint: String,
 ) -> Result<impl IntoResponse, B::Error> {
-    let state = &*state.lock().await;
-
     let cmd = state
         .backend
         .custom_cmds_routables()
         .get(&url_endpoint)
         .unwrap_or_else(|| panic!("Invariant broken on initialization. URL endpoint is expected to be in the state, but was not found."));
 
-    let stdin = json_body.and_then(|b| b.stdin.clone());
+    let options = json_body.map(|b| b.0).unwrap_or_default();
+
+    let job_id = state.jobs.create(&cmd.url_endpoint);
+    let result = run_as_job(&state, &cmd.url_endpoint, options, &caller, job_id, None).await;
 
-    let result = state
+    Ok(Json::from(result?))
+}
+
+/// Runs the custom command as the given job, which gets its output while it runs and its
+/// result at the end. The output is also sent to `output`, if any.
+/// The run is kept in the history, as one of `caller`.
+pub(crate) async fn run_as_job<B: ExecutionBackend>(
+    state: &StateType<B>,
+    url_endpoint: &str,
+    options: CustomCommandRunOptions,
+    caller: &str,
+    job_id: JobId,
+    output: Option<OutputSender>,
+) -> Result<RunCommandOutput, B::Error> {
+    // Checks of status commands are reported as statuses, and would flood the events and the history
+    let is_status = state
         .backend
-        .custom_cmd_call(&cmd.url_endpoint, stdin)
-        .await?;
+        .custom_cmds_routables()
+        .get(url_endpoint)
+        .is_some_and(|cmd| cmd.kind == CommandKind::Status);
 
-    Ok(Json::from(result))
+    if !is_status {
+        state.events.publish(ServerEvent::CommandStarted {
+            endpoint: url_endpoint.to_string(),
+        });
+    }
+
+    let (output_sender, mut output_receiver) = mpsc::unbounded_channel();
+
+    let started_at_unix_secs = now_unix_secs();
+    let started_at = Instant::now();
+    let mut history_entry = CommandHistoryEntry {
+        started_at_unix_secs,
+        duration_ms: 0,
+        caller: caller.to_string(),
+        stdin: None,
+        stdin_truncated: false,
+        parameters: options.parameters.clone(),
+        error_code: None,
+        error: None,
+        stopped: None,
+        stdout: String::new(),
+        stderr: String::new(),
+        stdout_truncated: false,
+        stderr_truncated: false,
+    };
+    // Passwords are never written anywhere
+    if let Some(cmd) = state.backend.custom_cmds_routables().get(url_endpoint) {
+        // Files can be large, and aren't text
+        if cmd.stdin_allow && !cmd.stdin_is_password && cmd.stdin_mode != StdinMode::File {
+            history_entry.stdin = options.stdin.clone();
+        }
+    }
+
+    let call =
+        state
+            .backend
+            .custom_cmd_call_streaming(url_endpoint, options, job_id, output_sender);
+    tokio::pin!(call);
+
+    let forward = |chunk| {
+        state.jobs.append_output(job_id, &chunk);
+        if let Some(output) = &output {
+            // The receiver is gone when the client disconnected, and the job still goes on
+            let _ = output.send(chunk);
+        }
+    };
+
+    let result = loop {
+        tokio::select! {
+            result = &mut call => break result,
+            Some(chunk) = output_receiver.recv() => forward(chunk),
+        }
+    };
+
+    // The output that was sent right before the command finished
+    while let Ok(chunk) = output_receiver.try_recv() {
+        forward(chunk);
+    }
+
+    state.jobs.finish(job_id, &result);
+    if is_status {
+        return result;
+    }
+    publish_command_result(state, url_endpoint, &result);
+
+    history_entry.duration_ms = started_at.elapsed().as_millis() as u64;
+    match &result {
+        Ok(output) => {
+            history_entry.error_code = Some(output.error_code);
+            history_entry.stopped = output.stopped;
+            history_entry.stdout.clone_from(&output.stdout);
+            history_entry.stderr.clone_from(&output.stderr);
+            history_entry.stdout_truncated = output.stdout_truncated;
+            history_entry.stderr_truncated = output.stderr_truncated;
+        }
+        Err(e) => history_entry.error = Some(e.to_string()),
+    }
+    state.history.record(url_endpoint, history_entry).await;
+
+    result
+}
+
+async fn start_handler_from_command<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+    RequestCaller(caller): RequestCaller,
+    json_body: Option<Json<CustomCommandRunOptions>>,
+    url_endpoint: String,
+) -> impl IntoResponse {
+    let options = json_body.map(|b| b.0).unwrap_or_default();
+
+    let job_id = state.jobs.create(&url_endpoint);
+    tokio::spawn(async move {
+        // The result is kept in the job, and published as an event
+        let _ = run_as_job(&state, &url_endpoint, options, &caller, job_id, None).await;
+    });
+
+    Json::from(JobStartedResponse { job_id })
+}
+
+fn publish_command_result<B: ExecutionBackend>(
+    state: &StateType<B>,
+    url_endpoint: &str,
+    result: &Result<RunCommandOutput, B::Error>,
+) {
+    state.events.publish(match result {
+        Ok(output) => ServerEvent::CommandFinished {
+            endpoint: url_endpoint.to_string(),
+            output: output.clone(),
+        },
+        Err(e) => ServerEvent::CommandFailed {
+            endpoint: url_endpoint.to_string(),
+            error: e.to_string(),
+        },
+    });
+}
+
+async fn stream_handler_from_command<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+    RequestCaller(caller): RequestCaller,
+    ws: WebSocketUpgrade,
+    url_endpoint: String,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| stream_command(state, socket, url_endpoint, caller))
+}
+
+async fn send_stream_message(socket: &mut WebSocket, message: &CommandStreamMessage) {
+    match serde_json::to_string(message) {
+        // Sending fails when the client is gone, and then there's nobody to tell
+        Ok(json) => {
+            let _ = socket.send(Message::Text(json)).await;
+        }
+        Err(e) => log::error!("Failed to serialize command stream message: {e}"),
+    }
+}
+
+/// Runs a custom command with the options in the first message of the socket, and sends
+/// the output while it runs. A client that disconnects doesn't stop the command.
+async fn stream_command<B: ExecutionBackend>(
+    state: StateType<B>,
+    mut socket: WebSocket,
+    url_endpoint: String,
+    caller: String,
+) {
+    let options = match socket.recv().await {
+        Some(Ok(Message::Text(text))) => serde_json::from_str::<CustomCommandRunOptions>(&text),
+        _ => return,
+    };
+    let options = match options {
+        Ok(options) => options,
+        Err(e) => {
+            let message = CommandStreamMessage::Failed {
+                status: StatusCode::BAD_REQUEST.as_u16(),
+                error: format!("Invalid run options: {e}"),
+            };
+            send_stream_message(&mut socket, &message).await;
+            return;
+        }
+    };
+
+    let (output_sender, mut output_receiver) = mpsc::unbounded_channel();
+
+    let job_id = state.jobs.create(&url_endpoint);
+    send_stream_message(&mut socket, &CommandStreamMessage::Started { job_id }).await;
+
+    let call = run_as_job(
+        &state,
+        &url_endpoint,
+        options,
+        &caller,
+        job_id,
+        Some(output_sender),
+    );
+    tokio::pin!(call);
+
+    let result = loop {
+        tokio::select! {
+            result = &mut call => break result,
+            Some(chunk) = output_receiver.recv() => {
+                send_stream_message(&mut socket, &CommandStreamMessage::Output(chunk)).await;
+            }
+        }
+    };
+
+    // The output that was sent right before the command finished
+    while let Ok(chunk) = output_receiver.try_recv() {
+        send_stream_message(&mut socket, &CommandStreamMessage::Output(chunk)).await;
+    }
+
+    let message = match result {
+        Ok(output) => CommandStreamMessage::Finished { output },
+        Err(e) => CommandStreamMessage::Failed {
+            error: e.to_string(),
+            status: e.into_response().status().as_u16(),
+        },
+    };
+    send_stream_message(&mut socket, &message).await;
+
+    let _ = socket.send(Message::Close(None)).await;
 }
 
 fn route_from_command<B: ExecutionBackend>(
@@ -37,17 +266,49 @@ fn route_from_command<B: ExecutionBackend>(
 ) -> Router<StateType<B>> {
     let url_endpoint = url_endpoint.into();
 
-    router.route(
-        &format!("/{}", url_endpoint),
-        post(move |state, json| route_handler_from_command(state, json, url_endpoint)),
-    )
+    let stream_url_endpoint = url_endpoint.clone();
+    let start_url_endpoint = url_endpoint.clone();
+
+    router
+        .route(
+            &format!("/{}", url_endpoint),
+            post(move |state, caller, json| {
+                route_handler_from_command(state, caller, json, url_endpoint)
+            }),
+        )
+        .route(
+            &format!("/{}/stream", stream_url_endpoint),
+            get(move |state, caller, ws| {
+                stream_handler_from_command(state, caller, ws, stream_url_endpoint)
+            }),
+        )
+        .route(
+            &format!("/{}/start", start_url_endpoint),
+            post(move |state, caller, json| {
+                start_handler_from_command(state, caller, json, start_url_endpoint)
+            }),
+        )
+}
+
+/// Publishes the commands that are busy because of the concurrency limits, whenever they change
+pub async fn watch_busy_commands<B: ExecutionBackend>(state: StateType<B>) {
+    let mut changes = state.backend.custom_cmds_busy_changes();
+    let mut previous = state.backend.custom_cmds_busy();
+
+    while changes.changed().await.is_ok() {
+        let busy = state.backend.custom_cmds_busy();
+        if busy != previous {
+            state.events.publish(ServerEvent::CommandsBusy {
+                endpoints: busy.clone(),
+            });
+            previous = busy;
+        }
+    }
 }
 
 pub async fn custom_commands_list_route_handler<B: ExecutionBackend>(
     State(state): State<StateType<B>>,
 ) -> Result<impl IntoResponse, B::Error> {
-    let state = &*state.lock().await;
-
     let result = state.backend.custom_cmds_list()?;
 
     Ok(Json::from(result))
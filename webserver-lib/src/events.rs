@@ -0,0 +1,179 @@
+use std::{collections::BTreeMap, convert::Infallible, time::Duration};
+
+use axum::{
+    extract::State,
+    response::{
+        sse::{Event, KeepAlive},
+        Sse,
+    },
+    routing::get,
+    Router,
+};
+use common::types::{DatasetFullMountState, ServerEvent};
+use futures::Stream;
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    Notify,
+};
+
+use crate::{backend::traits::ExecutionBackend, StateType};
+
+const EVENTS_ENDPOINT: &str = "/events";
+
+/// Events that are not consumed by a slow client are dropped for it after this many newer events
+const EVENTS_CAPACITY: usize = 256;
+
+/// Broadcasts server events to all the clients that listen to the events endpoint
+pub struct EventBus {
+    sender: broadcast::Sender<ServerEvent>,
+    datasets_refresh: Notify,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _receiver) = broadcast::channel(EVENTS_CAPACITY);
+
+        Self {
+            sender,
+            datasets_refresh: Notify::new(),
+        }
+    }
+
+    pub fn publish(&self, event: ServerEvent) {
+        // Sending fails only when nobody is listening, which is fine
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn subscribers_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Makes the dataset watcher check the datasets now, instead of waiting for the next poll.
+    /// Called after operations that change the state of datasets.
+    pub fn datasets_may_have_changed(&self) {
+        self.datasets_refresh.notify_one();
+    }
+}
+
+fn dataset_changes(
+    previous: &BTreeMap<String, DatasetFullMountState>,
+    current: &BTreeMap<String, DatasetFullMountState>,
+) -> Vec<ServerEvent> {
+    let changed = current
+        .values()
+        .filter(|state| previous.get(&state.dataset_name) != Some(state))
+        .map(|state| ServerEvent::DatasetChanged {
+            state: state.clone(),
+        });
+
+    let removed = previous
+        .keys()
+        .filter(|ds| !current.contains_key(*ds))
+        .map(|ds| ServerEvent::DatasetRemoved {
+            dataset_name: ds.to_string(),
+        });
+
+    changed.chain(removed).collect()
+}
+
+/// Polls the datasets state, and publishes the changes. Datasets are only polled while there are
+/// clients listening, so that ZFS isn't called for nothing. When a client joins, the state of all
+/// the datasets is published, so that it starts with the full picture.
+pub async fn watch_datasets<B: ExecutionBackend>(state: StateType<B>, interval: Duration) {
+    let mut previous = BTreeMap::new();
+    let mut known_subscribers_count = 0;
+
+    loop {
+        let subscribers_count = state.events.subscribers_count();
+        if subscribers_count > known_subscribers_count {
+            previous.clear();
+        }
+        known_subscribers_count = subscribers_count;
+
+        if subscribers_count > 0 {
+            match state.backend.zfs_encrypted_datasets_state().await {
+                Ok(current) => {
+                    for event in dataset_changes(&previous, &current.states) {
+                        state.events.publish(event);
+                    }
+                    previous = current.states;
+                }
+                Err(e) => log::warn!("Dataset watcher failed to retrieve the datasets: {e}"),
+            }
+        }
+
+        tokio::select! {
+            _ = state.events.datasets_refresh.notified() => {}
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+async fn events<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.events.subscribe();
+    state.events.datasets_may_have_changed();
+
+    let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => match Event::default().json_data(&event) {
+                    Ok(sse_event) => return Some((Ok(sse_event), receiver)),
+                    Err(e) => log::error!("Failed to serialize server event: {e}"),
+                },
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("Events client is too slow, and missed {skipped} events");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+pub fn events_routes<B: ExecutionBackend>() -> Router<StateType<B>> {
+    Router::new().route(EVENTS_ENDPOINT, get(events))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(name: &str, key_loaded: bool, is_mounted: bool) -> DatasetFullMountState {
+        DatasetFullMountState {
+            dataset_name: name.to_string(),
+            key_loaded,
+            is_mounted,
+        }
+    }
+
+    #[test]
+    fn changes_between_snapshots() {
+        let previous = [state("a", false, false), state("b", true, false)]
+            .into_iter()
+            .map(|s| (s.dataset_name.clone(), s))
+            .collect();
+        let current = [state("a", false, false), state("c", true, true)]
+            .into_iter()
+            .map(|s| (s.dataset_name.clone(), s))
+            .collect();
+
+        assert_eq!(
+            dataset_changes(&previous, &current),
+            vec![
+                ServerEvent::DatasetChanged {
+                    state: state("c", true, true)
+                },
+                ServerEvent::DatasetRemoved {
+                    dataset_name: "b".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    path::{Path as FsPath, PathBuf},
+    sync::Mutex,
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Path, State},
+    http::request::Parts,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use common::types::{CommandHistory, CommandHistoryEntry};
+
+use crate::{
+    backend::{error::Error, traits::ExecutionBackend},
+    run_options::config::CustomCommandsConfig,
+    StateType,
+};
+
+const HISTORY_DIR: &str = "/history";
+
+/// Who sent the request: the address of the connection, unless it's one of the trusted proxies.
+/// Then, it's the nearest address in `X-Forwarded-For` that isn't a trusted proxy, as any client
+/// can send the header, and only the proxies are trusted to add to it.
+pub struct RequestCaller(pub String);
+
+fn find_caller(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    trusted_proxies: &[IpAddr],
+) -> String {
+    let Some(peer) = peer else {
+        return "unknown".to_string();
+    };
+
+    let mut caller = peer.to_string();
+    if !trusted_proxies.contains(&peer) {
+        return caller;
+    }
+
+    // Every proxy appends the address it got the request from
+    let forwarded = forwarded_for
+        .unwrap_or_default()
+        .rsplit(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty());
+    for address in forwarded {
+        caller = address.to_string();
+        match address.parse::<IpAddr>() {
+            Ok(ip) if trusted_proxies.contains(&ip) => continue,
+            _ => break,
+        }
+    }
+
+    caller
+}
+
+#[async_trait]
+impl<B: ExecutionBackend> FromRequestParts<StateType<B>> for RequestCaller {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &StateType<B>,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip());
+        let forwarded_for = parts
+            .headers
+            .get("X-Forwarded-For")
+            .and_then(|h| h.to_str().ok());
+
+        Ok(Self(find_caller(
+            peer,
+            forwarded_for,
+            &state.custom_commands_config.trusted_proxies,
+        )))
+    }
+}
+
+/// The last runs of every custom command, which are written to a file, if one is configured,
+/// so that they survive restarts
+pub struct History {
+    path: Option<PathBuf>,
+    max_runs: usize,
+    max_output_bytes: usize,
+    runs: Mutex<BTreeMap<String, Vec<CommandHistoryEntry>>>,
+    /// Makes the writes of the file happen one at a time, in the order of the changes
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+/// Cuts the string to at most `max_bytes`, at a character boundary, and returns whether it was cut
+fn truncate_at_char_boundary(s: &mut String, max_bytes: usize) -> bool {
+    if s.len() <= max_bytes {
+        return false;
+    }
+
+    let end = (0..=max_bytes)
+        .rev()
+        .find(|i| s.is_char_boundary(*i))
+        .unwrap_or_default();
+    s.truncate(end);
+
+    true
+}
+
+fn load_runs(path: &FsPath) -> BTreeMap<String, Vec<CommandHistoryEntry>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        // Nothing ran yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return BTreeMap::new(),
+        Err(e) => {
+            log::error!("Failed to read history file `{}`: {e}", path.display());
+            return BTreeMap::new();
+        }
+    };
+
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        log::error!("Failed to parse history file `{}`: {e}", path.display());
+        BTreeMap::new()
+    })
+}
+
+/// Writes to a temporary file first, so that a crash never leaves a partial history
+async fn write_atomically(path: &FsPath, content: &str) -> std::io::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    tokio::fs::write(&temp_path, content).await?;
+    tokio::fs::rename(&temp_path, path).await
+}
+
+impl History {
+    pub fn new(config: &CustomCommandsConfig) -> Self {
+        let path = config.history_path.as_ref().map(PathBuf::from);
+        let runs = path.as_deref().map(load_runs).unwrap_or_default();
+
+        Self {
+            path,
+            max_runs: config.history_max_runs,
+            max_output_bytes: config.history_max_output_bytes,
+            runs: Mutex::new(runs),
+            write_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Adds the run to the history of the command, and writes the history to its file
+    pub async fn record(&self, endpoint: &str, mut entry: CommandHistoryEntry) {
+        if let Some(stdin) = &mut entry.stdin {
+            entry.stdin_truncated |= truncate_at_char_boundary(stdin, self.max_output_bytes);
+        }
+        entry.stdout_truncated |=
+            truncate_at_char_boundary(&mut entry.stdout, self.max_output_bytes);
+        entry.stderr_truncated |=
+            truncate_at_char_boundary(&mut entry.stderr, self.max_output_bytes);
+
+        let _write_guard = self.write_lock.lock().await;
+
+        let snapshot = {
+            let mut all_runs = self.runs.lock().expect("Poisoned mutex");
+            let runs = all_runs.entry(endpoint.to_string()).or_default();
+            runs.insert(0, entry);
+            runs.truncate(self.max_runs);
+
+            serde_json::to_string(&*all_runs)
+        };
+
+        let Some(path) = &self.path else {
+            return;
+        };
+        let written = match snapshot {
+            Ok(snapshot) => write_atomically(path, &snapshot).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = written {
+            log::error!("Failed to write history file `{}`: {e}", path.display());
+        }
+    }
+
+    pub fn history(&self, endpoint: &str) -> CommandHistory {
+        CommandHistory {
+            endpoint: endpoint.to_string(),
+            runs: self
+                .runs
+                .lock()
+                .expect("Poisoned mutex")
+                .get(endpoint)
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+async fn command_history<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+    Path(endpoint): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    if !state
+        .backend
+        .custom_cmds_routables()
+        .contains_key(&endpoint)
+    {
+        return Err(Error::CustomCommandNotFound(endpoint));
+    }
+
+    Ok(Json::from(state.history.history(&endpoint)))
+}
+
+pub fn history_routes<B: ExecutionBackend>() -> Router<StateType<B>> {
+    Router::new().route(&format!("{HISTORY_DIR}/:endpoint"), get(command_history))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarded_addresses_are_only_trusted_from_proxies() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let client: IpAddr = "192.168.1.5".parse().unwrap();
+
+        assert_eq!(find_caller(None, Some("1.2.3.4"), &[proxy]), "unknown");
+        assert_eq!(
+            find_caller(Some(client), Some("1.2.3.4"), &[proxy]),
+            "192.168.1.5"
+        );
+        assert_eq!(find_caller(Some(proxy), Some("1.2.3.4"), &[]), "10.0.0.1");
+        assert_eq!(
+            find_caller(Some(proxy), Some("1.2.3.4, 192.168.1.5"), &[proxy]),
+            "192.168.1.5"
+        );
+        assert_eq!(
+            find_caller(Some(proxy), Some("192.168.1.5, 10.0.0.1"), &[proxy]),
+            "192.168.1.5"
+        );
+        assert_eq!(find_caller(Some(proxy), None, &[proxy]), "10.0.0.1");
+    }
+
+    #[tokio::test]
+    async fn stdin_is_truncated_like_the_outputs() {
+        let config = CustomCommandsConfig {
+            history_max_output_bytes: 4,
+            ..Default::default()
+        };
+        let history = History::new(&config);
+
+        let entry = CommandHistoryEntry {
+            started_at_unix_secs: 0,
+            duration_ms: 0,
+            caller: "unknown".to_string(),
+            stdin: Some("abcdef".to_string()),
+            stdin_truncated: false,
+            parameters: BTreeMap::new(),
+            error_code: Some(0),
+            error: None,
+            stopped: None,
+            stdout: "ab".to_string(),
+            stderr: String::new(),
+            stdout_truncated: false,
+            stderr_truncated: false,
+        };
+        history.record("echo", entry).await;
+
+        let runs = history.history("echo").runs;
+        assert_eq!(runs[0].stdin.as_deref(), Some("abcd"));
+        assert!(runs[0].stdin_truncated);
+        assert_eq!(runs[0].stdout, "ab");
+        assert!(!runs[0].stdout_truncated);
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::BTreeMap,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use common::types::{
+    CommandOutputChunk, JobCancelledResponse, JobId, JobOutput, JobState, JobStatus, JobsList,
+    OutputStream, RunCommandOutput,
+};
+
+use crate::{
+    backend::{error::Error, traits::ExecutionBackend},
+    StateType,
+};
+
+const JOBS_DIR: &str = "/jobs";
+
+/// Finished jobs beyond this count are removed, oldest first, even before their retention ends
+const MAX_FINISHED_JOBS: usize = 1000;
+
+pub(crate) fn now_unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Default)]
+struct JobsInner {
+    next_job_id: JobId,
+    jobs: BTreeMap<JobId, JobOutput>,
+}
+
+impl JobsInner {
+    /// Removes the finished jobs that are past the retention, and the oldest ones beyond the limit
+    fn prune(&mut self, retention: Duration) {
+        let now = now_unix_secs();
+        self.jobs
+            .retain(|_, job| match job.status.finished_at_unix_secs {
+                Some(finished_at) => finished_at + retention.as_secs() >= now,
+                None => true,
+            });
+
+        let finished = self
+            .jobs
+            .values()
+            .filter(|j| j.status.state != JobState::Running)
+            .map(|j| j.status.job_id)
+            .collect::<Vec<_>>();
+        for job_id in finished
+            .iter()
+            .take(finished.len().saturating_sub(MAX_FINISHED_JOBS))
+        {
+            self.jobs.remove(job_id);
+        }
+    }
+}
+
+/// Every call of a custom command is recorded as a job, with its output while it runs,
+/// and its result is kept for the retention period after it finishes
+pub struct Jobs {
+    inner: Mutex<JobsInner>,
+    retention: Duration,
+}
+
+impl Jobs {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            inner: Mutex::new(JobsInner {
+                next_job_id: 1,
+                jobs: BTreeMap::new(),
+            }),
+            retention,
+        }
+    }
+
+    pub fn create(&self, endpoint: &str) -> JobId {
+        let mut inner = self.inner.lock().expect("Poisoned mutex");
+        inner.prune(self.retention);
+
+        let job_id = inner.next_job_id;
+        inner.next_job_id += 1;
+
+        inner.jobs.insert(
+            job_id,
+            JobOutput {
+                status: JobStatus {
+                    job_id,
+                    endpoint: endpoint.to_string(),
+                    state: JobState::Running,
+                    started_at_unix_secs: now_unix_secs(),
+                    finished_at_unix_secs: None,
+                    error: None,
+                },
+                output: RunCommandOutput::default(),
+            },
+        );
+
+        job_id
+    }
+
+    pub fn append_output(&self, job_id: JobId, chunk: &CommandOutputChunk) {
+        let mut inner = self.inner.lock().expect("Poisoned mutex");
+
+        if let Some(job) = inner.jobs.get_mut(&job_id) {
+            match chunk.stream {
+                OutputStream::Stdout => job.output.stdout.push_str(&chunk.data),
+                OutputStream::Stderr => job.output.stderr.push_str(&chunk.data),
+            }
+        }
+    }
+
+    pub fn finish<E: std::error::Error>(
+        &self,
+        job_id: JobId,
+        result: &Result<RunCommandOutput, E>,
+    ) {
+        let mut inner = self.inner.lock().expect("Poisoned mutex");
+
+        if let Some(job) = inner.jobs.get_mut(&job_id) {
+            job.status.finished_at_unix_secs = Some(now_unix_secs());
+            match result {
+                Ok(output) => {
+                    job.status.state = JobState::Finished;
+                    job.output = output.clone();
+                }
+                Err(e) => {
+                    job.status.state = JobState::Failed;
+                    job.status.error = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    pub fn output(&self, job_id: JobId) -> Option<JobOutput> {
+        self.inner
+            .lock()
+            .expect("Poisoned mutex")
+            .jobs
+            .get(&job_id)
+            .cloned()
+    }
+
+    pub fn list(&self) -> JobsList {
+        let mut inner = self.inner.lock().expect("Poisoned mutex");
+        inner.prune(self.retention);
+
+        JobsList {
+            jobs: inner
+                .jobs
+                .values()
+                .rev()
+                .map(|j| j.status.clone())
+                .collect(),
+        }
+    }
+}
+
+async fn jobs_list<B: ExecutionBackend>(State(state): State<StateType<B>>) -> impl IntoResponse {
+    Json::from(state.jobs.list())
+}
+
+async fn job_status<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+    Path(job_id): Path<JobId>,
+) -> Result<impl IntoResponse, Error> {
+    let job = state
+        .jobs
+        .output(job_id)
+        .ok_or(Error::JobNotFound(job_id))?;
+
+    Ok(Json::from(job.status))
+}
+
+async fn job_output<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+    Path(job_id): Path<JobId>,
+) -> Result<impl IntoResponse, Error> {
+    let job = state
+        .jobs
+        .output(job_id)
+        .ok_or(Error::JobNotFound(job_id))?;
+
+    Ok(Json::from(job))
+}
+
+/// Stops the run of the job, without affecting the other runs of its command
+async fn job_cancel<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+    Path(job_id): Path<JobId>,
+) -> Result<impl IntoResponse, Error> {
+    if state.jobs.output(job_id).is_none() {
+        return Err(Error::JobNotFound(job_id));
+    }
+
+    Ok(Json::from(JobCancelledResponse {
+        job_id,
+        cancelled: state.backend.custom_cmd_cancel_job(job_id),
+    }))
+}
+
+pub fn jobs_routes<B: ExecutionBackend>() -> Router<StateType<B>> {
+    let inner_routes = Router::new()
+        .route("/", get(jobs_list))
+        .route("/:job_id", get(job_status))
+        .route("/:job_id/output", get(job_output))
+        .route("/:job_id/cancel", post(job_cancel));
+
+    Router::new().nest(JOBS_DIR, inner_routes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finished_jobs_are_pruned_after_retention() {
+        let jobs = Jobs::new(Duration::ZERO);
+
+        let running = jobs.create("a");
+        let finished = jobs.create("b");
+        jobs.finish::<Error>(finished, &Ok(RunCommandOutput::default()));
+
+        // Finishing sets the time to now, so it's only past the zero retention a second later
+        std::thread::sleep(Duration::from_millis(1100));
+
+        let ids = jobs
+            .list()
+            .jobs
+            .into_iter()
+            .map(|j| j.job_id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![running]);
+    }
+}
@@ -1,29 +1,47 @@
 mod backend;
+mod boot;
 mod custom_commands;
+mod events;
+mod history;
+mod jobs;
 pub mod run_options;
+mod scheduler;
 pub mod state;
+mod statuses;
+#[cfg(test)]
+mod tests;
 mod zfs;
 
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::{
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    middleware::AddExtension,
     response::IntoResponse,
-    routing::{get, IntoMakeService},
+    routing::get,
     serve::Serve,
     Json, Router,
 };
 use backend::error::Error;
 use backend::{live::LiveExecutionBackend, traits::ExecutionBackend};
+use boot::boot_routes;
 use common::types::HelloResponse;
-use custom_commands::{custom_commands_list_route_handler, make_custom_commands_routes};
+use custom_commands::{
+    custom_commands_list_route_handler, make_custom_commands_routes, watch_busy_commands,
+};
+use events::{events_routes, watch_datasets};
+use history::history_routes;
 use hyper::{Method, StatusCode};
+use jobs::jobs_routes;
 use run_options::{config::ApiServerConfig, server_run_options::ServerRunOptions};
+use scheduler::{scheduler_routes, start_schedules};
 use state::ServerState;
-use tokio::{net::TcpListener, sync::Mutex};
+use statuses::{start_status_checks, statuses_routes};
+use tokio::net::TcpListener;
 use tower_http_axum::cors::{AllowMethods, CorsLayer};
 use zfs::zfs_routes;
 
-type StateType<B> = Arc<Mutex<ServerState<B>>>;
+type StateType<B> = Arc<ServerState<B>>;
 
 const ZFS_DIR: &str = "/zfs";
 const CUSTOM_COMMANDS_DIR: &str = "/custom-commands";
@@ -37,39 +55,67 @@ async fn hello() -> Result<impl IntoResponse, Error> {
     Ok(Json::from(HelloResponse::default()))
 }
 
-fn web_server<B: ExecutionBackend>(
-    socket: TcpListener,
-    config: Option<ApiServerConfig>,
-    backend: B,
-) -> Serve<IntoMakeService<Router>, Router> {
+fn make_router<B: ExecutionBackend>(config: Option<ApiServerConfig>, backend: B) -> Router {
     let cors_layer = CorsLayer::new()
         .allow_methods(AllowMethods::list([Method::GET, Method::POST]))
         .allow_headers(tower_http_axum::cors::Any)
         .allow_origin(tower_http_axum::cors::Any);
 
-    let (zfs_config, custom_cmds_config) = config
-        .map(|c| (c.zfs_config, c.custom_commands_config))
+    let (zfs_config, custom_cmds_config, boot_config) = config
+        .map(|c| (c.zfs_config, c.custom_commands_config, c.boot))
         .unwrap_or_default();
 
-    let state = ServerState::new(zfs_config, custom_cmds_config.clone(), backend);
+    let state = ServerState::new(zfs_config, custom_cmds_config.clone(), boot_config, backend);
 
     let custom_cmds_routes = make_custom_commands_routes(&state).route(
         CUSTOM_COMMANDS_LIST_ENDPOINT,
         get(custom_commands_list_route_handler),
     );
 
-    let state = Arc::new(Mutex::new(state));
+    let state = Arc::new(state);
+
+    let dataset_watch_interval = Duration::from_secs(state.zfs_config.dataset_watch_interval_secs);
+    tokio::spawn(watch_datasets(state.clone(), dataset_watch_interval));
+    tokio::spawn(watch_busy_commands(state.clone()));
+    start_schedules(&state);
+    start_status_checks(&state);
+
+    if state.boot.enabled() {
+        let state = state.clone();
+        tokio::spawn(async move { state.boot.run(&state.backend).await });
+    }
 
-    let routes = Router::new()
+    Router::new()
         .route("/hello", get(hello))
         .merge(zfs_routes())
+        .merge(boot_routes())
+        .merge(events_routes())
+        .merge(jobs_routes())
+        .merge(history_routes())
+        .merge(scheduler_routes())
+        .merge(statuses_routes())
         .merge(custom_cmds_routes)
         .with_state(state)
         .layer(cors_layer)
         .layer(tower_http_axum::trace::TraceLayer::new_for_http())
-        .fallback(handler_404);
+        .fallback(handler_404)
+}
 
-    axum::serve(socket, routes.into_make_service())
+fn web_server<B: ExecutionBackend>(
+    socket: TcpListener,
+    config: Option<ApiServerConfig>,
+    backend: B,
+) -> Serve<
+    IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    AddExtension<Router, ConnectInfo<SocketAddr>>,
+> {
+    let routes = make_router(config, backend);
+
+    // The address of the client is kept in the history of the commands it runs
+    axum::serve(
+        socket,
+        routes.into_make_service_with_connect_info::<SocketAddr>(),
+    )
 }
 
 pub async fn start_server(options: ServerRunOptions) -> Result<(), Box<dyn std::error::Error>> {
@@ -1,7 +1,20 @@
-use std::{collections::BTreeSet, path::Path, str::FromStr};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::IpAddr,
+    path::Path,
+    str::FromStr,
+};
 
+use chrono::{DateTime, TimeZone};
+use common::types::{
+    CommandConfirmation, CommandKind, CommandParameter, OutputEncoding, OutputFormat,
+    RunCommandOutput, StdinMode, DEFAULT_STDIN_MAX_BYTES,
+};
+use regex::Regex;
 use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::backend::command_parameters::ParameterSet;
+
 #[must_use]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -11,6 +24,9 @@ pub struct ApiServerConfig {
 
     #[serde(flatten)]
     pub zfs_config: ZfsConfig,
+
+    #[serde(default)]
+    pub boot: BootConfig,
 }
 
 impl ApiServerConfig {
@@ -31,10 +47,94 @@ impl FromStr for ApiServerConfig {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let config: ApiServerConfig = toml::from_str(s)?;
+        config.validate_custom_command_references()?;
+        config.validate_boot_datasets()?;
         Ok(config)
     }
 }
 
+impl ApiServerConfig {
+    /// The boot orchestration can't see blacklisted datasets, so it would wait for them forever
+    fn validate_boot_datasets(&self) -> Result<(), String> {
+        let blacklisted = self
+            .zfs_config
+            .blacklisted_zfs_datasets
+            .as_deref()
+            .unwrap_or_default();
+
+        match self
+            .boot
+            .datasets
+            .iter()
+            .find(|ds| blacklisted.contains(ds))
+        {
+            Some(ds) => Err(format!(
+                "Failed to load config. The boot dataset `{ds}` is blacklisted"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Hooks and boot commands that reference custom commands must point to enabled commands
+    /// with an explicit `url_endpoint`, and with defaults for all their parameters, since there's
+    /// nobody to provide them
+    fn validate_custom_command_references(&self) -> Result<(), String> {
+        let endpoints = self
+            .custom_commands()
+            .unwrap_or_default()
+            .iter()
+            .filter(|cmd| cmd.enabled)
+            .filter(|cmd| ParameterSet::new(&cmd.parameters).is_ok_and(|p| p.all_have_defaults()))
+            .filter_map(|cmd| cmd.url_endpoint.as_deref())
+            .collect::<BTreeSet<_>>();
+
+        for hook in self.zfs_config.all_hooks() {
+            if let Some(endpoint) = &hook.custom_command {
+                if !endpoints.contains(endpoint.as_str()) {
+                    return Err(format!(
+                        "Failed to load config. Hook for dataset `{}` references the custom command `{}`, which was not found among the enabled custom commands",
+                        hook.dataset, endpoint
+                    ));
+                }
+            }
+        }
+
+        for cmd in self.boot.all_commands() {
+            if let Some(endpoint) = &cmd.custom_command {
+                if !endpoints.contains(endpoint.as_str()) {
+                    return Err(format!(
+                        "Failed to load config. A boot command references the custom command `{}`, which was not found among the enabled custom commands",
+                        endpoint
+                    ));
+                }
+            }
+        }
+
+        // Notifications get the message in their stdin
+        let stdin_endpoints = self
+            .custom_commands()
+            .unwrap_or_default()
+            .iter()
+            .filter(|cmd| cmd.stdin_allow)
+            .filter_map(|cmd| cmd.url_endpoint.as_deref())
+            .collect::<BTreeSet<_>>();
+        for endpoint in self
+            .boot
+            .notifications
+            .iter()
+            .filter_map(|cmd| cmd.custom_command.as_deref())
+        {
+            if !stdin_endpoints.contains(endpoint) {
+                return Err(format!(
+                    "Failed to load config. A notification references the custom command `{endpoint}`, which doesn't take stdin, so it can't get the message"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[must_use]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -45,6 +145,69 @@ pub struct CustomCommandsConfig {
         rename = "custom_command"
     )]
     pub custom_commands: Option<Vec<CustomCommand>>,
+
+    #[serde(default, deserialize_with = "validate_timeout_secs")]
+    /// The time, in seconds, after which custom commands are killed, unless they have their own
+    /// `timeout_secs`, and so are the commands of hooks, boot commands and notifications.
+    /// If not set, commands can run forever.
+    pub default_command_timeout_secs: Option<u64>,
+
+    #[serde(default = "default_job_retention_secs")]
+    /// How long, in seconds, the results of finished commands are kept, to be retrieved later
+    pub job_retention_secs: u64,
+
+    #[serde(default, deserialize_with = "validate_max_concurrent")]
+    /// How many custom commands can run at the same time, in total. If not set, there's no limit.
+    pub max_concurrent_commands: Option<usize>,
+
+    #[serde(default)]
+    /// What happens to a command that is called while it can't run because of the limits
+    pub when_busy: WhenBusy,
+
+    #[serde(
+        default = "default_max_output_bytes",
+        deserialize_with = "validate_max_output_bytes"
+    )]
+    /// The most bytes that are kept of each output (stdout and stderr) of every command,
+    /// unless the command has its own `max_output_bytes`. The rest is dropped, but in a chain,
+    /// the next command still gets the whole stdout.
+    pub max_output_bytes: usize,
+
+    #[serde(default)]
+    /// The file where the last runs of every command are kept, so that they survive restarts.
+    /// If not set, the history is only kept until the server stops.
+    pub history_path: Option<String>,
+
+    #[serde(
+        default = "default_history_max_runs",
+        deserialize_with = "validate_history_max_runs"
+    )]
+    /// How many of the last runs of every command are kept in the history
+    pub history_max_runs: usize,
+
+    #[serde(
+        default = "default_history_max_output_bytes",
+        deserialize_with = "validate_max_output_bytes"
+    )]
+    /// The most bytes of each output that are kept in the history, which is smaller than
+    /// `max_output_bytes` to keep the history file small
+    pub history_max_output_bytes: usize,
+
+    #[serde(default)]
+    /// The addresses of the reverse proxies in front of the server. Only their `X-Forwarded-For`
+    /// is used for the callers in the history; otherwise, the address of the connection is.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+/// What to do with a call of a custom command that would go beyond the concurrency limits
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WhenBusy {
+    /// The call fails immediately with a conflict error
+    #[default]
+    Reject,
+    /// The call waits until the command can run
+    Queue,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -52,6 +215,15 @@ impl Default for CustomCommandsConfig {
     fn default() -> Self {
         Self {
             custom_commands: None,
+            default_command_timeout_secs: None,
+            job_retention_secs: default_job_retention_secs(),
+            max_concurrent_commands: None,
+            when_busy: WhenBusy::default(),
+            max_output_bytes: default_max_output_bytes(),
+            history_path: None,
+            history_max_runs: default_history_max_runs(),
+            history_max_output_bytes: default_history_max_output_bytes(),
+            trusted_proxies: Vec::new(),
         }
     }
 }
@@ -67,6 +239,40 @@ pub struct ZfsConfig {
     #[serde(default)]
     /// ZFS datasets that won't be reachable with the API
     pub blacklisted_zfs_datasets: Option<Vec<String>>,
+
+    #[serde(default, deserialize_with = "validate_hooks_list")]
+    /// Commands to run after a dataset's key is loaded
+    pub on_key_loaded: Vec<DatasetHook>,
+
+    #[serde(default, deserialize_with = "validate_hooks_list")]
+    /// Commands to run after a dataset is mounted
+    pub on_mounted: Vec<DatasetHook>,
+
+    #[serde(
+        default,
+        deserialize_with = "validate_unlock_groups",
+        rename = "unlock_group"
+    )]
+    /// Datasets that share one passphrase, and can be unlocked together
+    pub unlock_groups: Vec<UnlockGroupConfig>,
+
+    #[serde(
+        default = "default_dataset_watch_interval_secs",
+        deserialize_with = "validate_interval_secs"
+    )]
+    /// How often, in seconds, to check for dataset changes to send to the clients that listen to events
+    pub dataset_watch_interval_secs: u64,
+
+    #[serde(default, deserialize_with = "validate_mountpoint_prefixes")]
+    /// Directories under which datasets can be temporarily mounted instead of their own mountpoints,
+    /// e.g., to inspect them. If empty, alternate mountpoints are not allowed.
+    pub allowed_mountpoint_prefixes: Vec<String>,
+}
+
+impl ZfsConfig {
+    pub fn all_hooks(&self) -> impl Iterator<Item = &DatasetHook> {
+        self.on_key_loaded.iter().chain(self.on_mounted.iter())
+    }
 }
 
 impl Default for ZfsConfig {
@@ -74,10 +280,200 @@ impl Default for ZfsConfig {
         Self {
             zfs_enabled: default_zfs_enabled(),
             blacklisted_zfs_datasets: None,
+            on_key_loaded: Vec::new(),
+            on_mounted: Vec::new(),
+            unlock_groups: Vec::new(),
+            dataset_watch_interval_secs: default_dataset_watch_interval_secs(),
+            allowed_mountpoint_prefixes: Vec::new(),
+        }
+    }
+}
+
+/// A command that runs automatically after an operation on a dataset succeeds.
+/// Exactly one of `custom_command` and `run_cmd` must be provided.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetHook {
+    /// The dataset name, or a pattern where `*` matches any sequence of characters, e.g., `tank/*`
+    pub dataset: String,
+    /// The `url_endpoint` of a custom command to run
+    #[serde(default)]
+    pub custom_command: Option<String>,
+    /// Commands to run, in the same format as `run_cmd` of custom commands
+    #[serde(default)]
+    pub run_cmd: Option<SingleOrChainedCommands>,
+    /// The label that shows with the result in the UI. If left empty, the command is used.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// The recovery that the server drives on startup, after a reboot: it waits for the keys of locked
+/// datasets (while notifying someone to provide them), then mounts the datasets and runs start-up commands.
+#[must_use]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BootConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    #[serde(default)]
+    /// Datasets that must be unlocked and mounted. If empty, all the encrypted datasets that are not blacklisted.
+    pub datasets: Vec<String>,
+
+    #[serde(default)]
+    /// The link to the UI, to include in notifications
+    pub unlock_url: Option<String>,
+
+    #[serde(
+        default = "default_boot_poll_interval_secs",
+        deserialize_with = "validate_interval_secs"
+    )]
+    /// How often to check whether the keys were loaded, in case they're loaded outside the API server
+    pub poll_interval_secs: u64,
+
+    #[serde(
+        default,
+        deserialize_with = "validate_boot_commands",
+        rename = "notify"
+    )]
+    /// Commands that get the "waiting for unlock" message in their stdin, e.g., to send an email
+    pub notifications: Vec<BootCommand>,
+
+    #[serde(
+        default,
+        deserialize_with = "validate_boot_commands",
+        rename = "startup_command"
+    )]
+    /// Commands to run in order after the datasets are mounted. A failing command stops the ones after it.
+    pub startup_commands: Vec<BootCommand>,
+}
+
+impl BootConfig {
+    pub fn all_commands(&self) -> impl Iterator<Item = &BootCommand> {
+        self.notifications
+            .iter()
+            .chain(self.startup_commands.iter())
+    }
+}
+
+impl Default for BootConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            datasets: Vec::new(),
+            unlock_url: None,
+            poll_interval_secs: default_boot_poll_interval_secs(),
+            notifications: Vec::new(),
+            startup_commands: Vec::new(),
         }
     }
 }
 
+/// A command of the boot orchestration. Exactly one of `custom_command` and `run_cmd` must be provided.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BootCommand {
+    /// The `url_endpoint` of a custom command to run
+    #[serde(default)]
+    pub custom_command: Option<String>,
+    /// Commands to run, in the same format as `run_cmd` of custom commands
+    #[serde(default)]
+    pub run_cmd: Option<SingleOrChainedCommands>,
+    /// The label that shows with the result in the UI. If left empty, the command is used.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+fn validate_boot_commands<'de, D>(deserializer: D) -> Result<Vec<BootCommand>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let cmds: Vec<BootCommand> = Deserialize::deserialize(deserializer)?;
+
+    for cmd in &cmds {
+        if cmd.custom_command.is_some() == cmd.run_cmd.is_some() {
+            return Err(serde::de::Error::custom(
+                "Failed to load config. Every boot command must have exactly one of `custom_command` and `run_cmd`",
+            ));
+        }
+    }
+
+    Ok(cmds)
+}
+
+/// Datasets that share one passphrase, so that the passphrase is typed once to unlock all of them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnlockGroupConfig {
+    /// The name of the group, to show in the UI. Must be unique.
+    pub name: String,
+    /// The datasets to unlock, in order
+    pub datasets: Vec<String>,
+    /// Whether to mount the datasets after their keys are loaded
+    #[serde(default)]
+    pub mount_after: bool,
+}
+
+fn validate_unlock_groups<'de, D>(deserializer: D) -> Result<Vec<UnlockGroupConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let groups: Vec<UnlockGroupConfig> = Deserialize::deserialize(deserializer)?;
+
+    let mut seen = BTreeSet::new();
+    for group in &groups {
+        if !seen.insert(&group.name) {
+            return Err(serde::de::Error::custom(format!(
+                "Failed to load config. Unlock group with name `{}`, as a duplicate was found",
+                group.name
+            )));
+        }
+        if group.datasets.is_empty() {
+            return Err(serde::de::Error::custom(format!(
+                "Failed to load config. Unlock group `{}` has no datasets",
+                group.name
+            )));
+        }
+    }
+
+    Ok(groups)
+}
+
+fn validate_mountpoint_prefixes<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let prefixes: Vec<String> = Deserialize::deserialize(deserializer)?;
+
+    for prefix in &prefixes {
+        if !prefix.starts_with('/') || prefix.split('/').any(|part| part == "..") {
+            return Err(serde::de::Error::custom(format!(
+                "Failed to load config. Allowed mountpoint prefix `{prefix}` must be an absolute path without `..`"
+            )));
+        }
+    }
+
+    Ok(prefixes)
+}
+
+fn validate_hooks_list<'de, D>(deserializer: D) -> Result<Vec<DatasetHook>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let hooks: Vec<DatasetHook> = Deserialize::deserialize(deserializer)?;
+
+    for hook in &hooks {
+        if hook.custom_command.is_some() == hook.run_cmd.is_some() {
+            return Err(serde::de::Error::custom(format!(
+                "Failed to load config. Hook for dataset `{}` must have exactly one of `custom_command` and `run_cmd`",
+                hook.dataset
+            )));
+        }
+    }
+
+    Ok(hooks)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct CustomCommand {
@@ -97,13 +493,404 @@ pub struct CustomCommand {
     #[serde(default)]
     pub stdin_placeholder_text: String,
     /// The definition of the text to be input in stdin... something like "Email address", or "Password", etc.
-    #[serde(default = "default_true")]
-    pub stdin_is_password: bool,
+    /// Defaults to true for the "line" stdin mode, which is the only one that can be a password.
+    #[serde(default)]
+    pub stdin_is_password: Option<bool>,
+    /// How the UI takes the stdin: a single line (the only one that can be a password), many lines, or a file
+    #[serde(default)]
+    pub stdin_mode: StdinMode,
+    /// Whether a new line is added at the end of the stdin, as if it was typed in a terminal.
+    /// Defaults to true, except for files, which are given exactly as they are.
+    #[serde(default)]
+    pub stdin_trailing_newline: Option<bool>,
+    /// The most bytes of stdin that the command takes
+    #[serde(
+        default = "default_stdin_max_bytes",
+        deserialize_with = "validate_stdin_max_bytes"
+    )]
+    pub stdin_max_bytes: usize,
+
+    /// Values that the user gives, which replace `{name}` placeholders in the arguments of `run_cmd`
+    #[serde(default, rename = "parameter")]
+    pub parameters: Vec<CommandParameter>,
+
+    /// The time, in seconds, after which the command and everything it started are killed.
+    /// Overrides `default_command_timeout_secs`.
+    #[serde(default, deserialize_with = "validate_timeout_secs")]
+    pub timeout_secs: Option<u64>,
+
+    /// How many runs of this command can happen at the same time. If not set, there's no limit.
+    #[serde(default, deserialize_with = "validate_max_concurrent")]
+    pub max_concurrent: Option<usize>,
+
+    /// Only one command of the group can run at a time, e.g., starting and stopping a service
+    #[serde(default)]
+    pub exclusive_group: Option<String>,
+
+    /// How the commands of `run_cmd` are chained
+    #[serde(default)]
+    pub chain: ChainOptions,
+
+    /// The most bytes that are kept of each output. Overrides `max_output_bytes` of the server.
+    #[serde(default, deserialize_with = "validate_optional_max_output_bytes")]
+    pub max_output_bytes: Option<usize>,
+
+    /// How the output is returned: as text, or as base64 for binary output
+    #[serde(default)]
+    pub output_encoding: OutputEncoding,
+
+    /// How the stdout is structured, so that the UI shows it, e.g., as a table
+    #[serde(default)]
+    pub output_format: OutputFormat,
+
+    /// What the exit codes mean, to show next to them, e.g., `3 = "Service is stopped"`
+    #[serde(default, deserialize_with = "validate_exit_codes")]
+    pub exit_codes: BTreeMap<i32, String>,
+
+    /// When the server runs the command by itself, e.g., for health checks
+    #[serde(default, deserialize_with = "validate_schedule")]
+    pub schedule: Option<CommandSchedule>,
+
+    /// The section of the UI where the command shows. Commands without a group show first.
+    #[serde(default)]
+    pub group: Option<String>,
+
+    /// Commands show sorted by this number, from the lowest, and in the config order when it's equal
+    #[serde(default)]
+    pub order: i32,
+
+    /// What the command does, to show under its label in the UI
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// A short text to show before the label in the UI, e.g., an emoji
+    #[serde(default)]
+    pub icon: Option<String>,
+
+    /// What the user must confirm before the command runs, e.g., for destructive commands
+    #[serde(default, deserialize_with = "validate_confirm")]
+    pub confirm: Option<CommandConfirmation>,
+
+    /// Status commands are health checks, e.g., whether a service is running, which the server runs
+    /// by itself, and which the UI shows as badges instead of buttons
+    #[serde(default)]
+    pub kind: CommandKind,
+
+    /// When a status command counts as healthy. Defaults to the exit code 0.
+    #[serde(default, deserialize_with = "validate_status_success")]
+    pub success: Option<StatusSuccess>,
+
+    /// How often, in seconds, the server runs a status command. Defaults to 30.
+    #[serde(default, deserialize_with = "validate_status_interval_secs")]
+    pub status_interval_secs: Option<u64>,
+
+    /// Encrypted datasets that must be mounted before the command can run, e.g., where a service keeps its data
+    #[serde(default)]
+    pub requires_mounted: Vec<String>,
+
+    /// Encrypted datasets whose key must be loaded before the command can run
+    #[serde(default)]
+    pub requires_key_loaded: Vec<String>,
 
     #[serde(default = "default_true")]
     pub enabled: bool,
 }
 
+/// How the commands of a chain are connected, and which of them run
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ChainOptions {
+    /// Whether the stderr of every command goes with its stdout, like `2>&1`, to the next command
+    #[serde(default)]
+    pub merge_stderr: bool,
+    /// Whether the chain goes on after a command fails, like `;` instead of `&&`
+    #[serde(default)]
+    pub continue_on_error: bool,
+    /// The commands, numbered from 1, that only run if the command before them failed, like `||`.
+    /// They're skipped otherwise, and the chain goes on after them.
+    #[serde(default)]
+    pub on_failure_stages: Vec<usize>,
+}
+
+impl ChainOptions {
+    /// Whether the command at the given position, from 0, only runs after a failure
+    pub fn runs_on_failure(&self, idx: usize) -> bool {
+        self.on_failure_stages.contains(&(idx + 1))
+    }
+}
+
+/// When a custom command runs by itself. Exactly one of `cron` and `interval_secs` must be provided.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum CommandSchedule {
+    /// A cron expression in local time, e.g., `*/5 * * * *`, where a sixth field in front is for the seconds
+    Cron(String),
+    /// The time, in seconds, between the end of a run and the start of the next one
+    IntervalSecs(u64),
+}
+
+impl CommandSchedule {
+    fn parse_cron(expression: &str) -> Result<cron::Schedule, cron::error::Error> {
+        // The cron crate requires the seconds, which the usual crontab format doesn't have
+        let expression = if expression.split_whitespace().count() == 5 {
+            format!("0 {expression}")
+        } else {
+            expression.to_string()
+        };
+        cron::Schedule::from_str(&expression)
+    }
+
+    /// The time of the first run after `time`, if there's one
+    pub fn next_run_after<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        match self {
+            CommandSchedule::Cron(expression) => Self::parse_cron(expression)
+                .expect("Schedules are checked when the config is loaded")
+                .after(time)
+                .next(),
+            CommandSchedule::IntervalSecs(secs) => {
+                Some(time.clone() + chrono::Duration::seconds(*secs as i64))
+            }
+        }
+    }
+
+    /// The schedule as it shows in the UI
+    pub fn describe(&self) -> String {
+        match self {
+            CommandSchedule::Cron(expression) => expression.clone(),
+            CommandSchedule::IntervalSecs(secs) => format!("every {secs}s"),
+        }
+    }
+}
+
+/// When a status command counts as healthy. Exactly one of `exit_code` and `stdout_matches` must be provided.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum StatusSuccess {
+    /// The command exits with this code
+    ExitCode(i32),
+    /// A regular expression that must match somewhere in the stdout, e.g., `^active$`
+    StdoutMatches(String),
+}
+
+impl Default for StatusSuccess {
+    fn default() -> Self {
+        StatusSuccess::ExitCode(0)
+    }
+}
+
+impl StatusSuccess {
+    /// Whether the output of the command is a healthy one. A command that was stopped never is.
+    pub fn is_met(&self, output: &RunCommandOutput) -> bool {
+        if output.stopped.is_some() {
+            return false;
+        }
+
+        match self {
+            StatusSuccess::ExitCode(code) => output.error_code == *code,
+            StatusSuccess::StdoutMatches(pattern) => Regex::new(pattern)
+                .expect("Success patterns are checked when the config is loaded")
+                .is_match(&output.stdout),
+        }
+    }
+}
+
+fn validate_status_success<'de, D>(deserializer: D) -> Result<Option<StatusSuccess>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let success: Option<StatusSuccess> = Deserialize::deserialize(deserializer)?;
+
+    if let Some(StatusSuccess::StdoutMatches(pattern)) = &success {
+        Regex::new(pattern).map_err(|e| {
+            serde::de::Error::custom(format!(
+                "Failed to load config. Invalid success pattern `{pattern}`: {e}"
+            ))
+        })?;
+    }
+
+    Ok(success)
+}
+
+/// Polling with no interval would never stop running commands
+fn validate_interval_secs<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let interval: u64 = Deserialize::deserialize(deserializer)?;
+
+    if interval == 0 {
+        return Err(serde::de::Error::custom(
+            "Failed to load config. An interval must be at least one second",
+        ));
+    }
+
+    Ok(interval)
+}
+
+fn validate_status_interval_secs<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let interval: Option<u64> = Deserialize::deserialize(deserializer)?;
+
+    if interval == Some(0) {
+        return Err(serde::de::Error::custom(
+            "Failed to load config. A status interval must be at least one second",
+        ));
+    }
+
+    Ok(interval)
+}
+
+fn validate_schedule<'de, D>(deserializer: D) -> Result<Option<CommandSchedule>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let schedule: Option<CommandSchedule> = Deserialize::deserialize(deserializer)?;
+
+    match &schedule {
+        Some(CommandSchedule::Cron(expression)) => {
+            CommandSchedule::parse_cron(expression).map_err(|e| {
+                serde::de::Error::custom(format!(
+                    "Failed to load config. Invalid cron expression `{expression}`: {e}"
+                ))
+            })?;
+        }
+        Some(CommandSchedule::IntervalSecs(0)) => {
+            return Err(serde::de::Error::custom(
+                "Failed to load config. A schedule interval must be at least one second",
+            ));
+        }
+        _ => (),
+    }
+
+    Ok(schedule)
+}
+
+fn validate_exit_codes<'de, D>(deserializer: D) -> Result<BTreeMap<i32, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // The keys of TOML tables are always strings
+    let codes: BTreeMap<String, String> = Deserialize::deserialize(deserializer)?;
+
+    codes
+        .into_iter()
+        .map(|(code, meaning)| match code.parse::<i32>() {
+            Ok(code) => Ok((code, meaning)),
+            Err(_) => Err(serde::de::Error::custom(format!(
+                "Failed to load config. The exit code `{code}` must be a number"
+            ))),
+        })
+        .collect()
+}
+
+fn validate_confirm<'de, D>(deserializer: D) -> Result<Option<CommandConfirmation>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let confirm: Option<CommandConfirmation> = Deserialize::deserialize(deserializer)?;
+
+    if let Some(word) = confirm.as_ref().and_then(|c| c.typed_word.as_ref()) {
+        if word.trim().is_empty() || word.trim() != word {
+            return Err(serde::de::Error::custom(format!(
+                "Failed to load config. The confirmation word `{word}` must not be empty, or start or end with spaces"
+            )));
+        }
+    }
+
+    Ok(confirm)
+}
+
+fn validate_max_output_bytes<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let max: usize = Deserialize::deserialize(deserializer)?;
+
+    if max == 0 {
+        return Err(serde::de::Error::custom(
+            "Failed to load config. The output size limit must be at least one byte",
+        ));
+    }
+
+    Ok(max)
+}
+
+fn validate_optional_max_output_bytes<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Wrapper(#[serde(deserialize_with = "validate_max_output_bytes")] usize);
+
+    let max: Option<Wrapper> = Deserialize::deserialize(deserializer)?;
+    Ok(max.map(|Wrapper(max)| max))
+}
+
+/// Requests are limited to 2 MiB, and files are sent in base64, which is a third larger
+const STDIN_MAX_BYTES_LIMIT: usize = 1024 * 1024;
+
+fn validate_stdin_max_bytes<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let max: usize = Deserialize::deserialize(deserializer)?;
+
+    if max == 0 || max > STDIN_MAX_BYTES_LIMIT {
+        return Err(serde::de::Error::custom(format!(
+            "Failed to load config. The stdin size limit must be between 1 and {STDIN_MAX_BYTES_LIMIT} bytes"
+        )));
+    }
+
+    Ok(max)
+}
+
+fn validate_history_max_runs<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let max: usize = Deserialize::deserialize(deserializer)?;
+
+    if max == 0 {
+        return Err(serde::de::Error::custom(
+            "Failed to load config. The history must keep at least one run",
+        ));
+    }
+
+    Ok(max)
+}
+
+fn validate_max_concurrent<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let max: Option<usize> = Deserialize::deserialize(deserializer)?;
+
+    if max == Some(0) {
+        return Err(serde::de::Error::custom(
+            "Failed to load config. A concurrency limit must be at least one",
+        ));
+    }
+
+    Ok(max)
+}
+
+fn validate_timeout_secs<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let timeout: Option<u64> = Deserialize::deserialize(deserializer)?;
+
+    if timeout == Some(0) {
+        return Err(serde::de::Error::custom(
+            "Failed to load config. A timeout must be at least one second",
+        ));
+    }
+
+    Ok(timeout)
+}
+
 // Custom deserialization function to validate the label field
 fn validate_url_endpoint<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
 where
@@ -176,6 +963,65 @@ where
         None => return Ok(None),
     };
 
+    for cmd in &cmds {
+        let parameters = ParameterSet::new(&cmd.parameters).map_err(|e| {
+            serde::de::Error::custom(format!(
+                "Failed to load config. Command `{}` has invalid parameters: {e}",
+                cmd.label
+            ))
+        })?;
+
+        // Only a single line is typed in a password field
+        if cmd.stdin_is_password == Some(true) && cmd.stdin_mode != StdinMode::Line {
+            return Err(serde::de::Error::custom(format!(
+                "Failed to load config. Command `{}` has `stdin_is_password`, which is only for the \"line\" stdin mode",
+                cmd.label
+            )));
+        }
+
+        // Nobody provides the parameters of a scheduled run
+        if cmd.schedule.is_some() && !parameters.all_have_defaults() {
+            return Err(serde::de::Error::custom(format!(
+                "Failed to load config. Command `{}` has a schedule, so all its parameters must have defaults",
+                cmd.label
+            )));
+        }
+
+        match cmd.kind {
+            // The server runs status commands by itself, so there's nobody to ask for anything
+            CommandKind::Status => {
+                if cmd.stdin_allow || cmd.confirm.is_some() || !parameters.all_have_defaults() {
+                    return Err(serde::de::Error::custom(format!(
+                        "Failed to load config. Command `{}` is a status command, so it can't take stdin or a confirmation, and all its parameters must have defaults",
+                        cmd.label
+                    )));
+                }
+            }
+            CommandKind::Action => {
+                if cmd.success.is_some() || cmd.status_interval_secs.is_some() {
+                    return Err(serde::de::Error::custom(format!(
+                        "Failed to load config. Command `{}` has `success` or `status_interval_secs`, which are only for commands with `kind = \"status\"`",
+                        cmd.label
+                    )));
+                }
+            }
+        }
+
+        // The first command has no command before it to fail
+        let stages_count = cmd.run_cmd.commands().len();
+        if let Some(stage) = cmd
+            .chain
+            .on_failure_stages
+            .iter()
+            .find(|&&stage| stage < 2 || stage > stages_count)
+        {
+            return Err(serde::de::Error::custom(format!(
+                "Failed to load config. Command `{}` has the on-failure stage {stage}, where stages are numbered from 2 to {stages_count}",
+                cmd.label
+            )));
+        }
+    }
+
     // Find duplicates in commands
     {
         let mut seen: BTreeSet<Vec<Vec<String>>> = BTreeSet::new();
@@ -217,6 +1063,34 @@ pub fn default_zfs_enabled() -> bool {
     true
 }
 
+fn default_dataset_watch_interval_secs() -> u64 {
+    5
+}
+
+fn default_max_output_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_stdin_max_bytes() -> usize {
+    DEFAULT_STDIN_MAX_BYTES
+}
+
+fn default_history_max_runs() -> usize {
+    20
+}
+
+fn default_history_max_output_bytes() -> usize {
+    4096
+}
+
+fn default_job_retention_secs() -> u64 {
+    3600
+}
+
+fn default_boot_poll_interval_secs() -> u64 {
+    10
+}
+
 #[cfg(test)]
 mod tests {
     use super::ApiServerConfig;
@@ -227,4 +1101,226 @@ mod tests {
         // println!("{_config:?}");
         // println!("{}", toml::to_string_pretty(&_config).unwrap());
     }
+
+    #[test]
+    fn hooks_must_reference_enabled_commands() {
+        let config = r#"
+            [[custom_command]]
+            label = "Start docker"
+            url_endpoint = "docker-start"
+            run_cmd = ["true"]
+            enabled = false
+
+            [[on_mounted]]
+            dataset = "tank/docker"
+            custom_command = "docker-start"
+        "#;
+        assert!(config.parse::<ApiServerConfig>().is_err());
+
+        let config = config.replace("enabled = false", "enabled = true");
+        assert!(config.parse::<ApiServerConfig>().is_ok());
+    }
+
+    #[test]
+    fn hooks_need_defaults_for_all_parameters() {
+        let config = r#"
+            [[custom_command]]
+            label = "Restart a service"
+            url_endpoint = "restart-service"
+            run_cmd = ["systemctl", "restart", "{service}"]
+
+            [[custom_command.parameter]]
+            name = "service"
+            label = "Service"
+            type = "enum"
+            values = ["docker", "nginx"]
+
+            [[on_mounted]]
+            dataset = "tank/docker"
+            custom_command = "restart-service"
+        "#;
+        assert!(config.parse::<ApiServerConfig>().is_err());
+
+        let with_default = config.replace("values = [", "default = \"docker\"\nvalues = [");
+        assert!(with_default.parse::<ApiServerConfig>().is_ok());
+
+        let bad_default = config.replace("values = [", "default = \"smbd\"\nvalues = [");
+        assert!(bad_default.parse::<ApiServerConfig>().is_err());
+    }
+
+    #[test]
+    fn on_failure_stages_must_follow_another_stage() {
+        let config = r#"
+            [[custom_command]]
+            label = "Restart or report"
+            run_cmd = [["systemctl", "restart", "docker"], ["journalctl", "-u", "docker"]]
+
+            [custom_command.chain]
+            on_failure_stages = [STAGE]
+        "#;
+        assert!(config
+            .replace("STAGE", "2")
+            .parse::<ApiServerConfig>()
+            .is_ok());
+        assert!(config
+            .replace("STAGE", "1")
+            .parse::<ApiServerConfig>()
+            .is_err());
+        assert!(config
+            .replace("STAGE", "3")
+            .parse::<ApiServerConfig>()
+            .is_err());
+    }
+
+    #[test]
+    fn boot_is_validated() {
+        assert!("[boot]\npoll_interval_secs = 0"
+            .parse::<ApiServerConfig>()
+            .is_err());
+
+        let config = r#"
+            blacklisted_zfs_datasets = ["tank/private"]
+
+            [boot]
+            enabled = true
+            datasets = ["tank/secure", "DATASET"]
+        "#;
+        assert!(config
+            .replace("DATASET", "tank/private")
+            .parse::<ApiServerConfig>()
+            .is_err());
+        assert!(config
+            .replace("DATASET", "tank/docker")
+            .parse::<ApiServerConfig>()
+            .is_ok());
+    }
+
+    #[test]
+    fn dataset_watch_interval_must_not_be_zero() {
+        assert!("dataset_watch_interval_secs = 0"
+            .parse::<ApiServerConfig>()
+            .is_err());
+
+        let config = "dataset_watch_interval_secs = 1"
+            .parse::<ApiServerConfig>()
+            .unwrap();
+        assert_eq!(config.zfs_config.dataset_watch_interval_secs, 1);
+    }
+
+    #[test]
+    fn schedules_are_validated() {
+        let config = r#"
+            [[custom_command]]
+            label = "Health check"
+            run_cmd = ["true"]
+            schedule = SCHEDULE
+        "#;
+        for valid in [
+            r#"{ cron = "*/5 * * * *" }"#,
+            r#"{ cron = "30 */5 * * * *" }"#,
+            "{ interval_secs = 60 }",
+        ] {
+            assert!(
+                config
+                    .replace("SCHEDULE", valid)
+                    .parse::<ApiServerConfig>()
+                    .is_ok(),
+                "{valid}"
+            );
+        }
+        for invalid in [
+            r#"{ cron = "every minute" }"#,
+            "{ interval_secs = 0 }",
+            r#"{ cron = "* * * * *", interval_secs = 60 }"#,
+        ] {
+            assert!(
+                config
+                    .replace("SCHEDULE", invalid)
+                    .parse::<ApiServerConfig>()
+                    .is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn exit_codes_must_be_numbers() {
+        let config = r#"
+            [[custom_command]]
+            label = "Status"
+            run_cmd = ["systemctl", "is-active", "docker"]
+
+            [custom_command.exit_codes]
+            CODE = "Service is stopped"
+        "#;
+        assert!(config
+            .replace("CODE", "3")
+            .parse::<ApiServerConfig>()
+            .is_ok());
+        assert!(config
+            .replace("CODE", "-1")
+            .parse::<ApiServerConfig>()
+            .is_ok());
+        assert!(config
+            .replace("CODE", "stopped")
+            .parse::<ApiServerConfig>()
+            .is_err());
+    }
+
+    #[test]
+    fn status_commands_are_validated() {
+        let config = r#"
+            [[custom_command]]
+            label = "Docker"
+            run_cmd = ["systemctl", "is-active", "docker"]
+            EXTRA
+        "#;
+        let parse = |extra: &str| config.replace("EXTRA", extra).parse::<ApiServerConfig>();
+
+        assert!(parse("kind = \"status\"").is_ok());
+        assert!(parse("kind = \"status\"\nsuccess = { stdout_matches = \"^active\" }").is_ok());
+        assert!(parse("kind = \"status\"\nsuccess = { stdout_matches = \"(\" }").is_err());
+        assert!(parse("kind = \"status\"\nstatus_interval_secs = 0").is_err());
+        assert!(parse("kind = \"status\"\nstdin_allow = true").is_err());
+        // Only status commands have a success criterion
+        assert!(parse("success = { exit_code = 0 }").is_err());
+    }
+
+    #[test]
+    fn stdin_options_are_validated() {
+        let config = r#"
+            [[custom_command]]
+            label = "Mail"
+            url_endpoint = "mail"
+            run_cmd = ["mail", "admin"]
+            EXTRA
+        "#;
+        let parse = |extra: &str| config.replace("EXTRA", extra).parse::<ApiServerConfig>();
+
+        assert!(parse("stdin_allow = true\nstdin_is_password = true").is_ok());
+        // Only the line mode defaults to a password
+        assert!(parse("stdin_allow = true\nstdin_mode = \"multiline\"").is_ok());
+        // A password would show in a text area, or be read from a file
+        assert!(
+            parse("stdin_allow = true\nstdin_is_password = true\nstdin_mode = \"multiline\"")
+                .is_err()
+        );
+        assert!(
+            parse("stdin_allow = true\nstdin_is_password = true\nstdin_mode = \"file\"").is_err()
+        );
+
+        // Notifications get the message in their stdin
+        let notify = "[[boot.notify]]\ncustom_command = \"mail\"";
+        assert!(parse(&format!("stdin_allow = true\n{notify}")).is_ok());
+        assert!(parse(&format!("stdin_allow = false\n{notify}")).is_err());
+    }
+
+    #[test]
+    fn hooks_need_exactly_one_action() {
+        let config = r#"
+            [[on_key_loaded]]
+            dataset = "tank/*"
+        "#;
+        assert!(config.parse::<ApiServerConfig>().is_err());
+    }
 }
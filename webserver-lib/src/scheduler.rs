@@ -0,0 +1,166 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use axum::{extract::State, response::IntoResponse, routing::get, Json, Router};
+use chrono::Local;
+use common::types::{
+    CommandScheduleStatus, CustomCommandRunOptions, ScheduledRun, SchedulesStatus, ServerEvent,
+};
+
+use crate::{
+    backend::traits::ExecutionBackend, custom_commands::run_as_job, jobs::now_unix_secs,
+    run_options::config::CommandSchedule, StateType,
+};
+
+const SCHEDULE_STATUS_ENDPOINT: &str = "/schedule-status";
+
+/// The caller of scheduled runs, in the history
+const SCHEDULE_CALLER: &str = "schedule";
+
+/// How many of the most recent runs of every scheduled command are kept
+const MAX_KEPT_RUNS: usize = 10;
+
+/// The custom commands that the server runs by itself, with their next run and their last results
+pub struct Scheduler {
+    statuses: Mutex<BTreeMap<String, CommandScheduleStatus>>,
+}
+
+#[allow(clippy::new_without_default)]
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            statuses: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Changes the status of the command, and returns it
+    fn update(
+        &self,
+        endpoint: &str,
+        schedule: &CommandSchedule,
+        f: impl FnOnce(&mut CommandScheduleStatus),
+    ) -> CommandScheduleStatus {
+        let mut statuses = self.statuses.lock().expect("Poisoned mutex");
+
+        let status =
+            statuses
+                .entry(endpoint.to_string())
+                .or_insert_with(|| CommandScheduleStatus {
+                    endpoint: endpoint.to_string(),
+                    schedule: schedule.describe(),
+                    next_run_at_unix_secs: None,
+                    last_runs: Vec::new(),
+                });
+        f(status);
+
+        status.clone()
+    }
+
+    pub fn status(&self) -> SchedulesStatus {
+        SchedulesStatus {
+            commands: self
+                .statuses
+                .lock()
+                .expect("Poisoned mutex")
+                .values()
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Changes the status of the scheduled command, and lets the clients know
+fn update_status<B: ExecutionBackend>(
+    state: &StateType<B>,
+    endpoint: &str,
+    schedule: &CommandSchedule,
+    f: impl FnOnce(&mut CommandScheduleStatus),
+) {
+    let status = state.scheduler.update(endpoint, schedule, f);
+    state
+        .events
+        .publish(ServerEvent::ScheduleChanged { status });
+}
+
+/// Runs the custom command whenever its schedule is due, until the schedule has no more runs.
+/// The runs are jobs, like the ones from requests, so the limits of the command apply.
+async fn run_schedule<B: ExecutionBackend>(
+    state: StateType<B>,
+    endpoint: String,
+    schedule: CommandSchedule,
+    options: CustomCommandRunOptions,
+) {
+    loop {
+        let now = Local::now();
+        let next_run = schedule.next_run_after(&now);
+        update_status(&state, &endpoint, &schedule, |status| {
+            status.next_run_at_unix_secs = next_run.as_ref().map(|t| t.timestamp() as u64);
+        });
+        let Some(next_run) = next_run else {
+            return;
+        };
+
+        tokio::time::sleep((next_run - now).to_std().unwrap_or_default()).await;
+
+        // The next run is only known once this one is over
+        update_status(&state, &endpoint, &schedule, |status| {
+            status.next_run_at_unix_secs = None;
+        });
+
+        let started_at_unix_secs = now_unix_secs();
+        let job_id = state.jobs.create(&endpoint);
+        let result = run_as_job(
+            &state,
+            &endpoint,
+            options.clone(),
+            SCHEDULE_CALLER,
+            job_id,
+            None,
+        )
+        .await;
+
+        if let Err(e) = &result {
+            log::warn!("Scheduled run of custom command `{endpoint}` failed: {e}");
+        }
+
+        let run = ScheduledRun {
+            job_id,
+            started_at_unix_secs,
+            finished_at_unix_secs: now_unix_secs(),
+            error_code: result.as_ref().ok().map(|output| output.error_code),
+            error: result.err().map(|e| e.to_string()),
+        };
+        update_status(&state, &endpoint, &schedule, |status| {
+            status.last_runs.insert(0, run);
+            status.last_runs.truncate(MAX_KEPT_RUNS);
+        });
+    }
+}
+
+/// Starts running the custom commands that have a schedule, each in its own task
+pub fn start_schedules<B: ExecutionBackend>(state: &StateType<B>) {
+    for cmd in state.backend.custom_cmds_routables().values() {
+        if let Some(schedule) = &cmd.schedule {
+            // The command was configured to run by itself, which confirms it
+            let options = CustomCommandRunOptions {
+                confirmation: cmd.confirmation_token().map(String::from),
+                ..Default::default()
+            };
+            tokio::spawn(run_schedule(
+                state.clone(),
+                cmd.url_endpoint.clone(),
+                schedule.clone(),
+                options,
+            ));
+        }
+    }
+}
+
+async fn schedule_status<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+) -> impl IntoResponse {
+    Json::from(state.scheduler.status())
+}
+
+pub fn scheduler_routes<B: ExecutionBackend>() -> Router<StateType<B>> {
+    Router::new().route(SCHEDULE_STATUS_ENDPOINT, get(schedule_status))
+}
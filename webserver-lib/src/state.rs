@@ -1,11 +1,25 @@
+use std::time::Duration;
+
 use crate::{
     backend::traits::ExecutionBackend,
-    run_options::config::{CustomCommandsConfig, ZfsConfig},
+    boot::BootOrchestrator,
+    events::EventBus,
+    history::History,
+    jobs::Jobs,
+    run_options::config::{BootConfig, CustomCommandsConfig, ZfsConfig},
+    scheduler::Scheduler,
+    statuses::Statuses,
 };
 
 pub struct ServerState<B: ExecutionBackend> {
     pub zfs_config: ZfsConfig,
     pub custom_commands_config: CustomCommandsConfig,
+    pub boot: BootOrchestrator,
+    pub events: EventBus,
+    pub jobs: Jobs,
+    pub history: History,
+    pub scheduler: Scheduler,
+    pub statuses: Statuses,
     pub backend: B,
 }
 
@@ -14,11 +28,24 @@ impl<B: ExecutionBackend> ServerState<B> {
     pub fn new(
         zfs_config: ZfsConfig,
         custom_commands_config: CustomCommandsConfig,
+        boot_config: BootConfig,
         backend: B,
     ) -> Self {
+        let jobs = Jobs::new(Duration::from_secs(
+            custom_commands_config.job_retention_secs,
+        ));
+
+        let history = History::new(&custom_commands_config);
+
         Self {
             zfs_config,
             custom_commands_config,
+            boot: BootOrchestrator::new(boot_config),
+            events: EventBus::new(),
+            jobs,
+            history,
+            scheduler: Scheduler::new(),
+            statuses: Statuses::new(),
             backend,
         }
     }
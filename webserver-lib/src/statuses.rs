@@ -0,0 +1,102 @@
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+
+use axum::{extract::State, response::IntoResponse, routing::get, Json, Router};
+use common::types::{
+    CommandKind, CommandStatus, CommandStatuses, CustomCommandRunOptions, ServerEvent,
+};
+
+use crate::{backend::traits::ExecutionBackend, jobs::now_unix_secs, StateType};
+
+const COMMAND_STATUSES_ENDPOINT: &str = "/command-statuses";
+
+/// The latest results of the status commands. The server checks them by itself, so that
+/// all the clients share the same checks, instead of each client running them.
+pub struct Statuses {
+    statuses: Mutex<BTreeMap<String, CommandStatus>>,
+}
+
+#[allow(clippy::new_without_default)]
+impl Statuses {
+    pub fn new() -> Self {
+        Self {
+            statuses: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn update(&self, status: CommandStatus) {
+        self.statuses
+            .lock()
+            .expect("Poisoned mutex")
+            .insert(status.endpoint.clone(), status);
+    }
+
+    pub fn statuses(&self) -> CommandStatuses {
+        CommandStatuses {
+            commands: self
+                .statuses
+                .lock()
+                .expect("Poisoned mutex")
+                .values()
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Checks the status command at its interval, and lets the clients know of every result.
+/// The checks are neither jobs nor kept in the history, as they would push the real runs out.
+async fn check_status<B: ExecutionBackend>(
+    state: StateType<B>,
+    endpoint: String,
+    interval: Duration,
+) {
+    let mut ticks = tokio::time::interval(interval);
+    // A slow command isn't run again right away to catch up
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticks.tick().await;
+
+        // Status commands have neither stdin, nor a confirmation, nor parameters without defaults
+        let result = state
+            .backend
+            .custom_cmd_call(&endpoint, CustomCommandRunOptions::default())
+            .await;
+        if let Err(e) = &result {
+            log::warn!("Status command `{endpoint}` failed: {e}");
+        }
+
+        let status = CommandStatus {
+            endpoint: endpoint.clone(),
+            checked_at_unix_secs: now_unix_secs(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+            output: result.ok(),
+        };
+        state.statuses.update(status.clone());
+        state.events.publish(ServerEvent::StatusChanged { status });
+    }
+}
+
+/// Starts checking the status commands, each in its own task
+pub fn start_status_checks<B: ExecutionBackend>(state: &StateType<B>) {
+    for cmd in state.backend.custom_cmds_routables().values() {
+        if cmd.kind != CommandKind::Status {
+            continue;
+        }
+        tokio::spawn(check_status(
+            state.clone(),
+            cmd.url_endpoint.clone(),
+            Duration::from_secs(cmd.status_interval_secs),
+        ));
+    }
+}
+
+async fn command_statuses<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+) -> impl IntoResponse {
+    Json::from(state.statuses.statuses())
+}
+
+pub fn statuses_routes<B: ExecutionBackend>() -> Router<StateType<B>> {
+    Router::new().route(COMMAND_STATUSES_ENDPOINT, get(command_statuses))
+}
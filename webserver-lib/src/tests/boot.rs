@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use common::types::{BootState, BootStatus, CommandStopReason, KeyLoadedResponse};
+use serde_json::json;
+
+use crate::backend::zfs_driver::fake::FakeZfsDriver;
+
+use super::TestServer;
+
+/// Polls the boot status until it satisfies the predicate, as the orchestrator runs in the background
+async fn wait_for_boot_state(server: &TestServer, pred: impl Fn(&BootState) -> bool) -> BootStatus {
+    for _ in 0..100 {
+        let status: BootStatus = server.get("/boot-status").await;
+        if pred(&status.state) {
+            return status;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Boot orchestration didn't reach the expected state");
+}
+
+#[tokio::test]
+async fn boot_disabled_by_default() {
+    let server = TestServer::new("", FakeZfsDriver::new());
+
+    let status: BootStatus = server.get("/boot-status").await;
+    assert_eq!(status.state, BootState::Disabled);
+}
+
+#[tokio::test]
+async fn waits_for_keys_then_mounts_and_runs_startup_commands() {
+    let config = r#"
+        [boot]
+        enabled = true
+        datasets = ["tank/secure", "tank/secure/docker"]
+        unlock_url = "https://nas.local"
+
+        [[boot.notify]]
+        label = "Notify admin"
+        run_cmd = ["cat"]
+
+        [[boot.startup_command]]
+        run_cmd = ["echo", "docker started"]
+
+        [[boot.startup_command]]
+        label = "Second"
+        run_cmd = ["echo", "second"]
+    "#;
+    let driver = FakeZfsDriver::new()
+        .with_encryption_root("tank/secure", "secure-pass")
+        .with_child_dataset("tank/secure/docker", "tank/secure")
+        .with_encryption_root("tank/other", "other-pass");
+    let server = TestServer::new(config, driver);
+
+    let status =
+        wait_for_boot_state(&server, |s| matches!(s, BootState::WaitingForUnlock { .. })).await;
+    assert_eq!(
+        status.state,
+        BootState::WaitingForUnlock {
+            locked_datasets: vec!["tank/secure".to_string(), "tank/secure/docker".to_string()]
+        }
+    );
+    assert_eq!(status.notifications.len(), 1);
+    assert_eq!(status.notifications[0].label, "Notify admin");
+    assert!(status.notifications[0]
+        .output
+        .stdout
+        .contains("tank/secure/docker"));
+    assert!(status.notifications[0]
+        .output
+        .stdout
+        .contains("https://nas.local"));
+    assert!(status.startup_commands.is_empty());
+
+    let _: KeyLoadedResponse = server
+        .post(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure" }),
+            &[("Authorization", "secure-pass")],
+        )
+        .await;
+
+    // Loading the key wakes the orchestrator up, without waiting for the next poll
+    let status = wait_for_boot_state(&server, |s| {
+        matches!(s, BootState::Ready | BootState::Failed { .. })
+    })
+    .await;
+    assert_eq!(status.state, BootState::Ready);
+    assert_eq!(
+        status
+            .startup_commands
+            .iter()
+            .map(|c| (c.label.as_str(), c.output.stdout.trim()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("echo docker started", "docker started"),
+            ("Second", "second")
+        ]
+    );
+
+    // Notifications are sent once
+    assert_eq!(status.notifications.len(), 1);
+
+    for ds in ["tank/secure", "tank/secure/docker"] {
+        let states: common::types::DatasetFullMountState = server
+            .post(
+                "/zfs/encrypted-dataset-state",
+                json!({ "dataset_name": ds }),
+                &[],
+            )
+            .await;
+        assert!(states.is_mounted);
+    }
+}
+
+#[tokio::test]
+async fn failing_startup_command_stops_the_rest() {
+    let config = r#"
+        [boot]
+        enabled = true
+
+        [[boot.startup_command]]
+        run_cmd = ["false"]
+
+        [[boot.startup_command]]
+        run_cmd = ["echo", "not happening"]
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let status = wait_for_boot_state(&server, |s| matches!(s, BootState::Failed { .. })).await;
+    assert_eq!(status.startup_commands.len(), 1);
+}
+
+#[tokio::test]
+async fn hung_startup_command_is_killed_after_the_default_timeout() {
+    let config = r#"
+        default_command_timeout_secs = 1
+
+        [boot]
+        enabled = true
+
+        [[boot.startup_command]]
+        run_cmd = ["sleep", "30"]
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let status = wait_for_boot_state(&server, |s| matches!(s, BootState::Failed { .. })).await;
+    assert_eq!(
+        status.startup_commands[0].output.stopped,
+        Some(CommandStopReason::TimedOut)
+    );
+}
+
+#[tokio::test]
+async fn missing_datasets_fail_unless_their_pool_can_be_imported() {
+    let config = r#"
+        [boot]
+        enabled = true
+        datasets = ["tank/secure", "DATASET"]
+    "#;
+    let driver = || {
+        FakeZfsDriver::new()
+            .with_encryption_root("tank/secure", "secure-pass")
+            .with_exported_pool("backup")
+    };
+
+    let server = TestServer::new(&config.replace("DATASET", "tank/secrue"), driver());
+    let status = wait_for_boot_state(&server, |s| matches!(s, BootState::Failed { .. })).await;
+    let BootState::Failed { error } = status.state else {
+        unreachable!()
+    };
+    assert!(error.contains("tank/secrue"), "{error}");
+    assert!(status.notifications.is_empty());
+
+    // The dataset shows up once its pool is imported
+    let server = TestServer::new(&config.replace("DATASET", "backup/secure"), driver());
+    let status =
+        wait_for_boot_state(&server, |s| matches!(s, BootState::WaitingForUnlock { .. })).await;
+    assert_eq!(
+        status.state,
+        BootState::WaitingForUnlock {
+            locked_datasets: vec!["tank/secure".to_string(), "backup/secure".to_string()]
+        }
+    );
+}
@@ -0,0 +1,906 @@
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use common::types::{
+    AvailableCustomCommands, CommandHistory, CommandKind, CommandParameterType, CommandStopReason,
+    CommandStreamMessage, JobCancelledResponse, JobId, JobOutput, JobStartedResponse, JobState,
+    JobStatus, JobsList, OutputFormat, OutputStream, RunCommandOutput, SchedulesStatus, StdinMode,
+    DEFAULT_STATUS_INTERVAL_SECS,
+};
+use futures::{SinkExt, StreamExt};
+use serde_json::json;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::backend::zfs_driver::fake::FakeZfsDriver;
+
+use super::TestServer;
+
+#[tokio::test]
+async fn parameters_are_substituted_and_validated() {
+    let config = r#"
+        [[custom_command]]
+        label = "Greet"
+        url_endpoint = "greet"
+        run_cmd = [["echo", "hello {name}, {times}x, {loud}"], ["awk", "{print $2}"]]
+
+        [[custom_command.parameter]]
+        name = "name"
+        label = "Name"
+        type = "string"
+        regex = "[a-z]+"
+
+        [[custom_command.parameter]]
+        name = "times"
+        label = "Times"
+        type = "int"
+        default = 2
+
+        [[custom_command.parameter]]
+        name = "loud"
+        label = "Loud"
+        type = "bool"
+        default = false
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let commands: AvailableCustomCommands = server.get("/custom-commands-list").await;
+    let kinds = commands.commands[0]
+        .parameters
+        .iter()
+        .map(|p| p.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            CommandParameterType::String,
+            CommandParameterType::Int,
+            CommandParameterType::Bool
+        ]
+    );
+
+    // `{print $2}` isn't a declared parameter, so it's left alone
+    let output: RunCommandOutput = server
+        .post(
+            "/custom-commands/greet",
+            json!({ "parameters": { "name": "sam" } }),
+            &[],
+        )
+        .await;
+    assert_eq!(output.stdout.trim(), "sam,");
+
+    let output: RunCommandOutput = server
+        .post(
+            "/custom-commands/greet",
+            json!({ "parameters": { "name": "sam", "times": "3", "loud": "true" } }),
+            &[],
+        )
+        .await;
+    assert_eq!(output.error_code, 0);
+
+    for parameters in [
+        json!({}),
+        json!({ "name": "Sam; rm -rf /" }),
+        json!({ "name": "sam", "times": "two" }),
+        json!({ "name": "sam", "loud": "yes" }),
+        json!({ "name": "sam", "unknown": "x" }),
+    ] {
+        assert_eq!(
+            server
+                .post_status(
+                    "/custom-commands/greet",
+                    json!({ "parameters": parameters }),
+                    &[]
+                )
+                .await,
+            StatusCode::BAD_REQUEST,
+            "{parameters}"
+        );
+    }
+}
+
+/// Runs a custom command over the streaming WebSocket of a real listener, and returns
+/// the messages that the server sent
+async fn stream_command(
+    server: &TestServer,
+    endpoint: &str,
+    options: serde_json::Value,
+) -> Vec<CommandStreamMessage> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let router = server.router.clone();
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    let url = format!("ws://{address}/custom-commands/{endpoint}/stream");
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    socket
+        .send(Message::Text(options.to_string()))
+        .await
+        .unwrap();
+
+    let mut messages = Vec::new();
+    while let Some(message) = tokio::time::timeout(Duration::from_secs(5), socket.next())
+        .await
+        .expect("Timed out waiting for the command output")
+    {
+        if let Message::Text(text) = message.unwrap() {
+            messages.push(serde_json::from_str(&text).unwrap());
+        }
+    }
+    messages
+}
+
+#[tokio::test]
+async fn output_is_streamed_while_the_command_runs() {
+    let config = r#"
+        [[custom_command]]
+        label = "Slow"
+        url_endpoint = "slow"
+        run_cmd = ["sh", "-c", "echo one; echo oops >&2; sleep 0.2; echo {last}"]
+
+        [[custom_command.parameter]]
+        name = "last"
+        label = "Last"
+        type = "string"
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let messages =
+        stream_command(&server, "slow", json!({ "parameters": { "last": "two" } })).await;
+
+    // The job of the run is known first, so that the run can be cancelled
+    assert!(matches!(
+        messages.first(),
+        Some(CommandStreamMessage::Started { .. })
+    ));
+    let (chunks, last) = messages[1..].split_at(messages.len() - 2);
+    let streamed = |stream| {
+        chunks
+            .iter()
+            .filter_map(|m| match m {
+                CommandStreamMessage::Output(c) if c.stream == stream => Some(c.data.as_str()),
+                _ => None,
+            })
+            .collect::<String>()
+    };
+    assert_eq!(streamed(OutputStream::Stdout), "one\ntwo\n");
+    assert_eq!(streamed(OutputStream::Stderr), "oops\n");
+    // The first line arrives before the command finishes
+    assert!(chunks.len() >= 3);
+
+    let [CommandStreamMessage::Finished { output }] = last else {
+        panic!("Unexpected last message: {last:?}");
+    };
+    assert_eq!(
+        RunCommandOutput {
+            stages: Vec::new(),
+            ..output.clone()
+        },
+        RunCommandOutput {
+            stdout: "one\ntwo\n".to_string(),
+            stderr: "oops\n".to_string(),
+            error_code: 0,
+            ..Default::default()
+        }
+    );
+    assert_eq!(output.stages.len(), 1);
+
+    // Errors are reported with the status code that the request would have had
+    let messages = stream_command(&server, "slow", json!({})).await;
+    assert!(matches!(
+        messages.as_slice(),
+        [
+            CommandStreamMessage::Started { .. },
+            CommandStreamMessage::Failed { status: 400, .. }
+        ]
+    ));
+}
+
+/// Waits for the jobs of the endpoint to be running, and returns their ids
+async fn running_jobs(server: &TestServer, endpoint: &str, count: usize) -> Vec<JobId> {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let jobs: JobsList = server.get("/jobs").await;
+            let running = jobs
+                .jobs
+                .into_iter()
+                .filter(|j| j.endpoint == endpoint && j.state == JobState::Running)
+                .map(|j| j.job_id)
+                .collect::<Vec<_>>();
+            if running.len() >= count {
+                break running;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the jobs to run")
+}
+
+#[tokio::test]
+async fn commands_time_out_and_can_be_cancelled() {
+    let config = r#"
+        default_command_timeout_secs = 1
+
+        [[custom_command]]
+        label = "Hang"
+        url_endpoint = "hang"
+        run_cmd = ["sleep", "30"]
+
+        [[custom_command]]
+        label = "Hang longer"
+        url_endpoint = "hang-longer"
+        run_cmd = ["sleep", "31"]
+        timeout_secs = 30
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let output: RunCommandOutput = server.post("/custom-commands/hang", json!({}), &[]).await;
+    assert_eq!(output.stopped, Some(CommandStopReason::TimedOut));
+    assert!(output.signal.is_some());
+
+    // The command's own timeout is longer than the default, so only cancelling stops it.
+    // Cancelling a job doesn't stop the other run of the same command.
+    let started: JobStartedResponse = server
+        .post("/custom-commands/hang-longer/start", json!({}), &[])
+        .await;
+    let run = tokio::spawn({
+        let server = server.clone();
+        async move {
+            server
+                .post::<RunCommandOutput>("/custom-commands/hang-longer", json!({}), &[])
+                .await
+        }
+    });
+    let running = running_jobs(&server, "hang-longer", 2).await;
+    let waited_job = running
+        .into_iter()
+        .find(|job_id| *job_id != started.job_id)
+        .unwrap();
+
+    let cancelled: JobCancelledResponse = server
+        .post(&format!("/jobs/{waited_job}/cancel"), json!({}), &[])
+        .await;
+    assert!(cancelled.cancelled);
+
+    let output = tokio::time::timeout(Duration::from_secs(5), run)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(output.stopped, Some(CommandStopReason::Cancelled));
+
+    let status: JobStatus = server.get(&format!("/jobs/{}", started.job_id)).await;
+    assert_eq!(status.state, JobState::Running);
+
+    let cancelled: JobCancelledResponse = server
+        .post(&format!("/jobs/{}/cancel", started.job_id), json!({}), &[])
+        .await;
+    assert!(cancelled.cancelled);
+
+    // A job that is over can't be cancelled again, and an unknown one is an error
+    let cancelled: JobCancelledResponse = server
+        .post(&format!("/jobs/{waited_job}/cancel"), json!({}), &[])
+        .await;
+    assert!(!cancelled.cancelled);
+
+    let status = server
+        .post_status("/jobs/12345/cancel", json!({}), &[])
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn started_jobs_can_be_polled_until_they_finish() {
+    let config = r#"
+        [[custom_command]]
+        label = "Slow"
+        url_endpoint = "slow"
+        run_cmd = ["sh", "-c", "echo first; sleep 0.3; echo second"]
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let started: JobStartedResponse = server
+        .post("/custom-commands/slow/start", json!({}), &[])
+        .await;
+
+    let status = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let status: JobStatus = server.get(&format!("/jobs/{}", started.job_id)).await;
+            if status.state != JobState::Running {
+                break status;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(status.state, JobState::Finished);
+    assert_eq!(status.endpoint, "slow");
+    assert!(status.finished_at_unix_secs.is_some());
+
+    let job: JobOutput = server
+        .get(&format!("/jobs/{}/output", started.job_id))
+        .await;
+    assert_eq!(job.output.stdout, "first\nsecond\n");
+
+    // Requests that wait for the result are jobs too
+    let _: RunCommandOutput = server.post("/custom-commands/slow", json!({}), &[]).await;
+
+    let jobs: JobsList = server.get("/jobs").await;
+    let ids = jobs.jobs.iter().map(|j| j.job_id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![started.job_id + 1, started.job_id]);
+
+    let (status, _) = server.request(Method::GET, "/jobs/12345", None, &[]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn commands_of_an_exclusive_group_conflict() {
+    let config = r#"
+        [[custom_command]]
+        label = "Start"
+        url_endpoint = "start"
+        run_cmd = ["sleep", "30"]
+        exclusive_group = "docker"
+
+        [[custom_command]]
+        label = "Stop"
+        url_endpoint = "stop"
+        run_cmd = ["true"]
+        exclusive_group = "docker"
+
+        [[custom_command]]
+        label = "Other"
+        url_endpoint = "other"
+        run_cmd = ["echo", "other"]
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let run = tokio::spawn({
+        let server = server.clone();
+        async move {
+            server
+                .post::<RunCommandOutput>("/custom-commands/start", json!({}), &[])
+                .await
+        }
+    });
+
+    let busy = |commands: AvailableCustomCommands| {
+        commands
+            .commands
+            .into_iter()
+            .filter(|c| c.busy)
+            .map(|c| c.endpoint)
+            .collect::<Vec<_>>()
+    };
+    loop {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        if !busy(server.get("/custom-commands-list").await).is_empty() {
+            break;
+        }
+    }
+    assert_eq!(
+        busy(server.get("/custom-commands-list").await),
+        vec!["start", "stop"]
+    );
+
+    let status = server
+        .post_status("/custom-commands/stop", json!({}), &[])
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let _: RunCommandOutput = server.post("/custom-commands/other", json!({}), &[]).await;
+
+    let [job_id] = running_jobs(&server, "start", 1).await[..] else {
+        panic!("Only one run of the command was expected");
+    };
+    let _: JobCancelledResponse = server
+        .post(&format!("/jobs/{job_id}/cancel"), json!({}), &[])
+        .await;
+    tokio::time::timeout(Duration::from_secs(5), run)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let output: RunCommandOutput = server.post("/custom-commands/stop", json!({}), &[]).await;
+    assert_eq!(output.error_code, 0);
+}
+
+#[tokio::test]
+async fn scheduled_commands_run_by_themselves() {
+    let config = r#"
+        [[custom_command]]
+        label = "Health check"
+        url_endpoint = "health"
+        run_cmd = ["echo", "healthy"]
+        schedule = { interval_secs = 1 }
+
+        [[custom_command]]
+        label = "Manual"
+        url_endpoint = "manual"
+        run_cmd = ["echo", "manual"]
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let status = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let status: SchedulesStatus = server.get("/schedule-status").await;
+            if status.commands.iter().any(|c| !c.last_runs.is_empty()) {
+                break status;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    let [command] = status.commands.as_slice() else {
+        panic!("Only the scheduled command is expected: {status:?}");
+    };
+    assert_eq!(command.endpoint, "health");
+    assert_eq!(command.schedule, "every 1s");
+    let run = &command.last_runs[0];
+    assert_eq!(run.error_code, Some(0));
+    assert_eq!(run.error, None);
+
+    // The runs are jobs, with their output
+    let job: JobOutput = server.get(&format!("/jobs/{}/output", run.job_id)).await;
+    assert_eq!(job.status.endpoint, "health");
+    assert_eq!(job.output.stdout, "healthy\n");
+}
+
+#[tokio::test]
+async fn disabled_commands_are_never_scheduled() {
+    let config = r#"
+        [[custom_command]]
+        label = "Old health check"
+        url_endpoint = "health"
+        run_cmd = ["echo", "old"]
+        schedule = { interval_secs = 1 }
+        enabled = false
+
+        [[custom_command]]
+        label = "Cleanup"
+        url_endpoint = "cleanup"
+        run_cmd = ["echo", "cleaned"]
+        schedule = { interval_secs = 1 }
+        confirm = { message = "Clean everything?" }
+        enabled = false
+
+        [[custom_command]]
+        label = "Health check"
+        url_endpoint = "health"
+        run_cmd = ["echo", "healthy"]
+        schedule = { interval_secs = 1 }
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    // Two runs of the enabled command leave the disabled ones time to run too
+    let status = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let status: SchedulesStatus = server.get("/schedule-status").await;
+            if status.commands.iter().any(|c| c.last_runs.len() >= 2) {
+                break status;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    let [command] = status.commands.as_slice() else {
+        panic!("Only the enabled command is expected: {status:?}");
+    };
+    assert_eq!(command.endpoint, "health");
+    for run in &command.last_runs {
+        let job: JobOutput = server.get(&format!("/jobs/{}/output", run.job_id)).await;
+        assert_eq!(job.output.stdout, "healthy\n");
+    }
+
+    // Not routed, so it falls through to the fallback
+    let status = server
+        .post_status("/custom-commands/cleanup", json!({}), &[])
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn commands_are_listed_in_config_order() {
+    let config = r#"
+        [[custom_command]]
+        label = "Zebra"
+        run_cmd = ["echo", "zebra"]
+        group = "Animals"
+        description = "Stripes"
+        icon = "🦓"
+
+        [[custom_command]]
+        label = "Apple"
+        run_cmd = ["echo", "apple"]
+
+        [[custom_command]]
+        label = "Mango"
+        run_cmd = ["echo", "mango"]
+        order = -1
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let commands: AvailableCustomCommands = server.get("/custom-commands-list").await;
+    let labels = commands
+        .commands
+        .iter()
+        .map(|c| c.label.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(labels, vec!["Mango", "Zebra", "Apple"]);
+
+    let zebra = &commands.commands[1];
+    assert_eq!(zebra.group.as_deref(), Some("Animals"));
+    assert_eq!(zebra.description.as_deref(), Some("Stripes"));
+    assert_eq!(zebra.icon.as_deref(), Some("🦓"));
+}
+
+#[tokio::test]
+async fn confirmed_commands_need_the_confirmation() {
+    let config = r#"
+        [[custom_command]]
+        label = "Restart"
+        url_endpoint = "restart"
+        run_cmd = ["echo", "restarted"]
+        confirm = { message = "Restart everything?", typed_word = "RESTART" }
+
+        [[custom_command]]
+        label = "Stop"
+        url_endpoint = "stop"
+        run_cmd = ["echo", "stopped"]
+        confirm = { message = "Stop everything?" }
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let commands: AvailableCustomCommands = server.get("/custom-commands-list").await;
+    assert!(commands.commands.iter().all(|c| c.confirm.is_some()));
+
+    for (endpoint, options) in [
+        ("restart", json!({})),
+        ("restart", json!({ "confirmation": "restart" })),
+        ("stop", json!({ "confirmation": "yes" })),
+    ] {
+        let status = server
+            .post_status(&format!("/custom-commands/{endpoint}"), options, &[])
+            .await;
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED, "{endpoint}");
+    }
+
+    let output: RunCommandOutput = server
+        .post(
+            "/custom-commands/restart",
+            json!({ "confirmation": "RESTART" }),
+            &[],
+        )
+        .await;
+    assert_eq!(output.stdout, "restarted\n");
+
+    // A yes/no prompt is confirmed with the endpoint of the command
+    let output: RunCommandOutput = server
+        .post(
+            "/custom-commands/stop",
+            json!({ "confirmation": "stop" }),
+            &[],
+        )
+        .await;
+    assert_eq!(output.stdout, "stopped\n");
+}
+
+#[tokio::test]
+async fn outputs_have_their_format_and_exit_code_meaning() {
+    let config = r#"
+        [[custom_command]]
+        label = "Status"
+        url_endpoint = "status"
+        run_cmd = ["sh", "-c", "echo state=stopped; exit 3"]
+        output_format = "key_value"
+
+        [custom_command.exit_codes]
+        0 = "Service is running"
+        3 = "Service is stopped"
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let output: RunCommandOutput = server.post("/custom-commands/status", json!({}), &[]).await;
+    assert_eq!(output.error_code, 3);
+    assert_eq!(output.format, OutputFormat::KeyValue);
+    assert_eq!(
+        output.exit_code_meaning.as_deref(),
+        Some("Service is stopped")
+    );
+}
+
+#[tokio::test]
+async fn runs_are_kept_in_the_history_without_passwords() {
+    let history_path =
+        std::env::temp_dir().join(format!("command-history-test-{}.json", std::process::id()));
+    let config = format!(
+        r#"
+        history_path = "{}"
+        history_max_runs = 2
+
+        [[custom_command]]
+        label = "Echo"
+        url_endpoint = "echo"
+        run_cmd = ["cat"]
+        stdin_allow = true
+        stdin_is_password = false
+
+        [[custom_command]]
+        label = "Unlock"
+        url_endpoint = "unlock"
+        run_cmd = ["sh", "-c", "cat > /dev/null; exit 2"]
+        stdin_allow = true
+        stdin_is_password = true
+    "#,
+        history_path.display()
+    );
+    let server = TestServer::new(&config, FakeZfsDriver::new());
+
+    for text in ["first", "second", "third"] {
+        let _: RunCommandOutput = server
+            .post(
+                "/custom-commands/echo",
+                json!({ "stdin": text }),
+                &[("X-Forwarded-For", "10.0.0.7, 10.0.0.1")],
+            )
+            .await;
+    }
+    let _: RunCommandOutput = server
+        .post("/custom-commands/unlock", json!({ "stdin": "secret" }), &[])
+        .await;
+
+    let history: CommandHistory = server.get("/history/echo").await;
+    let stdins = history
+        .runs
+        .iter()
+        .map(|r| r.stdin.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(stdins, vec![Some("third"), Some("second")]);
+    assert_eq!(history.runs[0].stdout, "third\n");
+    // The header isn't trusted without a configured proxy as the peer
+    assert_eq!(history.runs[0].caller, "unknown");
+    assert_eq!(history.runs[0].error_code, Some(0));
+
+    let history: CommandHistory = server.get("/history/unlock").await;
+    assert_eq!(history.runs.len(), 1);
+    assert_eq!(history.runs[0].stdin, None);
+    assert_eq!(history.runs[0].error_code, Some(2));
+    assert_eq!(history.runs[0].caller, "unknown");
+
+    let (status, _) = server
+        .request(Method::GET, "/history/missing", None, &[])
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // The history is loaded from the file when the server starts again
+    let restarted = TestServer::new(&config, FakeZfsDriver::new());
+    let history: CommandHistory = restarted.get("/history/echo").await;
+    assert_eq!(history.runs.len(), 2);
+    assert!(!std::fs::read_to_string(&history_path)
+        .unwrap()
+        .contains("secret"));
+
+    std::fs::remove_file(history_path).unwrap();
+}
+
+#[tokio::test]
+async fn status_commands_report_whether_they_are_healthy() {
+    let config = r#"
+        [[custom_command]]
+        label = "Service"
+        url_endpoint = "service"
+        run_cmd = ["echo", "inactive"]
+        kind = "status"
+        success = { stdout_matches = "^active" }
+        status_interval_secs = 5
+
+        [[custom_command]]
+        label = "Disk"
+        url_endpoint = "disk"
+        run_cmd = ["true"]
+        kind = "status"
+
+        [[custom_command]]
+        label = "Echo"
+        url_endpoint = "echo"
+        run_cmd = ["echo", "active"]
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let output: RunCommandOutput = server
+        .post("/custom-commands/service", json!({}), &[])
+        .await;
+    assert_eq!(output.status_ok, Some(false));
+    let output: RunCommandOutput = server.post("/custom-commands/disk", json!({}), &[]).await;
+    assert_eq!(output.status_ok, Some(true));
+    let output: RunCommandOutput = server.post("/custom-commands/echo", json!({}), &[]).await;
+    assert_eq!(output.status_ok, None);
+
+    let list: AvailableCustomCommands = server.get("/custom-commands-list").await;
+    let kinds = list
+        .commands
+        .iter()
+        .map(|c| (c.endpoint.as_str(), c.kind, c.status_interval_secs))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            ("service", CommandKind::Status, Some(5)),
+            (
+                "disk",
+                CommandKind::Status,
+                Some(DEFAULT_STATUS_INTERVAL_SECS)
+            ),
+            ("echo", CommandKind::Action, None),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn stdin_is_given_as_configured() {
+    let config = r#"
+        [[custom_command]]
+        label = "Line"
+        url_endpoint = "line"
+        run_cmd = ["od", "-An", "-c"]
+        stdin_allow = true
+
+        [[custom_command]]
+        label = "Snippet"
+        url_endpoint = "snippet"
+        run_cmd = ["cat"]
+        stdin_allow = true
+        stdin_mode = "multiline"
+        stdin_trailing_newline = false
+
+        [[custom_command]]
+        label = "Upload"
+        url_endpoint = "upload"
+        run_cmd = ["wc", "-c"]
+        stdin_allow = true
+        stdin_mode = "file"
+        stdin_max_bytes = 4
+
+        [[custom_command]]
+        label = "No stdin"
+        url_endpoint = "no-stdin"
+        run_cmd = ["true"]
+        stdin_allow = false
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let output: RunCommandOutput = server
+        .post("/custom-commands/line", json!({ "stdin": "a" }), &[])
+        .await;
+    assert!(output.stdout.contains("\\n"), "{}", output.stdout);
+
+    let output: RunCommandOutput = server
+        .post(
+            "/custom-commands/snippet",
+            json!({ "stdin": "[section]\nkey = 1" }),
+            &[],
+        )
+        .await;
+    assert_eq!(output.stdout, "[section]\nkey = 1");
+
+    let list: AvailableCustomCommands = server.get("/custom-commands-list").await;
+    let upload = list
+        .commands
+        .iter()
+        .find(|c| c.endpoint == "upload")
+        .unwrap();
+    assert_eq!(upload.stdin_mode, StdinMode::File);
+    assert_eq!(upload.stdin_max_bytes, 4);
+
+    // Files are given exactly as they are, without a new line at the end
+    let output: RunCommandOutput = server
+        .post(
+            "/custom-commands/upload",
+            json!({ "stdin": "AAEC", "stdin_encoding": "base64" }),
+            &[],
+        )
+        .await;
+    assert_eq!(output.stdout.trim(), "3");
+
+    let status = server
+        .post_status(
+            "/custom-commands/upload",
+            json!({ "stdin": "AAECAwQ=", "stdin_encoding": "base64" }),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    let status = server
+        .post_status(
+            "/custom-commands/upload",
+            json!({ "stdin": "not base64!", "stdin_encoding": "base64" }),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Commands that don't take stdin don't get any
+    let status = server
+        .post_status("/custom-commands/no-stdin", json!({ "stdin": "a" }), &[])
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let output: RunCommandOutput = server
+        .post("/custom-commands/no-stdin", json!({}), &[])
+        .await;
+    assert_eq!(output.error_code, 0);
+}
+
+#[tokio::test]
+async fn commands_wait_for_their_datasets() {
+    let config = r#"
+        [[custom_command]]
+        label = "Start docker"
+        url_endpoint = "docker-start"
+        run_cmd = ["echo", "started"]
+        requires_mounted = ["tank/docker"]
+
+        [[custom_command]]
+        label = "Backup keys"
+        url_endpoint = "backup-keys"
+        run_cmd = ["echo", "backed up"]
+        requires_key_loaded = ["tank/docker"]
+    "#;
+    let driver = FakeZfsDriver::new().with_encryption_root("tank/docker", "docker-pass");
+    let server = TestServer::new(config, driver);
+
+    let list: AvailableCustomCommands = server.get("/custom-commands-list").await;
+    assert_eq!(
+        list.commands[0].preconditions.requires_mounted,
+        vec!["tank/docker"]
+    );
+
+    for endpoint in ["docker-start", "backup-keys"] {
+        let (status, body) = server
+            .request(
+                Method::POST,
+                &format!("/custom-commands/{endpoint}"),
+                Some(json!({})),
+                &[],
+            )
+            .await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        assert!(String::from_utf8_lossy(&body).contains("tank/docker"));
+    }
+
+    let status = server
+        .post_status(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/docker" }),
+            &[("Authorization", "docker-pass")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let output: RunCommandOutput = server
+        .post("/custom-commands/backup-keys", json!({}), &[])
+        .await;
+    assert_eq!(output.stdout, "backed up\n");
+    let status = server
+        .post_status("/custom-commands/docker-start", json!({}), &[])
+        .await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let status = server
+        .post_status(
+            "/zfs/mount-dataset",
+            json!({ "dataset_name": "tank/docker" }),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let output: RunCommandOutput = server
+        .post("/custom-commands/docker-start", json!({}), &[])
+        .await;
+    assert_eq!(output.stdout, "started\n");
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use axum::{
+    body::{Body, BodyDataStream},
+    http::{Request, StatusCode},
+};
+use common::types::{
+    CommandHistory, CommandStatuses, KeyLoadedResponse, RunCommandOutput, ServerEvent,
+};
+use futures::StreamExt;
+use serde_json::json;
+use tower::ServiceExt;
+
+use crate::backend::zfs_driver::fake::FakeZfsDriver;
+
+use super::TestServer;
+
+/// Reads the server-sent events of the events endpoint
+struct EventsClient {
+    body: BodyDataStream,
+    buffer: String,
+}
+
+impl EventsClient {
+    async fn connect(server: &TestServer) -> Self {
+        let request = Request::get("/events").body(Body::empty()).unwrap();
+        let response = server.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        Self {
+            body: response.into_body().into_data_stream(),
+            buffer: String::new(),
+        }
+    }
+
+    async fn next_event(&mut self) -> ServerEvent {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let message = self.buffer[..end].to_string();
+                self.buffer.drain(..end + 2);
+
+                // Keep-alive comments have no data
+                if let Some(data) = message.lines().find_map(|l| l.strip_prefix("data: ")) {
+                    return serde_json::from_str(data).unwrap();
+                }
+                continue;
+            }
+
+            let chunk = tokio::time::timeout(Duration::from_secs(5), self.body.next())
+                .await
+                .expect("Timed out waiting for an event")
+                .unwrap()
+                .unwrap();
+            self.buffer.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    }
+}
+
+#[tokio::test]
+async fn dataset_and_command_events() {
+    let config = r#"
+        [[custom_command]]
+        label = "Say hi"
+        url_endpoint = "say-hi"
+        run_cmd = ["echo", "hi"]
+    "#;
+    let driver = FakeZfsDriver::new()
+        .with_encryption_root("tank/secure", "secure-pass")
+        .with_child_dataset("tank/secure/docker", "tank/secure");
+    let server = TestServer::new(config, driver);
+
+    let mut client = EventsClient::connect(&server).await;
+
+    // A new listener gets the current state of the datasets
+    for _ in 0..2 {
+        assert!(matches!(
+            client.next_event().await,
+            ServerEvent::DatasetChanged { state } if !state.key_loaded
+        ));
+    }
+
+    let _: KeyLoadedResponse = server
+        .post(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure" }),
+            &[("Authorization", "secure-pass")],
+        )
+        .await;
+
+    // Both datasets share the encryption root, so both changed
+    let mut changed = Vec::new();
+    for _ in 0..2 {
+        match client.next_event().await {
+            ServerEvent::DatasetChanged { state } => {
+                assert!(state.key_loaded);
+                changed.push(state.dataset_name);
+            }
+            e => panic!("Unexpected event: {e:?}"),
+        }
+    }
+    changed.sort();
+    assert_eq!(changed, vec!["tank/secure", "tank/secure/docker"]);
+
+    let _: RunCommandOutput = server.post("/custom-commands/say-hi", json!({}), &[]).await;
+
+    assert_eq!(
+        client.next_event().await,
+        ServerEvent::CommandStarted {
+            endpoint: "say-hi".to_string()
+        }
+    );
+    match client.next_event().await {
+        ServerEvent::CommandFinished { endpoint, output } => {
+            assert_eq!(endpoint, "say-hi");
+            assert_eq!(output.stdout.trim(), "hi");
+        }
+        e => panic!("Unexpected event: {e:?}"),
+    }
+}
+
+#[tokio::test]
+async fn status_commands_are_checked_by_the_server() {
+    let config = r#"
+        [[custom_command]]
+        label = "Disk"
+        url_endpoint = "disk"
+        run_cmd = ["true"]
+        kind = "status"
+        status_interval_secs = 1
+
+        [[custom_command]]
+        label = "Say hi"
+        url_endpoint = "say-hi"
+        run_cmd = ["echo", "hi"]
+    "#;
+    let server = TestServer::new(config, FakeZfsDriver::new());
+
+    let mut client = EventsClient::connect(&server).await;
+
+    match client.next_event().await {
+        ServerEvent::StatusChanged { status } => {
+            assert_eq!(status.endpoint, "disk");
+            assert!(status.is_ok());
+        }
+        e => panic!("Unexpected event: {e:?}"),
+    }
+
+    // The checks are shared by all the clients
+    let statuses: CommandStatuses = server.get("/command-statuses").await;
+    let [status] = statuses.commands.as_slice() else {
+        panic!("Only the status command is expected: {statuses:?}");
+    };
+    assert_eq!(status.endpoint, "disk");
+    assert!(status.is_ok());
+
+    // Running a status command directly is neither announced nor kept in the history
+    let _: RunCommandOutput = server.post("/custom-commands/disk", json!({}), &[]).await;
+    let _: RunCommandOutput = server.post("/custom-commands/say-hi", json!({}), &[]).await;
+    let started = loop {
+        match client.next_event().await {
+            ServerEvent::StatusChanged { .. } => continue,
+            e => break e,
+        }
+    };
+    assert_eq!(
+        started,
+        ServerEvent::CommandStarted {
+            endpoint: "say-hi".to_string()
+        }
+    );
+
+    let history: CommandHistory = server.get("/history/disk").await;
+    assert!(history.runs.is_empty());
+}
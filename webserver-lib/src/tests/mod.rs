@@ -0,0 +1,99 @@
+mod boot;
+mod custom_commands;
+mod events;
+mod zfs;
+
+use std::str::FromStr;
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use serde::de::DeserializeOwned;
+use tower::ServiceExt;
+
+use crate::{
+    backend::{live::LiveExecutionBackend, zfs_driver::fake::FakeZfsDriver},
+    make_router,
+    run_options::config::ApiServerConfig,
+};
+
+/// A server with a fake ZFS driver, where requests are sent directly to the router
+#[derive(Clone)]
+struct TestServer {
+    router: Router,
+    driver: FakeZfsDriver,
+}
+
+impl TestServer {
+    fn new(config: &str, driver: FakeZfsDriver) -> Self {
+        let config = ApiServerConfig::from_str(config).unwrap();
+        let backend = LiveExecutionBackend::new_with_driver(config.clone(), driver.clone());
+
+        Self {
+            router: make_router(Some(config), backend),
+            driver,
+        }
+    }
+
+    fn driver(&self) -> &FakeZfsDriver {
+        &self.driver
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<serde_json::Value>,
+        headers: &[(&str, &str)],
+    ) -> (StatusCode, Vec<u8>) {
+        let request = headers
+            .iter()
+            .fold(Request::builder().method(method).uri(uri), |req, (k, v)| {
+                req.header(*k, *v)
+            });
+
+        let request = match body {
+            Some(body) => request
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (status, body.to_vec())
+    }
+
+    async fn get<T: DeserializeOwned>(&self, uri: &str) -> T {
+        let (status, body) = self.request(Method::GET, uri, None, &[]).await;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        uri: &str,
+        body: serde_json::Value,
+        headers: &[(&str, &str)],
+    ) -> T {
+        let (status, body) = self.request(Method::POST, uri, Some(body), headers).await;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    async fn post_status(
+        &self,
+        uri: &str,
+        body: serde_json::Value,
+        headers: &[(&str, &str)],
+    ) -> StatusCode {
+        self.request(Method::POST, uri, Some(body), headers).await.0
+    }
+}
@@ -0,0 +1,603 @@
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use common::types::{
+    AvailableCustomCommands, CommandStopReason, DatasetFullMountState, DatasetMountOptions,
+    DatasetMountedResponse, DatasetsFullMountState, HelloResponse, ImportablePools,
+    JobCancelledResponse, JobStartedResponse, KeyLoadedResponse, PassphraseVerifiedResponse,
+    PoolImportOptions, PoolImportedResponse, UnlockGroupResponse, UnlockGroups,
+};
+use serde_json::json;
+
+use crate::backend::zfs_driver::fake::FakeZfsDriver;
+
+use super::TestServer;
+
+fn driver() -> FakeZfsDriver {
+    FakeZfsDriver::new()
+        .with_encryption_root("tank/secure", "secure-pass")
+        .with_child_dataset("tank/secure/docker", "tank/secure")
+        .with_encryption_root("tank/private", "private-pass")
+}
+
+async fn dataset_state(server: &TestServer, dataset_name: &str) -> DatasetFullMountState {
+    server
+        .post(
+            "/zfs/encrypted-dataset-state",
+            json!({ "dataset_name": dataset_name }),
+            &[],
+        )
+        .await
+}
+
+#[tokio::test]
+async fn list_datasets_hides_blacklisted() {
+    let server = TestServer::new(r#"blacklisted_zfs_datasets = ["tank/private"]"#, driver());
+
+    let states: DatasetsFullMountState = server.get("/zfs/encrypted-datasets-state").await;
+    assert_eq!(
+        states.states.keys().collect::<Vec<_>>(),
+        vec!["tank/secure", "tank/secure/docker"]
+    );
+    assert!(states
+        .states
+        .values()
+        .all(|s| !s.key_loaded && !s.is_mounted));
+
+    let status = server
+        .post_status(
+            "/zfs/encrypted-dataset-state",
+            json!({ "dataset_name": "tank/private" }),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let status = server
+        .post_status(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/private" }),
+            &[("Authorization", "private-pass")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(server.driver().load_key_calls(), 0);
+}
+
+#[tokio::test]
+async fn load_key_and_mount() {
+    let server = TestServer::new("", driver());
+
+    // No passphrase
+    let status = server
+        .post_status(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure" }),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Mounting before the key is loaded
+    let status = server
+        .post_status(
+            "/zfs/mount-dataset",
+            json!({ "dataset_name": "tank/secure" }),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+
+    // Wrong passphrase
+    let status = server
+        .post_status(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure" }),
+            &[("Authorization", "wrong-pass")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(!dataset_state(&server, "tank/secure").await.key_loaded);
+
+    let response: KeyLoadedResponse = server
+        .post(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure" }),
+            &[("Authorization", "secure-pass")],
+        )
+        .await;
+    assert!(response.key_loaded);
+
+    // The child dataset shares the key of its encryption root, other roots don't
+    assert!(
+        dataset_state(&server, "tank/secure/docker")
+            .await
+            .key_loaded
+    );
+    assert!(!dataset_state(&server, "tank/private").await.key_loaded);
+
+    let response: DatasetMountedResponse = server
+        .post(
+            "/zfs/mount-dataset",
+            json!({ "dataset_name": "tank/secure/docker" }),
+            &[],
+        )
+        .await;
+    assert!(response.is_mounted);
+
+    assert_eq!(
+        dataset_state(&server, "tank/secure/docker").await,
+        DatasetFullMountState {
+            dataset_name: "tank/secure/docker".to_string(),
+            key_loaded: true,
+            is_mounted: true,
+        }
+    );
+    assert!(!dataset_state(&server, "tank/secure").await.is_mounted);
+
+    // Loading an already loaded key doesn't call the driver again
+    let _: KeyLoadedResponse = server
+        .post(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure/docker" }),
+            &[("Authorization", "anything")],
+        )
+        .await;
+    assert_eq!(server.driver().load_key_calls(), 2);
+}
+
+#[tokio::test]
+async fn verify_passphrase_does_not_load_key() {
+    let server = TestServer::new(r#"blacklisted_zfs_datasets = ["tank/private"]"#, driver());
+
+    let status = server
+        .post_status(
+            "/zfs/verify-passphrase",
+            json!({ "dataset_name": "tank/secure" }),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    for (passphrase, expected) in [("wrong-pass", false), ("secure-pass", true)] {
+        let response: PassphraseVerifiedResponse = server
+            .post(
+                "/zfs/verify-passphrase",
+                json!({ "dataset_name": "tank/secure/docker" }),
+                &[("Authorization", passphrase)],
+            )
+            .await;
+        assert_eq!(response.passphrase_correct, expected);
+    }
+    assert!(!dataset_state(&server, "tank/secure").await.key_loaded);
+    assert_eq!(server.driver().load_key_calls(), 0);
+
+    let status = server
+        .post_status(
+            "/zfs/verify-passphrase",
+            json!({ "dataset_name": "tank/private" }),
+            &[("Authorization", "private-pass")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn unknown_dataset() {
+    let server = TestServer::new("", driver());
+
+    let status = server
+        .post_status(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/nothing" }),
+            &[("Authorization", "pass")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let status = server
+        .post_status(
+            "/zfs/mount-dataset",
+            json!({ "dataset_name": "tank/nothing" }),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn zfs_disabled() {
+    let server = TestServer::new("zfs_enabled = false", driver());
+
+    let states: DatasetsFullMountState = server.get("/zfs/encrypted-datasets-state").await;
+    assert!(states.states.is_empty());
+
+    let pools: ImportablePools = server.get("/zfs/importable-pools").await;
+    assert!(pools.pools.is_empty());
+
+    let status = server
+        .post_status(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure" }),
+            &[("Authorization", "secure-pass")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn import_pool() {
+    let driver = driver()
+        .with_encryption_root("backup/enc", "backup-pass")
+        .with_exported_pool("backup");
+    let server = TestServer::new("", driver);
+
+    // The datasets of a pool that isn't imported are not visible
+    let states: DatasetsFullMountState = server.get("/zfs/encrypted-datasets-state").await;
+    assert!(!states.states.contains_key("backup/enc"));
+
+    let pools: ImportablePools = server.get("/zfs/importable-pools").await;
+    assert_eq!(pools.pools.keys().collect::<Vec<_>>(), vec!["backup"]);
+
+    let status = server
+        .post_status("/zfs/import-pool", json!({ "pool_name": "not-there" }), &[])
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let response: PoolImportedResponse = server
+        .post(
+            "/zfs/import-pool",
+            json!({ "pool_name": "backup", "options": { "readonly": true } }),
+            &[],
+        )
+        .await;
+    assert!(response.is_imported);
+    assert_eq!(
+        server.driver().imports(),
+        vec![(
+            "backup".to_string(),
+            PoolImportOptions {
+                readonly: true,
+                altroot: None,
+            }
+        )]
+    );
+
+    let pools: ImportablePools = server.get("/zfs/importable-pools").await;
+    assert!(pools.pools.is_empty());
+
+    let states: DatasetsFullMountState = server.get("/zfs/encrypted-datasets-state").await;
+    assert!(states.states.contains_key("backup/enc"));
+}
+
+#[tokio::test]
+async fn mount_with_options() {
+    let config = r#"
+        allowed_mountpoint_prefixes = ["/mnt/inspect"]
+
+        [[on_mounted]]
+        dataset = "tank/*"
+        run_cmd = ["echo", "mounted"]
+    "#;
+    let server = TestServer::new(config, driver());
+
+    let _: KeyLoadedResponse = server
+        .post(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure" }),
+            &[("Authorization", "secure-pass")],
+        )
+        .await;
+
+    for (mountpoint, expected_status) in [
+        ("/mnt/other", StatusCode::UNAUTHORIZED),
+        ("/mnt/inspect/../../etc", StatusCode::UNPROCESSABLE_ENTITY),
+        ("mnt/inspect", StatusCode::UNPROCESSABLE_ENTITY),
+    ] {
+        let status = server
+            .post_status(
+                "/zfs/mount-dataset",
+                json!({ "dataset_name": "tank/secure", "options": { "mountpoint": mountpoint } }),
+                &[],
+            )
+            .await;
+        assert_eq!(status, expected_status, "{mountpoint}");
+    }
+    assert!(!dataset_state(&server, "tank/secure").await.is_mounted);
+
+    let options = DatasetMountOptions {
+        readonly: true,
+        mountpoint: Some("/mnt/inspect/secure".to_string()),
+    };
+    let response: DatasetMountedResponse = server
+        .post(
+            "/zfs/mount-dataset",
+            json!({ "dataset_name": "tank/secure", "options": options }),
+            &[],
+        )
+        .await;
+    assert!(response.is_mounted);
+    // Hooks don't run for temporary mounts
+    assert!(response.hooks.is_empty());
+    assert_eq!(
+        server.driver().mounts(),
+        vec![("tank/secure".to_string(), options)]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn slow_load_key_does_not_block_other_requests() {
+    let server = TestServer::new("", driver().with_load_key_delay(Duration::from_millis(500)));
+
+    let load_key = |dataset_name: &'static str, passphrase: &'static str| {
+        let server = server.clone();
+        tokio::spawn(async move {
+            server
+                .request(
+                    Method::POST,
+                    "/zfs/load-key",
+                    Some(json!({ "dataset_name": dataset_name })),
+                    &[("Authorization", passphrase)],
+                )
+                .await
+                .0
+        })
+    };
+
+    let same_dataset_1 = load_key("tank/secure", "secure-pass");
+    let same_dataset_2 = load_key("tank/secure", "secure-pass");
+    let other_dataset = load_key("tank/private", "private-pass");
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let _: HelloResponse = tokio::time::timeout(Duration::from_millis(200), server.get("/hello"))
+        .await
+        .unwrap();
+
+    assert_eq!(same_dataset_1.await.unwrap(), StatusCode::OK);
+    assert_eq!(same_dataset_2.await.unwrap(), StatusCode::OK);
+    assert_eq!(other_dataset.await.unwrap(), StatusCode::OK);
+
+    // Calls to the same dataset are serialized, so the second call finds the key already loaded
+    assert_eq!(server.driver().load_key_calls(), 2);
+}
+
+#[tokio::test]
+async fn hooks_run_after_key_loaded_and_mounted() {
+    let config = r#"
+        [[custom_command]]
+        label = "Start docker"
+        url_endpoint = "docker-start"
+        run_cmd = ["echo", "docker started"]
+
+        [[on_key_loaded]]
+        dataset = "tank/*"
+        run_cmd = [["echo", "key loaded"], ["rev"]]
+        label = "Reversed message"
+
+        [[on_mounted]]
+        dataset = "tank/secure/docker"
+        custom_command = "docker-start"
+
+        [[on_mounted]]
+        dataset = "tank/private"
+        run_cmd = ["false"]
+    "#;
+    let server = TestServer::new(config, driver());
+
+    let response: KeyLoadedResponse = server
+        .post(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure" }),
+            &[("Authorization", "secure-pass")],
+        )
+        .await;
+    assert_eq!(response.hooks.len(), 1);
+    assert_eq!(response.hooks[0].label, "Reversed message");
+    assert_eq!(response.hooks[0].output.stdout.trim(), "dedaol yek");
+
+    // The key is already loaded, so no hooks run
+    let response: KeyLoadedResponse = server
+        .post(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure/docker" }),
+            &[("Authorization", "secure-pass")],
+        )
+        .await;
+    assert!(response.hooks.is_empty());
+
+    let response: DatasetMountedResponse = server
+        .post(
+            "/zfs/mount-dataset",
+            json!({ "dataset_name": "tank/secure/docker" }),
+            &[],
+        )
+        .await;
+    assert_eq!(response.hooks.len(), 1);
+    assert_eq!(response.hooks[0].label, "Start docker");
+    assert_eq!(response.hooks[0].output.stdout.trim(), "docker started");
+    assert_eq!(response.hooks[0].output.error_code, 0);
+
+    let response: DatasetMountedResponse = server
+        .post(
+            "/zfs/mount-dataset",
+            json!({ "dataset_name": "tank/secure/docker" }),
+            &[],
+        )
+        .await;
+    assert!(response.hooks.is_empty());
+
+    // A failing hook doesn't fail the mount
+    let _: KeyLoadedResponse = server
+        .post(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/private" }),
+            &[("Authorization", "private-pass")],
+        )
+        .await;
+    let response: DatasetMountedResponse = server
+        .post(
+            "/zfs/mount-dataset",
+            json!({ "dataset_name": "tank/private" }),
+            &[],
+        )
+        .await;
+    assert!(response.is_mounted);
+    assert_eq!(response.hooks[0].label, "false");
+    assert_ne!(response.hooks[0].output.error_code, 0);
+}
+
+#[tokio::test]
+async fn hooks_run_their_commands_within_the_limits() {
+    let config = r#"
+        [[custom_command]]
+        label = "Start docker"
+        url_endpoint = "docker-start"
+        run_cmd = ["echo", "docker started"]
+        exclusive_group = "docker"
+        confirm = { message = "Start docker?" }
+
+        [[custom_command]]
+        label = "Stop docker"
+        url_endpoint = "docker-stop"
+        run_cmd = ["sleep", "30"]
+        exclusive_group = "docker"
+
+        [[on_mounted]]
+        dataset = "tank/secure/docker"
+        custom_command = "docker-start"
+    "#;
+    let server = TestServer::new(config, driver());
+
+    let _: KeyLoadedResponse = server
+        .post(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure" }),
+            &[("Authorization", "secure-pass")],
+        )
+        .await;
+
+    let stop: JobStartedResponse = server
+        .post("/custom-commands/docker-stop/start", json!({}), &[])
+        .await;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let commands: AvailableCustomCommands = server.get("/custom-commands-list").await;
+            if commands.commands.iter().any(|c| c.busy) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    // The hook doesn't run at the same time as a command of its exclusive group
+    let response: DatasetMountedResponse = server
+        .post(
+            "/zfs/mount-dataset",
+            json!({ "dataset_name": "tank/secure/docker" }),
+            &[],
+        )
+        .await;
+    assert!(response.is_mounted);
+    assert_eq!(response.hooks.len(), 1);
+    assert_ne!(response.hooks[0].output.error_code, 0);
+    assert!(response.hooks[0].output.stderr.contains("can't run now"));
+
+    let _: JobCancelledResponse = server
+        .post(&format!("/jobs/{}/cancel", stop.job_id), json!({}), &[])
+        .await;
+}
+
+#[tokio::test]
+async fn hooks_are_killed_after_the_default_timeout() {
+    let config = r#"
+        default_command_timeout_secs = 1
+
+        [[on_key_loaded]]
+        dataset = "tank/*"
+        run_cmd = ["sleep", "30"]
+    "#;
+    let server = TestServer::new(config, driver());
+
+    let response: KeyLoadedResponse = tokio::time::timeout(
+        Duration::from_secs(5),
+        server.post(
+            "/zfs/load-key",
+            json!({ "dataset_name": "tank/secure" }),
+            &[("Authorization", "secure-pass")],
+        ),
+    )
+    .await
+    .expect("The hook was not killed");
+    assert!(response.key_loaded);
+    assert_eq!(
+        response.hooks[0].output.stopped,
+        Some(CommandStopReason::TimedOut)
+    );
+}
+
+#[tokio::test]
+async fn unlock_group_with_one_passphrase() {
+    let config = r#"
+        blacklisted_zfs_datasets = ["tank/hidden"]
+
+        [[unlock_group]]
+        name = "main"
+        datasets = ["tank/secure", "tank/shared", "tank/private", "tank/hidden"]
+        mount_after = true
+    "#;
+    let driver = driver()
+        .with_encryption_root("tank/shared", "secure-pass")
+        .with_encryption_root("tank/hidden", "secure-pass");
+    let server = TestServer::new(config, driver);
+
+    let groups: UnlockGroups = server.get("/zfs/unlock-groups").await;
+    assert_eq!(groups.groups.len(), 1);
+    assert_eq!(
+        groups.groups[0].datasets,
+        vec!["tank/secure", "tank/shared", "tank/private"]
+    );
+
+    let status = server
+        .post_status("/zfs/unlock-group", json!({ "group_name": "main" }), &[])
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let status = server
+        .post_status(
+            "/zfs/unlock-group",
+            json!({ "group_name": "nothing" }),
+            &[("Authorization", "secure-pass")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let response: UnlockGroupResponse = server
+        .post(
+            "/zfs/unlock-group",
+            json!({ "group_name": "main" }),
+            &[("Authorization", "secure-pass")],
+        )
+        .await;
+    assert_eq!(
+        response.outcomes.keys().collect::<Vec<_>>(),
+        vec!["tank/private", "tank/secure", "tank/shared"]
+    );
+
+    for ds in ["tank/secure", "tank/shared"] {
+        let outcome = &response.outcomes[ds];
+        assert!(outcome.key_loaded && outcome.is_mounted);
+        assert_eq!(outcome.error, None);
+    }
+
+    // A member with a different passphrase fails alone
+    let outcome = &response.outcomes["tank/private"];
+    assert!(!outcome.key_loaded && !outcome.is_mounted);
+    assert!(outcome.error.is_some());
+    assert!(!dataset_state(&server, "tank/private").await.key_loaded);
+
+    // Blacklisted datasets are never touched
+    assert_eq!(server.driver().load_key_calls(), 3);
+}
@@ -1,77 +1,154 @@
-use std::sync::Arc;
-
 use axum::{
     extract::State,
     response::IntoResponse,
     routing::{get, post},
     Json, Router,
 };
-use common::types::DatasetBody;
+use common::types::{DatasetBody, MountDatasetBody, PoolImportBody, UnlockGroupBody};
 use hyper::HeaderMap;
-use tokio::sync::Mutex;
 
 use crate::{
     backend::traits::{ExecutionBackend, ExtraRequestErrors},
-    state::ServerState,
     StateType, ZFS_DIR,
 };
 
+/// Passphrases are sent in the Authorization header, where `name` is the dataset or group they unlock
+fn passphrase_from_headers<'h, B: ExecutionBackend>(
+    headers: &'h HeaderMap,
+    name: &str,
+) -> Result<&'h str, <B as ExecutionBackend>::Error> {
+    let passphrase = match headers.get("Authorization") {
+        Some(pp) => pp,
+        None => return Err(B::Error::make_error_passphrase_missing(name)),
+    };
+
+    passphrase
+        .to_str()
+        .map_err(|e| B::Error::make_error_passphrase_non_printable(e, name))
+}
+
 async fn mount_dataset<B: ExecutionBackend>(
-    State(state): State<Arc<Mutex<ServerState<B>>>>,
-    json_body: Json<DatasetBody>,
+    State(state): State<StateType<B>>,
+    json_body: Json<MountDatasetBody>,
 ) -> Result<impl IntoResponse, <B as ExecutionBackend>::Error> {
-    let state = &*state.lock().await;
-
     let dataset_name = &json_body.dataset_name;
 
-    let result = state.backend.zfs_mount_dataset(dataset_name)?;
+    let result = state
+        .backend
+        .zfs_mount_dataset(dataset_name, &json_body.options)
+        .await?;
+
+    state.events.datasets_may_have_changed();
 
     Ok(Json::from(result))
 }
 
 async fn load_key<B: ExecutionBackend>(
-    State(state): State<Arc<Mutex<ServerState<B>>>>,
+    State(state): State<StateType<B>>,
     headers: HeaderMap,
     json_body: Json<DatasetBody>,
 ) -> Result<impl IntoResponse, <B as ExecutionBackend>::Error> {
     let dataset_name = &json_body.dataset_name;
 
-    let state = &*state.lock().await;
+    let passphrase = passphrase_from_headers::<B>(&headers, dataset_name)?;
 
-    let passphrase = match headers.get("Authorization") {
-        Some(pp) => pp,
-        None => return Err(B::Error::make_error_passphrase_missing(dataset_name)),
-    };
+    let result = state.backend.zfs_load_key(dataset_name, passphrase).await?;
 
-    let passphrase = passphrase
-        .to_str()
-        .map_err(|e| B::Error::make_error_passphrase_non_printable(e, dataset_name.clone()))?;
+    state.boot.keys_provided();
+    state.events.datasets_may_have_changed();
 
-    let result = state.backend.zfs_load_key(dataset_name, passphrase)?;
+    Ok(Json::from(result))
+}
+
+/// Checks the passphrase of a dataset without loading its key
+async fn verify_passphrase<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+    headers: HeaderMap,
+    json_body: Json<DatasetBody>,
+) -> Result<impl IntoResponse, <B as ExecutionBackend>::Error> {
+    let dataset_name = &json_body.dataset_name;
+
+    let passphrase = passphrase_from_headers::<B>(&headers, dataset_name)?;
+
+    let result = state
+        .backend
+        .zfs_verify_passphrase(dataset_name, passphrase)
+        .await?;
 
     Ok(Json::from(result))
 }
 
 /// Returns a list of the encrypted datasets, and whether they're mounted, and whether their keys are loaded.
 async fn encrypted_datasets_state<B: ExecutionBackend>(
-    State(state): State<Arc<Mutex<ServerState<B>>>>,
+    State(state): State<StateType<B>>,
 ) -> Result<impl IntoResponse, <B as ExecutionBackend>::Error> {
-    let state = &*state.lock().await;
-
-    let result = state.backend.zfs_encrypted_datasets_state()?;
+    let result = state.backend.zfs_encrypted_datasets_state().await?;
 
     Ok(Json::from(result))
 }
 
 /// Returns the given encrypted dataset state, and whether it's mounted, and whether their keys is loaded.
 async fn encrypted_dataset_state<B: ExecutionBackend>(
-    State(state): State<Arc<Mutex<ServerState<B>>>>,
+    State(state): State<StateType<B>>,
     json_body: Json<DatasetBody>,
 ) -> Result<impl IntoResponse, <B as ExecutionBackend>::Error> {
-    let state = &state.lock().await;
-
     let dataset_name = &json_body.dataset_name;
-    let result = state.backend.zfs_encrypted_dataset_state(dataset_name)?;
+    let result = state
+        .backend
+        .zfs_encrypted_dataset_state(dataset_name)
+        .await?;
+
+    Ok(Json::from(result))
+}
+
+/// Returns the pools that are not imported, but can be imported
+async fn importable_pools<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+) -> Result<impl IntoResponse, <B as ExecutionBackend>::Error> {
+    let result = state.backend.zfs_importable_pools().await?;
+
+    Ok(Json::from(result))
+}
+
+async fn import_pool<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+    json_body: Json<PoolImportBody>,
+) -> Result<impl IntoResponse, <B as ExecutionBackend>::Error> {
+    let result = state
+        .backend
+        .zfs_import_pool(&json_body.pool_name, &json_body.options)
+        .await?;
+
+    state.events.datasets_may_have_changed();
+
+    Ok(Json::from(result))
+}
+
+async fn unlock_groups<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+) -> Result<impl IntoResponse, <B as ExecutionBackend>::Error> {
+    let result = state.backend.zfs_unlock_groups().await?;
+
+    Ok(Json::from(result))
+}
+
+/// Loads the keys of all the datasets in a group with one passphrase, and returns the outcome of each dataset
+async fn unlock_group<B: ExecutionBackend>(
+    State(state): State<StateType<B>>,
+    headers: HeaderMap,
+    json_body: Json<UnlockGroupBody>,
+) -> Result<impl IntoResponse, <B as ExecutionBackend>::Error> {
+    let group_name = &json_body.group_name;
+
+    let passphrase = passphrase_from_headers::<B>(&headers, group_name)?;
+
+    let result = state
+        .backend
+        .zfs_unlock_group(group_name, passphrase)
+        .await?;
+
+    state.boot.keys_provided();
+    state.events.datasets_may_have_changed();
 
     Ok(Json::from(result))
 }
@@ -81,7 +158,12 @@ pub fn zfs_routes<B: ExecutionBackend>() -> Router<StateType<B>> {
         .route("/encrypted-datasets-state", get(encrypted_datasets_state))
         .route("/encrypted-dataset-state", post(encrypted_dataset_state))
         .route("/load-key", post(load_key))
-        .route("/mount-dataset", post(mount_dataset));
+        .route("/verify-passphrase", post(verify_passphrase))
+        .route("/mount-dataset", post(mount_dataset))
+        .route("/importable-pools", get(importable_pools))
+        .route("/import-pool", post(import_pool))
+        .route("/unlock-groups", get(unlock_groups))
+        .route("/unlock-group", post(unlock_group));
 
     Router::new().nest(ZFS_DIR, inner_routes)
 }